@@ -0,0 +1,526 @@
+/* ==============================================
+ * File:        src/abi.rs
+ * Author:      USDTG GROUP TECHNOLOGY LLC
+ * Developer:   Irfan Gedik
+ * Created Date: 2025-12-22
+ * Last Update:  2025-12-22
+ * Version:     1.0.0
+ *
+ * Description:
+ *   Contract ABI Layer
+ *
+ *   Converts JSON call arguments into WASM values and WASM results
+ *   back into typed JSON. Byte and string values travel through the
+ *   contract's linear memory:
+ *     - the contract exports `memory` and `alloc(len: i32) -> i32`
+ *     - a bytes/string parameter is lowered to `(ptr: i32, len: i32)`
+ *     - a bytes/string result is returned as `i64` = `(ptr << 32) | len`
+ *
+ *   Interface metadata (names, types, mutability, events) is embedded
+ *   in the module as a JSON custom section named `qverse.abi`.
+ *
+ *   Event encoding: topic0 = sha256(name); each indexed field takes the
+ *   next 32-byte topic (numbers big-endian in the last 8 bytes as i64 /
+ *   f64 bits, strings and bytes as their sha256). Non-indexed fields are
+ *   packed into data in ABI order: numbers little-endian at their width,
+ *   strings and bytes as a u32 little-endian length followed by the bytes.
+ *
+ * License:
+ *   MIT License
+ * ============================================== */
+
+use crate::vm::VmError;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use wasmer::wasmparser::{Parser, Payload};
+use wasmer::{FunctionType, Type, Value};
+
+/// Name of the custom section carrying the contract ABI
+pub const ABI_SECTION_NAME: &str = "qverse.abi";
+
+/// Current ABI metadata format version
+pub const ABI_VERSION: u32 = 1;
+
+/// Parameter / return types understood by the ABI
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AbiType {
+    I32,
+    I64,
+    F32,
+    F64,
+    Bytes,
+    String,
+}
+
+impl AbiType {
+    /// WASM types this ABI type occupies as a parameter
+    fn lowered_params(&self) -> Vec<Type> {
+        match self {
+            AbiType::I32 => vec![Type::I32],
+            AbiType::I64 => vec![Type::I64],
+            AbiType::F32 => vec![Type::F32],
+            AbiType::F64 => vec![Type::F64],
+            AbiType::Bytes | AbiType::String => vec![Type::I32, Type::I32],
+        }
+    }
+
+    /// WASM type this ABI type occupies as a result
+    fn lowered_result(&self) -> Type {
+        match self {
+            AbiType::I32 => Type::I32,
+            AbiType::I64 | AbiType::Bytes | AbiType::String => Type::I64,
+            AbiType::F32 => Type::F32,
+            AbiType::F64 => Type::F64,
+        }
+    }
+
+    fn from_wasm(ty: &Type) -> Result<Self, VmError> {
+        match ty {
+            Type::I32 => Ok(AbiType::I32),
+            Type::I64 => Ok(AbiType::I64),
+            Type::F32 => Ok(AbiType::F32),
+            Type::F64 => Ok(AbiType::F64),
+            other => Err(format!("Unsupported WASM type in export signature: {:?}", other).into()),
+        }
+    }
+}
+
+impl std::fmt::Display for AbiType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            AbiType::I32 => "i32",
+            AbiType::I64 => "i64",
+            AbiType::F32 => "f32",
+            AbiType::F64 => "f64",
+            AbiType::Bytes => "bytes",
+            AbiType::String => "string",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Typed signature of an exported contract function
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct FunctionSignature {
+    pub name: String,
+    pub params: Vec<AbiType>,
+    pub results: Vec<AbiType>,
+}
+
+impl FunctionSignature {
+    /// Reads a numeric-only signature straight from the export's WASM type
+    pub fn from_wasm(name: &str, ty: &FunctionType) -> Result<Self, VmError> {
+        Ok(Self {
+            name: name.to_string(),
+            params: ty.params().iter().map(AbiType::from_wasm).collect::<Result<_, _>>()?,
+            results: ty.results().iter().map(AbiType::from_wasm).collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Checks that this signature lowers exactly to the export's WASM type
+    pub fn check_against(&self, ty: &FunctionType) -> Result<(), VmError> {
+        let params: Vec<Type> = self.params.iter().flat_map(|p| p.lowered_params()).collect();
+        let results: Vec<Type> = self.results.iter().map(|r| r.lowered_result()).collect();
+
+        if params.as_slice() != ty.params() || results.as_slice() != ty.results() {
+            return Err(format!(
+                "Signature of '{}' does not match the module export (expected {:?} -> {:?}, found {:?} -> {:?})",
+                self.name, params, results, ty.params(), ty.results()
+            ).into());
+        }
+        Ok(())
+    }
+}
+
+/// Whether a function may change contract state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Mutability {
+    /// Read-only; must never write storage
+    View,
+    /// May write storage
+    #[default]
+    Mutable,
+    /// May write storage and receive tokens with the call
+    Payable,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct AbiParam {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: AbiType,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct AbiFunction {
+    pub name: String,
+    #[serde(default)]
+    pub params: Vec<AbiParam>,
+    #[serde(default)]
+    pub returns: Vec<AbiType>,
+    #[serde(default)]
+    pub mutability: Mutability,
+}
+
+impl AbiFunction {
+    pub fn signature(&self) -> FunctionSignature {
+        FunctionSignature {
+            name: self.name.clone(),
+            params: self.params.iter().map(|p| p.ty).collect(),
+            results: self.returns.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct AbiEventField {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: AbiType,
+    /// Indexed fields are emitted as topics, the rest as data
+    #[serde(default)]
+    pub indexed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct AbiEvent {
+    pub name: String,
+    #[serde(default)]
+    pub fields: Vec<AbiEventField>,
+}
+
+/// Machine-readable contract interface (`qverse.abi` custom section)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct ContractAbi {
+    pub version: u32,
+    #[serde(default)]
+    pub functions: Vec<AbiFunction>,
+    #[serde(default)]
+    pub events: Vec<AbiEvent>,
+}
+
+impl ContractAbi {
+    /// Reads the ABI section from a WASM binary; `None` when it has none
+    pub fn from_wasm(wasm_bytes: &[u8]) -> Result<Option<Self>, VmError> {
+        for payload in Parser::new(0).parse_all(wasm_bytes) {
+            if let Payload::CustomSection(section) = payload?
+                && section.name() == ABI_SECTION_NAME
+            {
+                return Self::from_json(section.data()).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn from_json(data: &[u8]) -> Result<Self, VmError> {
+        let abi: ContractAbi = serde_json::from_slice(data)
+            .map_err(|e| format!("Invalid {} section: {}", ABI_SECTION_NAME, e))?;
+        abi.validate()?;
+        Ok(abi)
+    }
+
+    fn validate(&self) -> Result<(), VmError> {
+        if self.version != ABI_VERSION {
+            return Err(format!("Unsupported ABI version {} (expected {})", self.version, ABI_VERSION).into());
+        }
+
+        let mut seen = HashSet::new();
+        for function in &self.functions {
+            if !seen.insert(&function.name) {
+                return Err(format!("Duplicate function '{}' in ABI", function.name).into());
+            }
+        }
+
+        let mut seen = HashSet::new();
+        for event in &self.events {
+            if !seen.insert(&event.name) {
+                return Err(format!("Duplicate event '{}' in ABI", event.name).into());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn function(&self, name: &str) -> Option<&AbiFunction> {
+        self.functions.iter().find(|f| f.name == name)
+    }
+
+    pub fn event(&self, name: &str) -> Option<&AbiEvent> {
+        self.events.iter().find(|e| e.name == name)
+    }
+}
+
+/// Conventional topic0 for an ABI event: sha256 of its name (hex)
+pub fn event_topic(name: &str) -> String {
+    hex::encode(Sha256::digest(name.as_bytes()))
+}
+
+/// Decodes an emitted event into `{ field: value }` per the event encoding above.
+/// Indexed strings/bytes come back as their sha256 (hex); bytes data as hex.
+pub fn decode_event(event: &AbiEvent, topics: &[String], data: &str) -> Result<JsonValue, VmError> {
+    let data = hex::decode(data.trim_start_matches("0x")).map_err(|e| format!("Invalid event data: {}", e))?;
+    let mut cursor = 0usize;
+
+    let mut next_topic = 1;
+    let mut fields = serde_json::Map::new();
+    for field in &event.fields {
+        let value = if field.indexed {
+            let topic = topics.get(next_topic).ok_or_else(|| format!("Missing topic for '{}'", field.name))?;
+            next_topic += 1;
+            let word = hex::decode(topic).ok().filter(|t| t.len() == 32)
+                .ok_or_else(|| format!("Topic for '{}' is not 32 bytes of hex", field.name))?;
+            let last: [u8; 8] = word[24..].try_into().map_err(|_| "Invalid topic")?;
+            match field.ty {
+                AbiType::I32 | AbiType::I64 => JsonValue::from(i64::from_be_bytes(last)),
+                AbiType::F32 | AbiType::F64 => JsonValue::from(f64::from_be_bytes(last)),
+                AbiType::String | AbiType::Bytes => JsonValue::String(topic.clone()),
+            }
+        } else {
+            match field.ty {
+                AbiType::I32 => JsonValue::from(i32::from_le_bytes(take_array(&data, &mut cursor)?)),
+                AbiType::I64 => JsonValue::from(i64::from_le_bytes(take_array(&data, &mut cursor)?)),
+                AbiType::F32 => JsonValue::from(f32::from_le_bytes(take_array(&data, &mut cursor)?) as f64),
+                AbiType::F64 => JsonValue::from(f64::from_le_bytes(take_array(&data, &mut cursor)?)),
+                AbiType::String | AbiType::Bytes => {
+                    let len = u32::from_le_bytes(take_array(&data, &mut cursor)?) as usize;
+                    let bytes = take_bytes(&data, &mut cursor, len)?;
+                    if field.ty == AbiType::String {
+                        JsonValue::String(String::from_utf8(bytes.to_vec()).map_err(|_| "Event string is not UTF-8")?)
+                    } else {
+                        JsonValue::String(hex::encode(bytes))
+                    }
+                }
+            }
+        };
+        fields.insert(field.name.clone(), value);
+    }
+
+    Ok(JsonValue::Object(fields))
+}
+
+fn take_bytes<'a>(data: &'a [u8], cursor: &mut usize, n: usize) -> Result<&'a [u8], VmError> {
+    let bytes = data.get(*cursor..*cursor + n).ok_or("Event data too short")?;
+    *cursor += n;
+    Ok(bytes)
+}
+
+fn take_array<const N: usize>(data: &[u8], cursor: &mut usize) -> Result<[u8; N], VmError> {
+    let bytes = take_bytes(data, cursor, N)?;
+    bytes.try_into().map_err(|_| "Event data too short".into())
+}
+
+/// Access to a contract instance's linear memory
+pub trait LinearMemory {
+    /// Allocates inside the contract and copies `bytes` in, returning the pointer
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<i32, VmError>;
+    fn read_bytes(&mut self, ptr: u32, len: u32) -> Result<Vec<u8>, VmError>;
+}
+
+/// Validates JSON arguments against `sig` and lowers them to WASM values
+pub fn encode_args(
+    sig: &FunctionSignature,
+    args: &[JsonValue],
+    memory: &mut dyn LinearMemory,
+) -> Result<Vec<Value>, VmError> {
+    if args.len() != sig.params.len() {
+        return Err(format!(
+            "'{}' expects {} argument(s), got {}",
+            sig.name, sig.params.len(), args.len()
+        ).into());
+    }
+
+    let mut values = Vec::new();
+    for (index, (ty, arg)) in sig.params.iter().zip(args).enumerate() {
+        let mismatch = || -> VmError {
+            format!("Argument {} of '{}': expected {}, got {}", index, sig.name, ty, arg).into()
+        };
+
+        match ty {
+            AbiType::I32 => {
+                let v = arg.as_i64().and_then(|v| i32::try_from(v).ok()).ok_or_else(mismatch)?;
+                values.push(Value::I32(v));
+            }
+            AbiType::I64 => values.push(Value::I64(arg.as_i64().ok_or_else(mismatch)?)),
+            AbiType::F32 => values.push(Value::F32(arg.as_f64().ok_or_else(mismatch)? as f32)),
+            AbiType::F64 => values.push(Value::F64(arg.as_f64().ok_or_else(mismatch)?)),
+            AbiType::String => {
+                let s = arg.as_str().ok_or_else(mismatch)?;
+                values.extend(write_region(memory, s.as_bytes())?);
+            }
+            AbiType::Bytes => {
+                let bytes = json_to_bytes(arg).ok_or_else(mismatch)?;
+                values.extend(write_region(memory, &bytes)?);
+            }
+        }
+    }
+
+    Ok(values)
+}
+
+/// Lifts raw WASM results into JSON according to `sig`
+pub fn decode_results(
+    sig: &FunctionSignature,
+    values: &[Value],
+    memory: &mut dyn LinearMemory,
+) -> Result<Vec<JsonValue>, VmError> {
+    if values.len() != sig.results.len() {
+        return Err(format!(
+            "'{}' returned {} value(s), ABI declares {}",
+            sig.name, values.len(), sig.results.len()
+        ).into());
+    }
+
+    let mut results = Vec::new();
+    for (ty, value) in sig.results.iter().zip(values) {
+        let json = match (ty, value) {
+            (AbiType::I32, Value::I32(v)) => JsonValue::from(*v),
+            (AbiType::I64, Value::I64(v)) => JsonValue::from(*v),
+            (AbiType::F32, Value::F32(v)) => JsonValue::from(*v as f64),
+            (AbiType::F64, Value::F64(v)) => JsonValue::from(*v),
+            (AbiType::String, Value::I64(packed)) => {
+                let bytes = read_region(memory, *packed)?;
+                JsonValue::String(String::from_utf8(bytes).map_err(|_| "Contract returned invalid UTF-8")?)
+            }
+            (AbiType::Bytes, Value::I64(packed)) => JsonValue::String(hex::encode(read_region(memory, *packed)?)),
+            (ty, value) => return Err(format!("Result of '{}': expected {}, got {:?}", sig.name, ty, value).into()),
+        };
+        results.push(json);
+    }
+
+    Ok(results)
+}
+
+/// Accepts hex (with or without `0x`) or an array of byte values
+fn json_to_bytes(arg: &JsonValue) -> Option<Vec<u8>> {
+    match arg {
+        JsonValue::String(s) => hex::decode(s.trim_start_matches("0x")).ok(),
+        JsonValue::Array(items) => items
+            .iter()
+            .map(|v| v.as_u64().and_then(|b| u8::try_from(b).ok()))
+            .collect(),
+        _ => None,
+    }
+}
+
+fn write_region(memory: &mut dyn LinearMemory, bytes: &[u8]) -> Result<[Value; 2], VmError> {
+    let len = i32::try_from(bytes.len()).map_err(|_| "Argument too large for linear memory")?;
+    let ptr = memory.write_bytes(bytes)?;
+    Ok([Value::I32(ptr), Value::I32(len)])
+}
+
+fn read_region(memory: &mut dyn LinearMemory, packed: i64) -> Result<Vec<u8>, VmError> {
+    let ptr = (packed as u64 >> 32) as u32;
+    let len = packed as u64 as u32;
+    memory.read_bytes(ptr, len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bump allocator over a plain Vec
+    struct VecMemory(Vec<u8>);
+
+    impl LinearMemory for VecMemory {
+        fn write_bytes(&mut self, bytes: &[u8]) -> Result<i32, VmError> {
+            let ptr = self.0.len() as i32;
+            self.0.extend_from_slice(bytes);
+            Ok(ptr)
+        }
+
+        fn read_bytes(&mut self, ptr: u32, len: u32) -> Result<Vec<u8>, VmError> {
+            Ok(self.0[ptr as usize..(ptr + len) as usize].to_vec())
+        }
+    }
+
+    fn sig(params: Vec<AbiType>, results: Vec<AbiType>) -> FunctionSignature {
+        FunctionSignature { name: "f".to_string(), params, results }
+    }
+
+    #[test]
+    fn test_encode_numeric_and_string_args() {
+        let mut memory = VecMemory(vec![0; 8]);
+        let s = sig(vec![AbiType::I32, AbiType::String, AbiType::F64], vec![]);
+        let values = encode_args(&s, &[serde_json::json!(7), serde_json::json!("hi"), serde_json::json!(1.5)], &mut memory).unwrap();
+
+        assert_eq!(values, vec![Value::I32(7), Value::I32(8), Value::I32(2), Value::F64(1.5)]);
+        assert_eq!(&memory.0[8..], b"hi");
+    }
+
+    #[test]
+    fn test_encode_rejects_type_mismatch() {
+        let mut memory = VecMemory(vec![]);
+        let s = sig(vec![AbiType::I32], vec![]);
+        assert!(encode_args(&s, &[serde_json::json!("7")], &mut memory).is_err());
+        assert!(encode_args(&s, &[serde_json::json!(i64::MAX)], &mut memory).is_err());
+        assert!(encode_args(&s, &[], &mut memory).is_err());
+    }
+
+    #[test]
+    fn test_decode_packed_bytes_result() {
+        let mut memory = VecMemory(b"....abc".to_vec());
+        let s = sig(vec![], vec![AbiType::Bytes, AbiType::I64]);
+        let packed = (4i64 << 32) | 3;
+        let results = decode_results(&s, &[Value::I64(packed), Value::I64(-1)], &mut memory).unwrap();
+
+        assert_eq!(results, vec![serde_json::json!("616263"), serde_json::json!(-1)]);
+    }
+
+    #[test]
+    fn test_abi_from_custom_section() {
+        let json = br#"{"version":1,"functions":[{"name":"greet","params":[{"name":"who","type":"string"}],"returns":["string"],"mutability":"view"}]}"#;
+        // Minimal module: header + one custom section
+        let mut wasm = b"\0asm\x01\0\0\0".to_vec();
+        let mut section = vec![ABI_SECTION_NAME.len() as u8];
+        section.extend_from_slice(ABI_SECTION_NAME.as_bytes());
+        section.extend_from_slice(json);
+        wasm.push(0);
+        let mut len = section.len();
+        while len >= 0x80 {
+            wasm.push((len as u8 & 0x7f) | 0x80); // LEB128
+            len >>= 7;
+        }
+        wasm.push(len as u8);
+        wasm.extend(section);
+
+        let abi = ContractAbi::from_wasm(&wasm).unwrap().unwrap();
+        let greet = abi.function("greet").unwrap();
+        assert_eq!(greet.mutability, Mutability::View);
+        assert_eq!(greet.signature().params, vec![AbiType::String]);
+        assert_eq!(greet.signature().results, vec![AbiType::String]);
+        assert!(ContractAbi::from_wasm(b"\0asm\x01\0\0\0").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_decode_event() {
+        let event: AbiEvent = serde_json::from_value(serde_json::json!({
+            "name": "Transfer",
+            "fields": [
+                { "name": "to", "type": "string", "indexed": true },
+                { "name": "amount", "type": "i64" },
+                { "name": "memo", "type": "string" },
+                { "name": "nonce", "type": "i32", "indexed": true }
+            ]
+        })).unwrap();
+        let to_hash = hex::encode(Sha256::digest(b"alice"));
+        let mut nonce = [0u8; 32];
+        nonce[24..].copy_from_slice(&(-2i64).to_be_bytes());
+        let mut data = 500i64.to_le_bytes().to_vec();
+        data.extend(2u32.to_le_bytes());
+        data.extend(b"hi");
+
+        let topics = vec![event_topic("Transfer"), to_hash.clone(), hex::encode(nonce)];
+        let decoded = decode_event(&event, &topics, &hex::encode(data)).unwrap();
+        assert_eq!(decoded, serde_json::json!({ "to": to_hash, "amount": 500, "memo": "hi", "nonce": -2 }));
+    }
+
+    #[test]
+    fn test_signature_check() {
+        let ty = FunctionType::new(vec![Type::I32, Type::I32], vec![Type::I64]);
+        assert!(sig(vec![AbiType::String], vec![AbiType::Bytes]).check_against(&ty).is_ok());
+        assert!(sig(vec![AbiType::I64], vec![AbiType::I64]).check_against(&ty).is_err());
+    }
+}
@@ -36,6 +36,12 @@ pub struct QMind {
     transaction_history: HashMap<String, Vec<f64>>, 
 }
 
+impl Default for QMind {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl QMind {
     pub fn new() -> Self {
         Self {
@@ -50,7 +56,7 @@ impl QMind {
 
         // Kural 1: Anormal Miktar Kontrolü (Anomaly Detection)
         // Eğer kullanıcı ortalamasının 10 katı işlem yapıyorsa şüpheli.
-        let history = self.transaction_history.entry(sender.to_string()).or_insert(Vec::new());
+        let history = self.transaction_history.entry(sender.to_string()).or_default();
         if !history.is_empty() {
             let avg: f64 = history.iter().sum::<f64>() / history.len() as f64;
             if amount > avg * 10.0 {
@@ -0,0 +1,508 @@
+/* ==============================================
+ * File:        src/analysis.rs
+ * Author:      USDTG GROUP TECHNOLOGY LLC
+ * Developer:   Irfan Gedik
+ * Created Date: 2025-12-22
+ * Last Update:  2025-12-22
+ * Version:     1.0.0
+ *
+ * Description:
+ *   Static Analysis Engine
+ *
+ *   Builds a call graph over compiled contract bytecode (direct calls,
+ *   indirect calls through tables, host imports) and checks properties
+ *   on it: loops that only the gas meter can stop, exports that can
+ *   never return, and view functions that reach state-writing host calls.
+ *
+ * License:
+ *   MIT License
+ * ============================================== */
+
+use crate::abi::{ContractAbi, Mutability};
+use crate::host::HOST_MODULE;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use wasmer::wasmparser::{self, ElementItems, ExternalKind, Operator, Parser, Payload, TypeRef};
+
+pub const PROP_NO_UNBOUNDED_LOOPS: &str = "no_unbounded_loops";
+pub const PROP_NO_UNREACHABLE_EXPORTS: &str = "no_unreachable_exports";
+pub const PROP_VIEW_FUNCTIONS_READONLY: &str = "view_functions_readonly";
+
+pub const SUPPORTED_PROPERTIES: &[&str] = &[
+    PROP_NO_UNBOUNDED_LOOPS,
+    PROP_NO_UNREACHABLE_EXPORTS,
+    PROP_VIEW_FUNCTIONS_READONLY,
+];
+
+/// Host imports that change contract or ledger state
+const STATE_WRITING_HOST_FUNCTIONS: &[&str] = &["storage_write", "transfer"];
+
+/// Where a property fails
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct Counterexample {
+    pub function_index: u32,
+    pub function: String,
+    pub offset: usize, // byte offset in the module
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct CallGraphNode {
+    pub function_index: u32,
+    pub name: String,
+    pub imported: bool,
+    /// Reachable from an export, the start function or a table
+    pub reachable: bool,
+    pub calls: Vec<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    Call(u32, usize),
+    Trap(usize),
+    MayReturn,
+}
+
+#[derive(Debug, Default)]
+struct FunctionInfo {
+    calls: Vec<(u32, usize)>, // (callee, call site offset)
+    indirect_calls: Vec<usize>,
+    unbounded_loops: Vec<usize>,
+    /// Instructions that always run, up to the first possible return
+    straight_line: Vec<Step>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrameKind {
+    Function,
+    Block,
+    Loop,
+}
+
+struct Frame {
+    kind: FrameKind,
+    offset: usize,
+    /// An unconditional branch/return/trap at this level: the frame's `end` is not reached by fallthrough
+    dead_tail: bool,
+    /// Control can leave this frame other than through its `end` (only tracked for loops)
+    exits: bool,
+}
+
+/// Call graph and per-function facts of one module
+pub struct ModuleAnalysis {
+    imports: Vec<String>, // "module.name" of imported functions, by index
+    functions: Vec<FunctionInfo>, // defined functions, by index - imports.len()
+    exports: Vec<(String, u32)>,
+    roots: BTreeSet<u32>,
+    table_functions: BTreeSet<u32>,
+}
+
+impl ModuleAnalysis {
+    pub fn analyze(wasm_bytes: &[u8]) -> Result<Self, wasmparser::BinaryReaderError> {
+        wasmparser::validate(wasm_bytes)?;
+
+        let mut analysis = Self {
+            imports: Vec::new(),
+            functions: Vec::new(),
+            exports: Vec::new(),
+            roots: BTreeSet::new(),
+            table_functions: BTreeSet::new(),
+        };
+
+        for payload in Parser::new(0).parse_all(wasm_bytes) {
+            match payload? {
+                Payload::ImportSection(reader) => {
+                    for import in reader {
+                        let import = import?;
+                        if let TypeRef::Func(_) = import.ty {
+                            analysis.imports.push(format!("{}.{}", import.module, import.name));
+                        }
+                    }
+                }
+                Payload::ExportSection(reader) => {
+                    for export in reader {
+                        let export = export?;
+                        if export.kind == ExternalKind::Func {
+                            analysis.exports.push((export.name.to_string(), export.index));
+                            analysis.roots.insert(export.index);
+                        }
+                    }
+                }
+                Payload::StartSection { func, .. } => {
+                    analysis.roots.insert(func);
+                }
+                Payload::ElementSection(reader) => {
+                    for element in reader {
+                        match element?.items {
+                            ElementItems::Functions(functions) => {
+                                for function in functions {
+                                    analysis.table_functions.insert(function?);
+                                }
+                            }
+                            ElementItems::Expressions(_, exprs) => {
+                                for expr in exprs {
+                                    for op in expr?.get_operators_reader() {
+                                        if let Operator::RefFunc { function_index } = op? {
+                                            analysis.table_functions.insert(function_index);
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                Payload::CodeSectionEntry(body) => {
+                    let info = Self::analyze_body(&body, &mut analysis.table_functions)?;
+                    analysis.functions.push(info);
+                }
+                _ => {}
+            }
+        }
+
+        let table_functions = analysis.table_functions.clone();
+        analysis.roots.extend(table_functions);
+        Ok(analysis)
+    }
+
+    fn analyze_body(
+        body: &wasmparser::FunctionBody,
+        table_functions: &mut BTreeSet<u32>,
+    ) -> Result<FunctionInfo, wasmparser::BinaryReaderError> {
+        let mut info = FunctionInfo::default();
+        let mut frames = vec![Frame { kind: FrameKind::Function, offset: 0, dead_tail: false, exits: false }];
+        let mut straight_line_done = false;
+
+        for op in body.get_operators_reader()?.into_iter_with_offsets() {
+            let (op, offset) = op?;
+            let depth = frames.len() - 1;
+            // Dallanma hedefleri: göreli derinlik -> frame indeksi
+            let mut targets: Vec<u32> = Vec::new();
+            let mut unconditional = false;
+            let mut leaves_function = false;
+
+            match &op {
+                Operator::Block { .. } | Operator::If { .. } | Operator::Try { .. } => {
+                    frames.push(Frame { kind: FrameKind::Block, offset, dead_tail: false, exits: false });
+                }
+                Operator::Loop { .. } => {
+                    frames.push(Frame { kind: FrameKind::Loop, offset, dead_tail: false, exits: false });
+                }
+                Operator::End => {
+                    if let Some(frame) = frames.pop()
+                        && frame.kind == FrameKind::Loop && frame.dead_tail && !frame.exits
+                    {
+                        info.unbounded_loops.push(frame.offset);
+                    }
+                    if frames.is_empty() && !straight_line_done {
+                        info.straight_line.push(Step::MayReturn);
+                        straight_line_done = true;
+                    }
+                    continue;
+                }
+                Operator::Br { relative_depth } => {
+                    targets.push(*relative_depth);
+                    unconditional = true;
+                }
+                Operator::BrIf { relative_depth }
+                | Operator::BrOnNull { relative_depth }
+                | Operator::BrOnNonNull { relative_depth } => targets.push(*relative_depth),
+                Operator::BrTable { targets: table } => {
+                    for target in table.targets() {
+                        targets.push(target?);
+                    }
+                    targets.push(table.default());
+                    unconditional = true;
+                }
+                Operator::Return => {
+                    leaves_function = true;
+                    unconditional = true;
+                }
+                Operator::Unreachable | Operator::Throw { .. } | Operator::Rethrow { .. } => {
+                    if depth == 0 && !straight_line_done {
+                        info.straight_line.push(Step::Trap(offset));
+                        straight_line_done = true;
+                    }
+                    leaves_function = true;
+                    unconditional = true;
+                }
+                Operator::Call { function_index } => {
+                    info.calls.push((*function_index, offset));
+                    if depth == 0 && !straight_line_done {
+                        info.straight_line.push(Step::Call(*function_index, offset));
+                    }
+                }
+                Operator::ReturnCall { function_index } => {
+                    info.calls.push((*function_index, offset));
+                    if depth == 0 && !straight_line_done {
+                        info.straight_line.push(Step::Call(*function_index, offset));
+                    }
+                    leaves_function = true;
+                    unconditional = true;
+                }
+                Operator::CallIndirect { .. } => info.indirect_calls.push(offset),
+                Operator::ReturnCallIndirect { .. } => {
+                    info.indirect_calls.push(offset);
+                    leaves_function = true;
+                    unconditional = true;
+                }
+                Operator::RefFunc { function_index } => {
+                    table_functions.insert(*function_index);
+                }
+                _ => {}
+            }
+
+            // Hedefi döngünün dışında olan her dallanma, o döngüden çıkış demektir
+            let target_frames: Vec<usize> = targets.iter()
+                .map(|&relative| depth.saturating_sub(relative as usize))
+                .collect();
+            let outermost = if leaves_function { Some(0) } else { target_frames.iter().copied().min() };
+            if let Some(outermost) = outermost {
+                for frame in frames.iter_mut().skip(outermost + 1) {
+                    frame.exits = true;
+                }
+                if outermost == 0 && !straight_line_done {
+                    info.straight_line.push(Step::MayReturn);
+                    straight_line_done = true;
+                }
+            }
+            // Döngüye geri dönen koşulsuz dallanma yalnızca kendi seviyesindeki kuyruğu öldürür
+            if unconditional && let Some(frame) = frames.last_mut() {
+                frame.dead_tail = true;
+            }
+        }
+
+        Ok(info)
+    }
+
+    fn imported(&self) -> u32 {
+        self.imports.len() as u32
+    }
+
+    fn info(&self, index: u32) -> Option<&FunctionInfo> {
+        index.checked_sub(self.imported()).and_then(|i| self.functions.get(i as usize))
+    }
+
+    /// Export name, `module.name` for imports, otherwise `func[N]`
+    pub fn function_name(&self, index: u32) -> String {
+        if let Some(import) = self.imports.get(index as usize) {
+            return import.clone();
+        }
+        self.exports.iter()
+            .find(|(_, i)| *i == index)
+            .map(|(name, _)| name.clone())
+            .unwrap_or_else(|| format!("func[{}]", index))
+    }
+
+    /// Direct callees plus, for indirect calls, every function placed in a table
+    fn callees(&self, index: u32) -> Vec<(u32, usize)> {
+        let Some(info) = self.info(index) else { return Vec::new() };
+        let mut callees = info.calls.clone();
+        for &site in &info.indirect_calls {
+            callees.extend(self.table_functions.iter().map(|&f| (f, site)));
+        }
+        callees
+    }
+
+    pub fn call_graph(&self) -> Vec<CallGraphNode> {
+        let reachable = self.reachable_from(self.roots.iter().copied());
+        (0..self.imported() + self.functions.len() as u32)
+            .map(|index| {
+                let calls: BTreeSet<u32> = self.callees(index).into_iter().map(|(f, _)| f).collect();
+                CallGraphNode {
+                    function_index: index,
+                    name: self.function_name(index),
+                    imported: index < self.imported(),
+                    reachable: reachable.contains_key(&index),
+                    calls: calls.into_iter().collect(),
+                }
+            })
+            .collect()
+    }
+
+    /// BFS over the call graph; maps each reached function to (caller, call site)
+    fn reachable_from(&self, roots: impl Iterator<Item = u32>) -> HashMap<u32, Option<(u32, usize)>> {
+        let mut parents: HashMap<u32, Option<(u32, usize)>> = HashMap::new();
+        let mut queue = VecDeque::new();
+        for root in roots {
+            if parents.insert(root, None).is_none() {
+                queue.push_back(root);
+            }
+        }
+        while let Some(function) = queue.pop_front() {
+            for (callee, site) in self.callees(function) {
+                if let std::collections::hash_map::Entry::Vacant(entry) = parents.entry(callee) {
+                    entry.insert(Some((function, site)));
+                    queue.push_back(callee);
+                }
+            }
+        }
+        parents
+    }
+
+    pub fn check(&self, property: &str, abi: Option<&ContractAbi>) -> Option<Result<String, Counterexample>> {
+        match property {
+            PROP_NO_UNBOUNDED_LOOPS => Some(self.check_loops()),
+            PROP_NO_UNREACHABLE_EXPORTS => Some(self.check_exports()),
+            PROP_VIEW_FUNCTIONS_READONLY => Some(self.check_view_functions(abi)),
+            _ => None,
+        }
+    }
+
+    fn check_loops(&self) -> Result<String, Counterexample> {
+        for (i, info) in self.functions.iter().enumerate() {
+            if let Some(&offset) = info.unbounded_loops.first() {
+                let index = self.imported() + i as u32;
+                return Err(Counterexample {
+                    function_index: index,
+                    function: self.function_name(index),
+                    offset,
+                    message: "Loop has no exit condition; only gas exhaustion stops it".to_string(),
+                });
+            }
+        }
+        Ok(format!("All loops in {} function(s) have an exit path", self.functions.len()))
+    }
+
+    fn check_exports(&self) -> Result<String, Counterexample> {
+        let mut memo = HashMap::new();
+        for (name, index) in &self.exports {
+            if let Some(offset) = self.always_traps(*index, &mut memo, &mut HashSet::new()) {
+                return Err(Counterexample {
+                    function_index: *index,
+                    function: name.clone(),
+                    offset,
+                    message: format!("Export '{}' traps on every path and can never return", name),
+                });
+            }
+        }
+        Ok(format!("All {} export(s) have a path that returns", self.exports.len()))
+    }
+
+    /// Offset of the trap (or of the call leading to it) when every execution of `index` traps
+    fn always_traps(&self, index: u32, memo: &mut HashMap<u32, Option<usize>>, visiting: &mut HashSet<u32>) -> Option<usize> {
+        if let Some(result) = memo.get(&index) {
+            return *result;
+        }
+        let info = self.info(index)?; // host imports return
+        if !visiting.insert(index) {
+            return None; // özyineleme: sonsuz döngü trap sayılmaz
+        }
+
+        let mut result = None;
+        for step in &info.straight_line {
+            match *step {
+                Step::Trap(offset) => {
+                    result = Some(offset);
+                    break;
+                }
+                Step::Call(callee, offset) => {
+                    if self.always_traps(callee, memo, visiting).is_some() {
+                        result = Some(offset);
+                        break;
+                    }
+                }
+                Step::MayReturn => break,
+            }
+        }
+
+        visiting.remove(&index);
+        memo.insert(index, result);
+        result
+    }
+
+    fn check_view_functions(&self, abi: Option<&ContractAbi>) -> Result<String, Counterexample> {
+        let Some(abi) = abi else {
+            return Ok("No ABI: no functions are declared view".to_string());
+        };
+
+        let writers: Vec<u32> = self.imports.iter().enumerate()
+            .filter(|(_, name)| {
+                name.strip_prefix(HOST_MODULE)
+                    .and_then(|rest| rest.strip_prefix('.'))
+                    .is_some_and(|host_fn| STATE_WRITING_HOST_FUNCTIONS.contains(&host_fn))
+            })
+            .map(|(i, _)| i as u32)
+            .collect();
+
+        let mut checked = 0;
+        for function in abi.functions.iter().filter(|f| f.mutability == Mutability::View) {
+            let Some(&(_, export)) = self.exports.iter().find(|(name, _)| *name == function.name) else {
+                continue;
+            };
+            checked += 1;
+
+            let parents = self.reachable_from(std::iter::once(export));
+            if let Some(&writer) = writers.iter().find(|w| parents.contains_key(w)) {
+                // Yolu geri izle: view -> ... -> host yazma çağrısı
+                let site = parents[&writer].map(|(_, call_site)| call_site).unwrap_or_default();
+                let mut path = vec![self.function_name(writer)];
+                let mut current = writer;
+                while let Some(Some((caller, _))) = parents.get(&current) {
+                    path.push(self.function_name(*caller));
+                    current = *caller;
+                }
+                path.reverse();
+                return Err(Counterexample {
+                    function_index: export,
+                    function: function.name.clone(),
+                    offset: site,
+                    message: format!("View function '{}' writes state via {}", function.name, path.join(" -> ")),
+                });
+            }
+        }
+        Ok(format!("{} view function(s) never reach a state-writing host call", checked))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn analyze(wat: &str) -> ModuleAnalysis {
+        ModuleAnalysis::analyze(&wasmer::wat2wasm(wat.as_bytes()).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_unbounded_loop() {
+        let analysis = analyze(r#"(module
+            (func (export "spin") (loop br 0))
+            (func (export "count") (param i32)
+                (loop local.get 0 i32.const 1 i32.sub local.tee 0 br_if 0)))"#);
+        let counterexample = analysis.check(PROP_NO_UNBOUNDED_LOOPS, None).unwrap().unwrap_err();
+        assert_eq!(counterexample.function, "spin");
+
+        let bounded = analyze(r#"(module (func (param i32)
+            (block (loop local.get 0 i32.eqz br_if 1 br 0))))"#);
+        assert!(bounded.check(PROP_NO_UNBOUNDED_LOOPS, None).unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_unreachable_export_through_call() {
+        let analysis = analyze(r#"(module
+            (func $fail unreachable)
+            (func (export "ok") (result i32) i32.const 1)
+            (func (export "broken") call $fail))"#);
+        let counterexample = analysis.check(PROP_NO_UNREACHABLE_EXPORTS, None).unwrap().unwrap_err();
+        assert_eq!(counterexample.function, "broken");
+
+        let graph = analysis.call_graph();
+        assert_eq!(graph[2].calls, vec![0]);
+        assert!(graph.iter().all(|node| node.reachable));
+    }
+
+    #[test]
+    fn test_view_function_writes_storage() {
+        let analysis = analyze(r#"(module
+            (import "qverse" "storage_write" (func $write (param i32 i32 i32 i32)))
+            (memory 1)
+            (func $save i32.const 0 i32.const 1 i32.const 0 i32.const 1 call $write)
+            (func (export "get") call $save)
+            (func (export "set") call $save))"#);
+        let abi = ContractAbi::from_json(br#"{"version": 1, "functions": [
+            {"name": "get", "mutability": "view"}, {"name": "set"}]}"#).unwrap();
+
+        let counterexample = analysis.check(PROP_VIEW_FUNCTIONS_READONLY, Some(&abi)).unwrap().unwrap_err();
+        assert_eq!(counterexample.function, "get");
+        assert!(counterexample.message.contains("get -> func[1] -> qverse.storage_write"), "{}", counterexample.message);
+    }
+}
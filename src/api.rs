@@ -17,25 +17,30 @@
  * ============================================== */
 
 use actix_web::{web, HttpResponse, Responder};
-use crate::models::{ApiResponse, TokenSymbol, Wallet, Transaction, LiquidityPool, Order, Trade};
-use crate::models::{CompiledContract, DeployedContract};
+use crate::models::{ApiResponse, TokenSymbol, Wallet, Transaction, LiquidityPool, Order, OrderSide, OrderStatus, SelfTradeMode, TimeInForce, CurveType};
+use crate::models::{CompiledContract, DeployedContract, UpgradeApproval, UpgradeAuthorityKind};
 use crate::mobile::MobileDevice;
-use crate::db::Database;
+use crate::db::{ContractCallRecord, Database, StorageConflict};
 use crate::AppState; // Now defined in lib.rs
-use crate::exchange::{AMM, OrderMatcher};
+use crate::contracts::{NativeCall, STAKING_CONTRACT_ADDRESS};
+use crate::abi::{self, AbiType, ContractAbi, FunctionSignature};
+use crate::fees::TREASURY_ADDRESS;
+use crate::state::{DbState, StateDiff};
+use crate::vm::CallContext;
+use crate::wasm_validator::{ValidationLimits, ValidationReport, WasmValidator};
+use crate::exchange::{self, AMM, MatchingEngine, Placement};
 use crate::wallet::{MultiSigManager, QRCodeGenerator, PaymentGateway};
-use crate::developer::{ContractCompiler, FormalVerifier, SDKGenerator};
+use crate::developer::{ContractCompiler, FormalVerifier, SDKGenerator, SdkLanguage, SourceLanguage, SyntaxError};
+use crate::sdk::DEFAULT_SDK_BASE_URL;
 use crate::mobile::{MobileDeviceManager, PushNotificationService, BiometricAuthManager};
 use crate::validation;
-use crate::cache::CacheManager;
-use crate::metrics::Metrics;
 use crate::batch::BatchOperations;
 use crate::middleware::check_rate_limit;
 use serde::Deserialize;
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
-use wasmer::Value;
-use sqlx::Row;
 use log;
+use std::sync::Arc;
 use std::time::Instant;
 
 // --- DTOs ---
@@ -45,10 +50,16 @@ pub struct CreateUserRequest {
     pub username: String,
 }
 
+#[derive(Deserialize)]
+pub struct RekeyWalletRequest {
+    pub secret_key: String, // Spend key the legacy wallet was created with
+}
+
 #[derive(Deserialize)]
 pub struct TransferRequest {
     pub from_wallet_id: Uuid,
-    pub to_wallet_id: Uuid,
+    pub to_wallet_id: Option<Uuid>,
+    pub to_address: Option<String>, // Native contract, e.g. contract_staking_v1
     pub token: String,
     pub amount: f64,
     pub fee: f64,
@@ -59,13 +70,42 @@ pub struct TransferRequest {
 pub struct StakeRequest {
     pub wallet_id: Uuid,
     pub amount: f64,
+    pub secret_key: String, // Spend key of the wallet
+}
+
+#[derive(Deserialize)]
+pub struct ClaimRewardsRequest {
+    pub wallet_id: Uuid,
+    pub secret_key: String, // Spend key of the wallet
 }
 
 #[derive(Deserialize)]
 pub struct ContractExecRequest {
-    pub wasm_hex: String, // Hex encoded WASM bytecode
+    pub wasm_hex: Option<String>, // Hex encoded WASM bytecode (ad-hoc, nothing recorded)
+    pub contract_address: Option<String>, // Deployed contract (recorded as a transaction)
+    pub caller_wallet_id: Option<Uuid>,
+    pub secret_key: Option<String>, // Spend key of caller_wallet_id
     pub function: String,
-    pub args: Vec<String>, // Simplified args
+    #[serde(default)]
+    pub args: Vec<serde_json::Value>,
+    // Needed for bytes/string values; numeric types are read from the module
+    pub param_types: Option<Vec<AbiType>>,
+    pub return_types: Option<Vec<AbiType>>,
+    pub gas_limit: Option<u64>, // Default: the node's per-call limit
+    pub gas_price: Option<f64>, // QVR per gas unit, default: the minimum
+}
+
+#[derive(Deserialize)]
+pub struct ContractEventsQuery {
+    pub event: Option<String>, // ABI event name, matched as topic0
+    pub topic0: Option<String>,
+    pub topic1: Option<String>,
+    pub topic2: Option<String>,
+    pub topic3: Option<String>,
+    pub from_block: Option<i64>,
+    pub to_block: Option<i64>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
 }
 
 #[derive(Deserialize)]
@@ -80,7 +120,28 @@ pub struct SwapRequest {
     pub token_in: String,
     pub token_out: String,
     pub amount_in: f64,
-    pub min_amount_out: Option<f64>,
+    pub min_amount_out: Option<f64>, // For the whole route
+    pub max_hops: Option<usize>,     // Default and cap: MAX_SWAP_HOPS
+    pub secret_key: String, // Spend key of the wallet
+}
+
+#[derive(Deserialize)]
+pub struct SwapQuoteQuery {
+    pub token_in: String,
+    pub token_out: String,
+    pub amount_in: f64,
+    pub max_hops: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct CreatePoolRequest {
+    pub wallet_id: Uuid,
+    pub secret_key: String, // Spend key of the wallet
+    pub token_a: String,
+    pub token_b: String,
+    pub fee_rate: Option<f64>, // Default 0.003
+    pub curve_type: Option<String>, // CONSTANT_PRODUCT (default) or STABLE_SWAP
+    pub amplification: Option<f64>, // STABLE_SWAP only, 1..=10000
 }
 
 #[derive(Deserialize)]
@@ -88,8 +149,32 @@ pub struct AddLiquidityRequest {
     pub wallet_id: Uuid,
     pub token_a: String,
     pub token_b: String,
-    pub amount_a: f64,
+    pub amount_a: f64, // Most to deposit; the pool ratio may use less of one side
     pub amount_b: f64,
+    pub amount_a_min: Option<f64>,
+    pub amount_b_min: Option<f64>,
+    pub secret_key: String,
+}
+
+#[derive(Deserialize)]
+pub struct RemoveLiquidityRequest {
+    pub wallet_id: Uuid,
+    pub token_a: String,
+    pub token_b: String,
+    pub shares: f64,
+    pub amount_a_min: Option<f64>,
+    pub amount_b_min: Option<f64>,
+    pub secret_key: String,
+}
+
+#[derive(Deserialize)]
+pub struct TransferSharesRequest {
+    pub from_wallet_id: Uuid,
+    pub to_wallet_id: Uuid,
+    pub token_a: String,
+    pub token_b: String,
+    pub shares: f64,
+    pub secret_key: String,
 }
 
 #[derive(Deserialize)]
@@ -98,8 +183,48 @@ pub struct CreateOrderRequest {
     pub pair: String,
     pub side: String,
     pub order_type: String,
-    pub price: f64,
+    pub price: Option<f64>,        // LIMIT: limit price; MARKET: worst price; STOPLOSS: limit after trigger (else market)
     pub amount: f64,
+    pub stop_price: Option<f64>,   // STOPLOSS: trigger price
+    pub max_slippage: Option<f64>, // Market orders: worst price vs. the best (or stop) price, 0.0..1.0
+    pub time_in_force: Option<String>, // GTC (default), IOC, FOK or GTD
+    pub expires_at: Option<i64>,       // GTD: unix seconds
+    pub post_only: Option<bool>,       // Limit orders: reject instead of trading on entry
+    pub secret_key: String,            // Spend key of the wallet
+}
+
+#[derive(Deserialize)]
+pub struct CancelOrderRequest {
+    pub wallet_id: Uuid,
+    pub secret_key: String,
+}
+
+#[derive(Deserialize)]
+pub struct CancelAllOrdersRequest {
+    pub wallet_id: Uuid,
+    pub secret_key: String,
+    pub pair: Option<String>, // Only this pair's orders
+}
+
+#[derive(Deserialize)]
+pub struct AmendOrderRequest {
+    pub wallet_id: Uuid,
+    pub secret_key: String,
+    pub price: Option<f64>,
+    pub amount: Option<f64>, // New total size, including the filled part
+}
+
+#[derive(Deserialize)]
+pub struct WalletOrdersQuery {
+    pub pair: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct SelfTradeModeRequest {
+    pub mode: String, // CANCEL_NEWEST, CANCEL_OLDEST, CANCEL_BOTH or DECREMENT_AND_CANCEL
+    pub secret_key: String, // Spend key of the wallet
 }
 
 // Bridge DTOs
@@ -118,6 +243,9 @@ pub struct CreateProposalRequest {
     pub proposer_wallet_id: Uuid,
     pub title: String,
     pub description: String,
+    // Contract upgrade the proposal authorizes once passed (both or neither)
+    pub upgrade_contract_address: Option<String>,
+    pub upgrade_compiled_contract_id: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -141,6 +269,7 @@ pub struct UpdatePriceRequest {
     pub token_symbol: String,
     pub price: f64,
     pub source: String,
+    pub api_key: String, // Key configured for `source` in ORACLE_KEYS
 }
 
 // Airdrop DTOs
@@ -158,11 +287,20 @@ pub struct CreateMultiSigRequest {
     pub threshold: i32,
 }
 
+#[derive(Deserialize)]
+pub struct CreateMultiSigUpgradeRequest {
+    pub multisig_id: String,
+    pub proposer_wallet_id: Uuid, // Must be a signer of the multisig
+    pub secret_key: String,
+    pub contract_address: String,
+    pub new_compiled_contract_id: String,
+}
+
 #[derive(Deserialize)]
 pub struct SignMultiSigRequest {
     pub multisig_tx_id: String,
     pub signer_wallet_id: Uuid,
-    pub signature: String,
+    pub secret_key: String, // Spend key of the signer
 }
 
 #[derive(Deserialize)]
@@ -182,26 +320,55 @@ pub struct ScanQRRequest {
 // Developer Tools DTOs
 #[derive(Deserialize)]
 pub struct CompileContractRequest {
-    pub source_code: String,
+    pub source_code: Option<String>,
+    #[serde(default)]
+    pub language: SourceLanguage, // Language of source_code (rust | wat)
+    pub wasm_hex: Option<String>, // Pre-built binary instead of source
     pub contract_name: String,
 }
 
 #[derive(Deserialize)]
 pub struct VerifyContractRequest {
     pub contract_id: String,
-    pub properties: Vec<String>,
+    #[serde(default)]
+    pub properties: Vec<String>, // empty = every supported property
 }
 
 #[derive(Deserialize)]
 pub struct DeployContractRequest {
     pub compiled_contract_id: String,
     pub deployer_wallet_id: Uuid,
+    #[serde(default)]
+    pub upgradeable: bool,
+    pub upgrade_authority: Option<UpgradeAuthorityKind>, // Default: the deployer wallet
+    pub upgrade_authority_id: Option<String>,
+    #[serde(default = "default_true")]
+    pub reentrancy_guard: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+pub struct UpgradeContractRequest {
+    pub new_compiled_contract_id: String,
+    // Exactly one approval matching the contract's upgrade authority
+    pub caller_wallet_id: Option<Uuid>,
+    pub secret_key: Option<String>, // Spend key of caller_wallet_id
+    pub multisig_tx_id: Option<String>,
+    pub proposal_id: Option<String>,
+    // Exported by the new code, run once against existing storage
+    pub migrate_function: Option<String>,
+    #[serde(default)]
+    pub migrate_args: Vec<serde_json::Value>,
 }
 
 #[derive(Deserialize)]
 pub struct GenerateSDKRequest {
     pub contract_id: String,
-    pub language: String, // "js" or "rust"
+    pub language: String, // "typescript", "rust" or "python"
+    pub base_url: Option<String>, // Default base URL baked into the client
 }
 
 // Mobile Integration DTOs
@@ -290,12 +457,71 @@ pub async fn stake(
         return resp;
     }
     
-    match data.db.stake_tokens(req.wallet_id, req.amount).await {
+    if let Err(resp) = authorize_wallet(&data.db, req.wallet_id, &req.secret_key).await {
+        return resp;
+    }
+
+    // Staking, native staking kontratına giden bir işlemdir
+    let tx = Transaction::new_public(&req.wallet_id.to_string(), STAKING_CONTRACT_ADDRESS, TokenSymbol::QVR, req.amount, 0.0);
+    let mut contracts = data.contracts.lock().await;
+    match contracts.dispatch(&data.db, &tx, &req.wallet_id.to_string()).await {
         Ok(_) => HttpResponse::Ok().json(ApiResponse::success("Staking Successful! 🚀")),
         Err(e) => HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string())),
     }
 }
 
+pub async fn unstake(
+    data: web::Data<AppState>,
+    http_req: actix_web::HttpRequest,
+    req: web::Json<StakeRequest>
+) -> impl Responder {
+    if let Err(resp) = check_rate_limit(&data.rate_limiter, &http_req).await {
+        return resp;
+    }
+    if let Err(e) = validation::validate_amount(req.amount) {
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string()));
+    }
+    if let Err(resp) = authorize_wallet(&data.db, req.wallet_id, &req.secret_key).await {
+        return resp;
+    }
+
+    let call = NativeCall {
+        sender: req.wallet_id.to_string(),
+        amount: req.amount,
+        timestamp: chrono::Utc::now().timestamp(),
+    };
+    let mut contracts = data.contracts.lock().await;
+    match contracts.invoke(&data.db, STAKING_CONTRACT_ADDRESS, "unstake", &call).await {
+        Ok(result) => HttpResponse::Ok().json(ApiResponse::success(result)),
+        Err(e) => HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string())),
+    }
+}
+
+pub async fn claim_rewards(
+    data: web::Data<AppState>,
+    http_req: actix_web::HttpRequest,
+    req: web::Json<ClaimRewardsRequest>
+) -> impl Responder {
+    if let Err(resp) = check_rate_limit(&data.rate_limiter, &http_req).await {
+        return resp;
+    }
+
+    if let Err(resp) = authorize_wallet(&data.db, req.wallet_id, &req.secret_key).await {
+        return resp;
+    }
+
+    let call = NativeCall {
+        sender: req.wallet_id.to_string(),
+        amount: 0.0,
+        timestamp: chrono::Utc::now().timestamp(),
+    };
+    let mut contracts = data.contracts.lock().await;
+    match contracts.invoke(&data.db, STAKING_CONTRACT_ADDRESS, "claim_rewards", &call).await {
+        Ok(result) => HttpResponse::Ok().json(ApiResponse::success(result)),
+        Err(e) => HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string())),
+    }
+}
+
 #[utoipa::path(
     get,
     path = "/health",
@@ -383,6 +609,47 @@ pub async fn create_user(
     }
 }
 
+/// Gives a wallet created before spend key hashes were stored a new spend key, returned once
+pub async fn rekey_wallet(
+    data: web::Data<AppState>,
+    http_req: actix_web::HttpRequest,
+    path: web::Path<Uuid>,
+    req: web::Json<RekeyWalletRequest>
+) -> impl Responder {
+    if let Err(resp) = check_rate_limit(&data.rate_limiter, &http_req).await {
+        return resp;
+    }
+
+    let wallet_id = path.into_inner();
+    let mut wallet = match data.db.get_wallet(wallet_id).await {
+        Ok(Some(wallet)) => wallet,
+        Ok(None) => return HttpResponse::NotFound().json(ApiResponse::<()>::error("Wallet not found".into())),
+        Err(e) => return HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
+    };
+    if wallet.spend_key_hash.is_some() {
+        return HttpResponse::Conflict().json(ApiResponse::<()>::error("Wallet already has a spend key".into()));
+    }
+    if !wallet.verify_legacy_spend_key(&req.secret_key) {
+        log::warn!("Invalid legacy spend key for wallet {}", wallet_id);
+        return HttpResponse::Unauthorized().json(ApiResponse::<()>::error("Invalid secret key".into()));
+    }
+
+    let spend_sk = wallet.rekey();
+    let spend_key_hash = wallet.spend_key_hash.as_deref().unwrap_or_default();
+    match data.db.set_spend_key_hash(wallet_id, spend_key_hash).await {
+        Ok(true) => {
+            log::info!("Wallet {} re-keyed", wallet_id);
+            HttpResponse::Ok().json(ApiResponse::success(serde_json::json!({
+                "wallet": wallet,
+                "spend_secret_key": spend_sk,
+            })))
+        },
+        // Re-keyed concurrently
+        Ok(false) => HttpResponse::Conflict().json(ApiResponse::<()>::error("Wallet already has a spend key".into())),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
+    }
+}
+
 pub async fn get_balance(
     data: web::Data<AppState>,
     http_req: actix_web::HttpRequest,
@@ -400,6 +667,23 @@ pub async fn get_balance(
     }
 }
 
+/// Total, held (by open orders) and available amount of a token
+pub async fn get_balance_holds(
+    data: web::Data<AppState>,
+    path: web::Path<(Uuid, String)>
+) -> impl Responder {
+    let (wallet_id, token) = path.into_inner();
+    match data.db.get_balance_holds(wallet_id, &token).await {
+        Ok((total, held)) => HttpResponse::Ok().json(ApiResponse::success(serde_json::json!({
+            "token": token,
+            "total": total,
+            "held": held,
+            "available": total - held
+        }))),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
+    }
+}
+
 pub async fn transfer(
     data: web::Data<AppState>,
     http_req: actix_web::HttpRequest,
@@ -416,9 +700,25 @@ pub async fn transfer(
     if let Err(e) = validation::validate_wallet_id(&req.from_wallet_id) {
         return HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string()));
     }
-    if let Err(e) = validation::validate_wallet_id(&req.to_wallet_id) {
-        return HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string()));
-    }
+    let destination = match (&req.to_wallet_id, &req.to_address) {
+        (Some(to_wallet_id), None) => {
+            if let Err(e) = validation::validate_wallet_id(to_wallet_id) {
+                return HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string()));
+            }
+            to_wallet_id.to_string()
+        }
+        (None, Some(address)) => {
+            if !data.contracts.lock().await.contains(address) {
+                return HttpResponse::NotFound().json(ApiResponse::<()>::error(format!("Unknown native contract: {}", address)));
+            }
+            address.clone()
+        }
+        _ => {
+            return HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+                "Provide exactly one of to_wallet_id or to_address".into(),
+            ));
+        }
+    };
     if let Err(e) = validation::validate_token_symbol(&req.token) {
         return HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string()));
     }
@@ -455,11 +755,11 @@ pub async fn transfer(
         },
     };
 
-    log::info!("Processing transfer: {} {} from {} to {}", req.amount, req.token, req.from_wallet_id, req.to_wallet_id);
+    log::info!("Processing transfer: {} {} from {} to {}", req.amount, req.token, req.from_wallet_id, destination);
 
     let tx = Transaction::new_public(
         &from_wallet.id.to_string(), 
-        &req.to_wallet_id.to_string(), 
+        &destination, 
         token_sym.clone(), 
         req.amount, 
         req.fee
@@ -476,6 +776,28 @@ pub async fn transfer(
         },
     };
 
+    // Native kontrata giden işlemler registry üzerinden execute edilir
+    if req.to_address.is_some() {
+        let mut contracts = data.contracts.lock().await;
+        return match contracts.dispatch(&data.db, &tx, &from_wallet.id.to_string()).await {
+            Ok(result) => {
+                data.metrics.record_response_time(start.elapsed().as_millis() as u64);
+                data.metrics.increment_success();
+                data.metrics.increment_transactions();
+                log::info!("Native contract call completed: {} -> {}", tx.id, destination);
+                HttpResponse::Ok().json(ApiResponse::success(serde_json::json!({
+                    "transaction": tx,
+                    "result": result,
+                })))
+            }
+            Err(e) => {
+                data.metrics.increment_failure();
+                log::error!("Native contract call failed: {}", e);
+                HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string()))
+            }
+        };
+    }
+
     match data.db.process_transfer(&tx, &from_wallet.id.to_string()).await {
         Ok(_) => {
             let response_time = start.elapsed().as_millis() as u64;
@@ -493,17 +815,55 @@ pub async fn transfer(
     }
 }
 
+pub async fn list_native_contracts(data: web::Data<AppState>) -> impl Responder {
+    let contracts: Vec<serde_json::Value> = data.contracts.lock().await.states()
+        .into_iter()
+        .map(|(address, state)| serde_json::json!({
+            "address": address,
+            "state": serde_json::from_str::<serde_json::Value>(&state).unwrap_or(serde_json::Value::String(state)),
+        }))
+        .collect();
+    HttpResponse::Ok().json(ApiResponse::success(contracts))
+}
+
+/// Gas pricing parameters and what the treasury has collected
+pub async fn get_fee_info(data: web::Data<AppState>) -> impl Responder {
+    let treasury_balance = match data.db.get_treasury_balance().await {
+        Ok(amount) => amount,
+        Err(e) => return HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
+    };
+    HttpResponse::Ok().json(ApiResponse::success(serde_json::json!({
+        "min_gas_price": data.gas.min_gas_price,
+        "max_gas_limit": data.vm.gas_limit(),
+        "treasury_share": data.gas.treasury_share,
+        "stakers_share": 1.0 - data.gas.treasury_share,
+        "treasury_address": TREASURY_ADDRESS,
+        "treasury_balance": treasury_balance,
+    })))
+}
+
 pub async fn get_stake_info(data: web::Data<AppState>,
     path: web::Path<Uuid>
 ) -> impl Responder {
     let wallet_id = path.into_inner();
-    match data.db.get_stake(wallet_id).await {
-        Ok(amount) => HttpResponse::Ok().json(ApiResponse::success(serde_json::json!({
-            "staked_amount": amount,
-            "rewards": amount * 0.05 
-        }))),
-        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
-    }
+    let (staked, pending_rewards) = match data.db.get_stake(wallet_id).await {
+        Ok(stake) => stake,
+        Err(e) => return HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
+    };
+    let unlocking = match data.db.get_unlocking_stakes(wallet_id).await {
+        Ok(rows) => rows,
+        Err(e) => return HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
+    };
+
+    let unlocking_total: f64 = unlocking.iter().map(|(amount, _)| amount).sum();
+    HttpResponse::Ok().json(ApiResponse::success(serde_json::json!({
+        "staked_amount": staked,
+        "pending_rewards": pending_rewards,
+        "unlocking_amount": unlocking_total,
+        "unlocking": unlocking.iter()
+            .map(|(amount, unlock_at)| serde_json::json!({ "amount": amount, "unlock_at": unlock_at }))
+            .collect::<Vec<_>>(),
+    })))
 }
 
 pub async fn get_transactions(data: web::Data<AppState>,
@@ -531,24 +891,284 @@ pub async fn get_network_status(
     })))
 }
 
+/// A contract call resolved from a request, ready for the VM
+struct PreparedCall {
+    wasm_bytes: Vec<u8>,
+    function: String,
+    args: Vec<serde_json::Value>,
+    signature: Option<FunctionSignature>,
+    deployed: Option<DeployedContract>,
+    gas_limit: u64,
+    ctx: CallContext,
+}
+
+async fn prepare_contract_call(data: &AppState, req: ContractExecRequest) -> Result<PreparedCall, HttpResponse> {
+    // Resolve code: ad-hoc bytecode or a deployed contract
+    let (wasm_hex, deployed) = match (req.wasm_hex, &req.contract_address) {
+        (Some(wasm_hex), None) => (wasm_hex, None),
+        (None, Some(address)) => {
+            let deployed = match data.db.get_deployed_contract(address).await {
+                Ok(Some(d)) => d,
+                Ok(None) => return Err(HttpResponse::NotFound().json(ApiResponse::<()>::error("Contract not found".into()))),
+                Err(e) => return Err(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string()))),
+            };
+            let compiled_id = deployed.compiled_contract_id.clone().unwrap_or_default();
+            match data.db.get_compiled_contract(&compiled_id).await {
+                Ok(Some(c)) => (c.wasm_hex, Some(deployed)),
+                Ok(None) => return Err(HttpResponse::NotFound().json(ApiResponse::<()>::error("Contract code not found".into()))),
+                Err(e) => return Err(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string()))),
+            }
+        },
+        _ => return Err(HttpResponse::BadRequest().json(ApiResponse::<()>::error("Provide either wasm_hex or contract_address".into()))),
+    };
+
+    // Decode Hex WASM
+    let wasm_bytes = match hex::decode(&wasm_hex) {
+        Ok(b) => b,
+        Err(_) => return Err(HttpResponse::BadRequest().json(ApiResponse::<()>::error("Invalid Hex".into()))),
+    };
+
+    // Explicit types override the ones read from the export
+    let signature = match (req.param_types, req.return_types) {
+        (None, None) => None,
+        (Some(params), Some(results)) => Some(FunctionSignature {
+            name: req.function.clone(),
+            params,
+            results,
+        }),
+        _ => return Err(HttpResponse::BadRequest().json(ApiResponse::<()>::error("param_types and return_types must be given together".into()))),
+    };
+
+    // Otherwise use the module's embedded ABI when it declares the function
+    let signature = match signature {
+        Some(sig) => Some(sig),
+        None => match ContractAbi::from_wasm(&wasm_bytes) {
+            Ok(abi) => abi.and_then(|a| a.function(&req.function).map(|f| f.signature())),
+            Err(e) => return Err(HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string()))),
+        },
+    };
+
+    // Contracts see the signer by wallet address; it pays the gas, so it must prove its spend key
+    let caller = match req.caller_wallet_id {
+        Some(id) => Some(authorize_wallet(&data.db, id, req.secret_key.as_deref().unwrap_or_default()).await?.address),
+        None => None,
+    };
+
+    // Deployed contracts see committed state and the current block; ad-hoc code gets an empty one
+    let ctx = match &deployed {
+        Some(d) => match data.db.current_block_number().await {
+            Ok(height) => CallContext::new(d.address.clone(), Arc::new(DbState::new(data.db.clone())))
+                .with_block_height(height.max(0) as u64),
+            Err(e) => return Err(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string()))),
+        },
+        None => CallContext::ephemeral(),
+    };
+    let gas_limit = req.gas_limit.unwrap_or(data.vm.gas_limit());
+    if gas_limit == 0 || gas_limit > data.vm.gas_limit() {
+        return Err(HttpResponse::BadRequest().json(ApiResponse::<()>::error(format!("gas_limit must be between 1 and {}", data.vm.gas_limit()))));
+    }
+    let ctx = ctx.with_caller(caller).with_gas_limit(gas_limit);
+
+    Ok(PreparedCall {
+        wasm_bytes,
+        function: req.function,
+        args: req.args,
+        signature,
+        deployed,
+        gas_limit,
+        ctx,
+    })
+}
+
+/// Runs of one contract call before a storage conflict is reported to the client
+const MAX_CALL_ATTEMPTS: usize = 3;
+
 pub async fn execute_contract(
     data: web::Data<AppState>,
     req: web::Json<ContractExecRequest>
 ) -> impl Responder {
-    let mut vm = data.vm.lock().unwrap();
-    
-    // Decode Hex WASM
-    let wasm_bytes = match hex::decode(&req.wasm_hex) {
-        Ok(b) => b,
-        Err(_) => return HttpResponse::BadRequest().json(ApiResponse::<()>::error("Invalid Hex".into())),
+    let req = req.into_inner();
+    let caller = req.caller_wallet_id.map(|id| id.to_string());
+    let gas_price = req.gas_price.unwrap_or(data.gas.min_gas_price);
+
+    let call = match prepare_contract_call(&data, req).await {
+        Ok(call) => call,
+        Err(resp) => return resp,
+    };
+
+    // Ad-hoc code runs on empty state: nothing is recorded or charged
+    let deployed = match call.deployed {
+        Some(d) => d,
+        None => return match data.vm.call_contract(call.wasm_bytes, call.function, call.args, call.signature, call.ctx).await {
+            Ok(output) => HttpResponse::Ok().json(ApiResponse::success(output)),
+            Err(e) => HttpResponse::BadRequest().json(ApiResponse::<()>::error(format!("VM Error: {}", e))),
+        },
+    };
+
+    if gas_price.is_nan() || gas_price < data.gas.min_gas_price {
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error(format!("Gas price must be at least {} QVR", data.gas.min_gas_price)));
+    }
+    let block_number = match data.db.current_block_number().await {
+        Ok(n) => n,
+        Err(e) => return HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
+    };
+
+    // Reserve the maximum fee up front; the unused part is refunded with the record
+    let reserved = data.gas.max_fee(call.gas_limit, gas_price);
+    let payer = match caller.as_deref() {
+        _ if reserved <= 0.0 => None,
+        Some(wallet_id) => Some(wallet_id),
+        None => return HttpResponse::BadRequest().json(ApiResponse::<()>::error("caller_wallet_id is required to pay for gas".into())),
+    };
+    if let Some(wallet_id) = payer
+        && let Err(e) = data.db.reserve_gas_fee(wallet_id, reserved).await
+    {
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string()));
+    }
+
+    // Storage writes are compare-and-set: if another call committed to the same slots first,
+    // the record is rejected as a whole and the call is re-run on fresh state
+    let mut attempt = 1;
+    let (tx_id, result, gas_used, gas_fee, error) = loop {
+        let result = data.vm.call_contract_metered(
+            call.wasm_bytes.clone(), call.function.clone(), call.args.clone(), call.signature.clone(), call.ctx.clone(),
+        ).await;
+        let gas_used = match &result {
+            Ok(output) => output.gas_used,
+            Err(failure) => failure.gas_used,
+        };
+
+        // Failed calls are recorded too: they pay for the gas they burned
+        let error = result.as_ref().err().map(|failure| format!("VM Error: {}", failure.error));
+        let no_changes = StateDiff::default();
+        let (events, state_diff) = match &result {
+            Ok(output) => (output.events.as_slice(), &output.state_diff),
+            Err(_) => (&[][..], &no_changes),
+        };
+
+        // Stakers' share is planned and applied under the registry lock, like epoch rewards
+        let mut contracts = data.contracts.lock().await;
+        let gas_fee = payer.map(|_| data.gas.settle(call.gas_limit, gas_price, gas_used));
+        let fee_rewards = gas_fee.map(|fee| contracts.fee_rewards(fee.stakers)).unwrap_or_default();
+        let gas_fee = gas_fee.map(|fee| if fee_rewards.is_empty() { fee.without_stakers() } else { fee });
+
+        let tx_id = Uuid::new_v4().to_string();
+        let record = ContractCallRecord {
+            tx_id: &tx_id,
+            caller_wallet_id: caller.as_deref(),
+            contract_address: &deployed.address,
+            function: &call.function,
+            gas_used,
+            block_number,
+            events,
+            state_diff,
+            gas_fee: gas_fee.as_ref(),
+            fee_rewards: &fee_rewards,
+            error: error.as_deref(),
+        };
+        match data.db.record_contract_call(&record).await {
+            Ok(()) => {
+                contracts.apply_changes(&fee_rewards);
+                break (tx_id, result, gas_used, gas_fee, error);
+            }
+            Err(e) if attempt < MAX_CALL_ATTEMPTS && e.downcast_ref::<StorageConflict>().is_some() => {
+                log::warn!("Contract call {} conflicted ({}), retrying", tx_id, e);
+                attempt += 1;
+            }
+            Err(e) => {
+                log::error!("Failed to record contract call {}: {}", tx_id, e);
+                if let Some(wallet_id) = payer
+                    && let Err(e) = data.db.release_gas_fee(wallet_id, reserved).await
+                {
+                    log::error!("Failed to release gas reservation of {}: {}", wallet_id, e);
+                }
+                return match e.downcast_ref::<StorageConflict>() {
+                    Some(_) => HttpResponse::Conflict().json(ApiResponse::<()>::error(e.to_string())),
+                    None => HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
+                };
+            }
+        }
+    };
+
+    let output = match result {
+        Ok(output) => output,
+        Err(_) => return HttpResponse::BadRequest().json(ApiResponse {
+            success: false,
+            error,
+            data: Some(serde_json::json!({
+                "transaction_id": tx_id,
+                "gas_used": gas_used,
+                "gas": gas_fee,
+            })),
+        }),
+    };
+
+    HttpResponse::Ok().json(ApiResponse::success(serde_json::json!({
+        "transaction_id": tx_id,
+        "block_number": block_number,
+        "contract_address": deployed.address,
+        "results": output.results,
+        "gas_used": output.gas_used,
+        "gas": gas_fee,
+        "events": output.events,
+        "state_diff": output.state_diff,
+    })))
+}
+
+/// Dry run: executes against current state and returns what the call
+/// would change, without recording anything
+pub async fn simulate_contract(
+    data: web::Data<AppState>,
+    req: web::Json<ContractExecRequest>
+) -> impl Responder {
+    let call = match prepare_contract_call(&data, req.into_inner()).await {
+        Ok(call) => call,
+        Err(resp) => return resp,
     };
 
-    // Convert args (simplified for demo)
-    let args: Vec<Value> = vec![]; // Parsing real args requires more logic
+    match data.vm.call_contract(call.wasm_bytes, call.function, call.args, call.signature, call.ctx).await {
+        Ok(output) => HttpResponse::Ok().json(ApiResponse::success(serde_json::json!({
+            "simulated": true,
+            "contract_address": call.deployed.map(|d| d.address),
+            "results": output.results,
+            "gas_used": output.gas_used,
+            "events": output.events,
+            "state_diff": output.state_diff,
+        }))),
+        Err(e) => HttpResponse::BadRequest().json(ApiResponse::<()>::error(format!("VM Error: {}", e))),
+    }
+}
+
+pub async fn get_contract_events(
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    query: web::Query<ContractEventsQuery>
+) -> impl Responder {
+    let address = path.into_inner();
+    let query = query.into_inner();
+
+    let deployed = match data.db.get_deployed_contract(&address).await {
+        Ok(Some(d)) => d,
+        Ok(None) => return HttpResponse::NotFound().json(ApiResponse::<()>::error("Contract not found".into())),
+        Err(e) => return HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
+    };
 
-    match vm.execute_contract(&wasm_bytes, &req.function, args) {
-        Ok(_) => HttpResponse::Ok().json(ApiResponse::success("Contract Executed Successfully (Gas Used: 4500)")),
-        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::<()>::error(format!("VM Error: {}", e))),
+    let topic0 = match (query.event, query.topic0) {
+        (Some(_), Some(_)) => return HttpResponse::BadRequest().json(ApiResponse::<()>::error("Use either event or topic0".into())),
+        (Some(name), None) => Some(abi::event_topic(&name)),
+        (None, topic0) => topic0,
+    };
+    let topics = [topic0, query.topic1, query.topic2, query.topic3];
+    let limit = query.limit.unwrap_or(100).clamp(1, 1000);
+    let offset = query.offset.unwrap_or(0).max(0);
+
+    match data.db.get_contract_events(&deployed.address, &topics, query.from_block, query.to_block, limit, offset).await {
+        Ok(events) => HttpResponse::Ok().json(ApiResponse::success(serde_json::json!({
+            "events": events,
+            "limit": limit,
+            "offset": offset
+        }))),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
     }
 }
 
@@ -597,63 +1217,42 @@ pub async fn swap_tokens(
     
     log::info!("Swap request: {} {} -> {} (amount: {})", req.wallet_id, req.token_in, req.token_out, req.amount_in);
     
-    // Get liquidity pool from DB
-    let pool: Option<LiquidityPool> = sqlx::query_as(
-        "SELECT * FROM liquidity_pools WHERE (token_a = ? AND token_b = ?) OR (token_a = ? AND token_b = ?)"
-    )
-    .bind(&req.token_in)
-    .bind(&req.token_out)
-    .bind(&req.token_out)
-    .bind(&req.token_in)
-    .fetch_optional(&data.db.pool)
-    .await
-    .ok()
-    .flatten();
-    
-    let (reserve_in, reserve_out) = match pool {
-        Some(p) => {
-            if p.token_a == req.token_in {
-                (p.reserve_a, p.reserve_b)
-            } else {
-                (p.reserve_b, p.reserve_a)
-            }
-        },
-        None => {
-            log::warn!("Liquidity pool not found for {}/{}", req.token_in, req.token_out);
-            return HttpResponse::NotFound().json(ApiResponse::<()>::error("Liquidity pool not found".into()));
-        },
-    };
-    
-    if reserve_in <= 0.0 || reserve_out <= 0.0 {
-        return HttpResponse::BadRequest().json(ApiResponse::<()>::error("Insufficient liquidity".into()));
+    if req.token_in == req.token_out {
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error("token_in and token_out must differ".into()));
     }
-    
-    // Calculate swap output
-    let amount_out = match AMM::calculate_swap_out(reserve_in, reserve_out, req.amount_in, 0.003) {
-        Ok(amt) => amt,
+    let min_amount_out = req.min_amount_out.unwrap_or(0.0);
+    if !min_amount_out.is_finite() || min_amount_out < 0.0 {
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error("min_amount_out must be a non-negative number".into()));
+    }
+
+    let wallet = match authorize_wallet(&data.db, req.wallet_id, &req.secret_key).await {
+        Ok(wallet) => wallet,
+        Err(resp) => return resp,
+    };
+    let signature = wallet.sign_spend(&req.secret_key, &format!(
+        "swap:{}:{}:{}:{}:{}", req.wallet_id, req.token_in, req.token_out, req.amount_in, min_amount_out
+    ));
+
+    let route = match best_swap_route(&data, &req.token_in, &req.token_out, req.amount_in, req.max_hops).await {
+        Ok(route) => route,
+        Err(resp) => return resp,
+    };
+
+    // Every hop is re-quoted, settled and recorded together
+    let pool_ids: Vec<String> = route.hops.iter().map(|hop| hop.pool_id.clone()).collect();
+    let (tx_id, route) = match data.db.execute_swap_route(
+        &req.wallet_id.to_string(), &pool_ids, &req.token_in, req.amount_in, min_amount_out, &signature,
+    ).await {
+        Ok(result) => result,
         Err(e) => {
-            log::error!("Swap calculation failed: {}", e);
+            data.metrics.increment_failure();
+            log::warn!("Swap failed: {}", e);
             return HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string()));
         },
     };
     
-    // Check slippage protection
-    if let Some(min) = req.min_amount_out {
-        if amount_out < min {
-            log::warn!("Slippage too high: expected min {}, got {}", min, amount_out);
-            return HttpResponse::BadRequest().json(ApiResponse::<()>::error("Slippage too high".into()));
-        }
-    }
-    
-    // Calculate price impact
-    let price_impact = if reserve_out > 0.0 {
-        (amount_out / reserve_out) * 100.0
-    } else {
-        0.0
-    };
-    
-    log::info!("Swap calculated: {} {} -> {} {} (price impact: {:.2}%)", 
-        req.amount_in, req.token_in, amount_out, req.token_out, price_impact);
+    log::info!("Swap executed: {} {} -> {} {} over {} hop(s)", 
+        route.amount_in, req.token_in, route.amount_out, req.token_out, route.hops.len());
     
     let response_time = start.elapsed().as_millis() as u64;
     data.metrics.record_response_time(response_time);
@@ -664,13 +1263,303 @@ pub async fn swap_tokens(
         data.cache.pools.remove("all_pools").await;
     
     HttpResponse::Ok().json(ApiResponse::success(serde_json::json!({
-        "amount_out": amount_out,
-        "price_impact": price_impact,
-        "fee": req.amount_in * 0.003,
+        "transaction_id": tx_id,
+        "amount_out": route.amount_out,
+        "fees": route.fees(),
+        "route": route.hops,
         "response_time_ms": response_time
     })))
 }
 
+/// Best route over the current pools, at most `max_hops` (capped by MAX_SWAP_HOPS) long
+async fn best_swap_route(
+    data: &AppState,
+    token_in: &str,
+    token_out: &str,
+    amount_in: f64,
+    max_hops: Option<usize>,
+) -> Result<exchange::SwapRoute, HttpResponse> {
+    let max_hops = max_hops.unwrap_or(data.max_swap_hops);
+    if max_hops == 0 || max_hops > data.max_swap_hops {
+        return Err(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            format!("max_hops must be between 1 and {}", data.max_swap_hops)
+        )));
+    }
+    let pools = match data.db.get_all_liquidity_pools().await {
+        Ok(pools) => pools,
+        Err(e) => return Err(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string()))),
+    };
+    AMM::best_route(&pools, token_in, token_out, amount_in, max_hops).ok_or_else(|| {
+        log::warn!("No swap route for {}/{} within {} hops", token_in, token_out, max_hops);
+        HttpResponse::NotFound().json(ApiResponse::<()>::error("No liquidity route found".into()))
+    })
+}
+
+/// Quotes the best route for a swap without executing it
+pub async fn quote_swap(data: web::Data<AppState>,
+    query: web::Query<SwapQuoteQuery>
+) -> impl Responder {
+    for token in [&query.token_in, &query.token_out] {
+        if let Err(e) = validation::validate_token_symbol(token) {
+            return HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string()));
+        }
+    }
+    if let Err(e) = validation::validate_amount(query.amount_in) {
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string()));
+    }
+    if query.token_in == query.token_out {
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error("token_in and token_out must differ".into()));
+    }
+
+    match best_swap_route(&data, &query.token_in, &query.token_out, query.amount_in, query.max_hops).await {
+        Ok(route) => HttpResponse::Ok().json(ApiResponse::success(serde_json::json!({
+            "amount_in": route.amount_in,
+            "amount_out": route.amount_out,
+            "route": route.hops
+        }))),
+        Err(resp) => resp,
+    }
+}
+
+/// Highest StableSwap amplification a pool can be created with
+const MAX_AMPLIFICATION: f64 = 10_000.0;
+
+/// Loads `wallet_id` and checks that `secret_key` is its spend key
+async fn authorize_wallet(db: &Database, wallet_id: Uuid, secret_key: &str) -> Result<Wallet, HttpResponse> {
+    match db.get_wallet(wallet_id).await {
+        Ok(Some(wallet)) if wallet.verify_spend_key(secret_key) => Ok(wallet),
+        Ok(Some(_)) => {
+            log::warn!("Invalid spend key for wallet {}", wallet_id);
+            Err(HttpResponse::Unauthorized().json(ApiResponse::<()>::error("Invalid secret key".into())))
+        },
+        Ok(None) => Err(HttpResponse::NotFound().json(ApiResponse::<()>::error("Wallet not found".into()))),
+        Err(e) => Err(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string()))),
+    }
+}
+
+/// Pool of a token pair, in either order
+async fn pair_pool(db: &Database, token_a: &str, token_b: &str) -> Result<LiquidityPool, HttpResponse> {
+    for token in [token_a, token_b] {
+        if let Err(e) = validation::validate_token_symbol(token) {
+            return Err(HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string())));
+        }
+    }
+    match db.get_liquidity_pool(token_a, token_b).await {
+        Ok(Some(pool)) => Ok(pool),
+        Ok(None) => Err(HttpResponse::NotFound().json(ApiResponse::<()>::error("Liquidity pool not found".into()))),
+        Err(e) => Err(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string()))),
+    }
+}
+
+pub async fn create_liquidity_pool(data: web::Data<AppState>,
+    req: web::Json<CreatePoolRequest>
+) -> impl Responder {
+    for token in [&req.token_a, &req.token_b] {
+        if let Err(e) = validation::validate_token_symbol(token) {
+            return HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string()));
+        }
+    }
+    if req.token_a == req.token_b {
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error("A pool needs two different tokens".into()));
+    }
+    let fee_rate = req.fee_rate.unwrap_or(0.003);
+    if !(0.0..1.0).contains(&fee_rate) {
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error("fee_rate must be in [0, 1)".into()));
+    }
+    let Some(curve) = CurveType::parse(req.curve_type.as_deref().unwrap_or("CONSTANT_PRODUCT")) else {
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "Invalid curve type. Must be 'CONSTANT_PRODUCT' or 'STABLE_SWAP'".into()
+        ));
+    };
+    let amplification = match (curve, req.amplification) {
+        (CurveType::StableSwap, Some(amp)) if (1.0..=MAX_AMPLIFICATION).contains(&amp) => amp,
+        (CurveType::StableSwap, _) => {
+            return HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+                format!("StableSwap pools require an amplification between 1 and {}", MAX_AMPLIFICATION)
+            ));
+        },
+        (CurveType::ConstantProduct, None) => 0.0,
+        (CurveType::ConstantProduct, Some(_)) => {
+            return HttpResponse::BadRequest().json(ApiResponse::<()>::error("amplification is only valid for STABLE_SWAP pools".into()));
+        },
+    };
+    if let Err(resp) = authorize_wallet(&data.db, req.wallet_id, &req.secret_key).await {
+        return resp;
+    }
+
+    match data.db.create_liquidity_pool(&req.token_a, &req.token_b, fee_rate, curve, amplification).await {
+        Ok(pool) => {
+            log::info!("{} pool {} created for {}/{}", pool.curve_type, pool.id, pool.token_a, pool.token_b);
+            data.cache.pools.remove("all_pools").await;
+            HttpResponse::Ok().json(ApiResponse::success(serde_json::json!({
+                "pool": pool,
+                "lp_token": pool.lp_token()
+            })))
+        },
+        Err(e) => HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string())),
+    }
+}
+
+pub async fn add_liquidity(data: web::Data<AppState>,
+    req: web::Json<AddLiquidityRequest>
+) -> impl Responder {
+    for amount in [req.amount_a, req.amount_b] {
+        if let Err(e) = validation::validate_amount(amount) {
+            return HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string()));
+        }
+    }
+    let (min_a, min_b) = (req.amount_a_min.unwrap_or(0.0), req.amount_b_min.unwrap_or(0.0));
+    if [min_a, min_b].iter().any(|min| !min.is_finite() || *min < 0.0) {
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error("Minimum amounts must be non-negative numbers".into()));
+    }
+    let wallet = match authorize_wallet(&data.db, req.wallet_id, &req.secret_key).await {
+        Ok(wallet) => wallet,
+        Err(resp) => return resp,
+    };
+    let pool = match pair_pool(&data.db, &req.token_a, &req.token_b).await {
+        Ok(pool) => pool,
+        Err(resp) => return resp,
+    };
+
+    // Amounts in the pool's token order
+    let (amount_a, amount_b, min_a, min_b) = if pool.token_a == req.token_a {
+        (req.amount_a, req.amount_b, min_a, min_b)
+    } else {
+        (req.amount_b, req.amount_a, min_b, min_a)
+    };
+    let signature = wallet.sign_spend(&req.secret_key, &format!(
+        "add_liquidity:{}:{}:{}:{}:{}:{}", req.wallet_id, pool.id, amount_a, amount_b, min_a, min_b
+    ));
+    let quote = data.db.add_liquidity(&req.wallet_id.to_string(), &pool.id, &signature, |p| {
+        AMM::quote_add_liquidity(p, amount_a, amount_b, min_a, min_b)
+    }).await;
+
+    match quote {
+        Ok(quote) => {
+            log::info!("Liquidity added to pool {}: {} {} + {} {} for {} shares", pool.id, quote.amount_a, pool.token_a, quote.amount_b, pool.token_b, quote.shares);
+            data.cache.pools.remove("all_pools").await;
+            HttpResponse::Ok().json(ApiResponse::success(serde_json::json!({
+                "pool_id": pool.id,
+                "lp_token": pool.lp_token(),
+                "token_a": pool.token_a,
+                "token_b": pool.token_b,
+                "amount_a": quote.amount_a,
+                "amount_b": quote.amount_b,
+                "shares": quote.shares,
+                "locked": quote.locked
+            })))
+        },
+        Err(e) => HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string())),
+    }
+}
+
+pub async fn remove_liquidity(data: web::Data<AppState>,
+    req: web::Json<RemoveLiquidityRequest>
+) -> impl Responder {
+    if let Err(e) = validation::validate_amount(req.shares) {
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string()));
+    }
+    let (min_a, min_b) = (req.amount_a_min.unwrap_or(0.0), req.amount_b_min.unwrap_or(0.0));
+    if [min_a, min_b].iter().any(|min| !min.is_finite() || *min < 0.0) {
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error("Minimum amounts must be non-negative numbers".into()));
+    }
+    let wallet = match authorize_wallet(&data.db, req.wallet_id, &req.secret_key).await {
+        Ok(wallet) => wallet,
+        Err(resp) => return resp,
+    };
+    let pool = match pair_pool(&data.db, &req.token_a, &req.token_b).await {
+        Ok(pool) => pool,
+        Err(resp) => return resp,
+    };
+
+    let (min_a, min_b) = if pool.token_a == req.token_a { (min_a, min_b) } else { (min_b, min_a) };
+    let signature = wallet.sign_spend(&req.secret_key, &format!(
+        "remove_liquidity:{}:{}:{}:{}:{}", req.wallet_id, pool.id, req.shares, min_a, min_b
+    ));
+    let shares = req.shares;
+    let quote = data.db.remove_liquidity(&req.wallet_id.to_string(), &pool.id, &signature, |p| {
+        AMM::quote_remove_liquidity(p, shares, min_a, min_b)
+    }).await;
+
+    match quote {
+        Ok(quote) => {
+            log::info!("Liquidity removed from pool {}: {} shares for {} {} + {} {}", pool.id, quote.shares, quote.amount_a, pool.token_a, quote.amount_b, pool.token_b);
+            data.cache.pools.remove("all_pools").await;
+            HttpResponse::Ok().json(ApiResponse::success(serde_json::json!({
+                "pool_id": pool.id,
+                "token_a": pool.token_a,
+                "token_b": pool.token_b,
+                "amount_a": quote.amount_a,
+                "amount_b": quote.amount_b,
+                "shares": quote.shares
+            })))
+        },
+        Err(e) => HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string())),
+    }
+}
+
+/// Sends LP shares of a pool to another wallet
+pub async fn transfer_lp_shares(data: web::Data<AppState>,
+    req: web::Json<TransferSharesRequest>
+) -> impl Responder {
+    if let Err(e) = validation::validate_amount(req.shares) {
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string()));
+    }
+    if req.from_wallet_id == req.to_wallet_id {
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error("Cannot transfer shares to the same wallet".into()));
+    }
+    let wallet = match authorize_wallet(&data.db, req.from_wallet_id, &req.secret_key).await {
+        Ok(wallet) => wallet,
+        Err(resp) => return resp,
+    };
+    match data.db.get_wallet(req.to_wallet_id).await {
+        Ok(Some(_)) => {},
+        Ok(None) => return HttpResponse::NotFound().json(ApiResponse::<()>::error("Recipient wallet not found".into())),
+        Err(e) => return HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
+    }
+    let pool = match pair_pool(&data.db, &req.token_a, &req.token_b).await {
+        Ok(pool) => pool,
+        Err(resp) => return resp,
+    };
+
+    let signature = wallet.sign_spend(&req.secret_key, &format!(
+        "lp_transfer:{}:{}:{}:{}", req.from_wallet_id, req.to_wallet_id, pool.id, req.shares
+    ));
+    match data.db.transfer_lp_shares(&pool, &req.from_wallet_id.to_string(), &req.to_wallet_id.to_string(), req.shares, &signature).await {
+        Ok(tx_id) => HttpResponse::Ok().json(ApiResponse::success(serde_json::json!({
+            "transaction_id": tx_id,
+            "lp_token": pool.lp_token(),
+            "shares": req.shares
+        }))),
+        Err(e) => HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string())),
+    }
+}
+
+/// LP positions of a wallet with the reserves its shares are worth
+pub async fn get_liquidity_positions(data: web::Data<AppState>,
+    path: web::Path<Uuid>
+) -> impl Responder {
+    match data.db.get_liquidity_positions(path.into_inner()).await {
+        Ok(positions) => {
+            let positions: Vec<serde_json::Value> = positions.iter().map(|(pool, shares)| {
+                let share = if pool.total_supply > 0.0 { shares / pool.total_supply } else { 0.0 };
+                serde_json::json!({
+                    "pool_id": pool.id,
+                    "lp_token": pool.lp_token(),
+                    "token_a": pool.token_a,
+                    "token_b": pool.token_b,
+                    "shares": shares,
+                    "share_of_pool": share,
+                    "amount_a": pool.reserve_a * share,
+                    "amount_b": pool.reserve_b * share
+                })
+            }).collect();
+            HttpResponse::Ok().json(ApiResponse::success(positions))
+        },
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
+    }
+}
+
 pub async fn get_liquidity_pools(data: web::Data<AppState>
 ) -> impl Responder {
     // Check cache
@@ -680,14 +1569,7 @@ pub async fn get_liquidity_pools(data: web::Data<AppState>
     }
     
     // Query from DB
-    let pools: Vec<LiquidityPool> = match sqlx::query_as(
-        "SELECT * FROM liquidity_pools ORDER BY total_supply DESC"
-    )
-    .fetch_all(&data.db.pool)
-    .await {
-        Ok(p) => p,
-        Err(_) => vec![], // Return empty if error
-    };
+    let pools: Vec<LiquidityPool> = data.db.get_all_liquidity_pools().await.unwrap_or_default(); // Empty if error
     
     let pools_json: Vec<serde_json::Value> = pools.iter().map(|p| serde_json::json!({
         "id": p.id,
@@ -696,7 +1578,11 @@ pub async fn get_liquidity_pools(data: web::Data<AppState>
         "reserve_a": p.reserve_a,
         "reserve_b": p.reserve_b,
         "total_supply": p.total_supply,
-        "fee_rate": p.fee_rate
+        "fee_rate": p.fee_rate,
+        "fees_a": p.fees_a,
+        "fees_b": p.fees_b,
+        "curve_type": p.curve_type,
+        "amplification": p.amplification
     })).collect();
     
     // Cache the result
@@ -721,42 +1607,375 @@ pub async fn create_order(data: web::Data<AppState>,
     if let Err(e) = validation::validate_order_type(&req.order_type) {
         return HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string()));
     }
-    if let Err(e) = validation::validate_price(req.price) {
-        return HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string()));
+    for price in [req.price, req.stop_price].into_iter().flatten() {
+        if let Err(e) = validation::validate_price(price) {
+            return HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string()));
+        }
     }
     if let Err(e) = validation::validate_amount(req.amount) {
         return HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string()));
     }
+    let slippage = req.max_slippage.unwrap_or(exchange::DEFAULT_MARKET_SLIPPAGE);
+    if !(0.0..1.0).contains(&slippage) {
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error("max_slippage must be in [0, 1)".into()));
+    }
+    let Some(side) = OrderSide::parse(&req.side) else {
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error("Invalid order side".into()));
+    };
+    let tif = req.time_in_force.as_deref().unwrap_or("GTC");
+    if let Err(e) = validation::validate_time_in_force(tif) {
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string()));
+    }
+    let tif = TimeInForce::parse(tif).unwrap_or(TimeInForce::Gtc);
+    match (tif, req.expires_at) {
+        (TimeInForce::Gtd, Some(at)) if at <= chrono::Utc::now().timestamp() => {
+            return HttpResponse::BadRequest().json(ApiResponse::<()>::error("expires_at must be in the future".into()));
+        },
+        (TimeInForce::Gtd, None) => {
+            return HttpResponse::BadRequest().json(ApiResponse::<()>::error("GTD orders require expires_at".into()));
+        },
+        (TimeInForce::Gtc | TimeInForce::Ioc | TimeInForce::Fok, Some(_)) => {
+            return HttpResponse::BadRequest().json(ApiResponse::<()>::error("expires_at is only valid for GTD orders".into()));
+        },
+        _ => {},
+    }
+    let post_only = req.post_only.unwrap_or(false);
+    if post_only && (!req.order_type.eq_ignore_ascii_case("LIMIT") || matches!(tif, TimeInForce::Ioc | TimeInForce::Fok)) {
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error("post_only requires a GTC or GTD limit order".into()));
+    }
+    if let Err(resp) = authorize_wallet(&data.db, req.wallet_id, &req.secret_key).await {
+        return resp;
+    }
     
-    log::info!("Creating order: {} {} {} at {} (amount: {})", req.wallet_id, req.side, req.pair, req.price, req.amount);
-    
-    let order_id = Uuid::new_v4().to_string();
-    
-    // Save order to DB
-    match sqlx::query(
-        "INSERT INTO orders (id, wallet_id, pair, side, order_type, price, amount, status)
-         VALUES (?, ?, ?, ?, ?, ?, ?, 'PENDING')"
-    )
-    .bind(&order_id)
-    .bind(req.wallet_id.to_string())
-    .bind(&req.pair)
-    .bind(&req.side.to_uppercase())
-    .bind(&req.order_type.to_uppercase())
-    .bind(req.price)
-    .bind(req.amount)
-    .execute(&data.db.pool)
-    .await {
-        Ok(_) => {
-            log::info!("Order created: {}", order_id);
-            HttpResponse::Ok().json(ApiResponse::success(serde_json::json!({
-                "order_id": order_id,
-                "status": "PENDING"
-            })))
+    log::info!("Creating order: {} {} {} {} at {:?} (amount: {})", req.wallet_id, req.order_type, req.side, req.pair, req.price, req.amount);
+
+    let stp_mode = match data.db.get_self_trade_mode(req.wallet_id).await {
+        Ok(mode) => mode,
+        Err(e) => return HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
+    };
+    let mut engine = data.exchange.lock().await;
+
+    // Stop orders are stored as the order they become when triggered
+    let (order_type, price, trigger_state) = match (req.order_type.to_uppercase().as_str(), req.stop_price) {
+        ("LIMIT", None) => match req.price {
+            Some(price) => ("LIMIT", price, None),
+            None => return HttpResponse::BadRequest().json(ApiResponse::<()>::error("Limit orders require a price".into())),
+        },
+        ("MARKET", None) => match req.price.or_else(|| engine.best_price(&req.pair, side).map(|best| exchange::slippage_bound(side, best, slippage))) {
+            Some(price) => ("MARKET", price, None),
+            None => return HttpResponse::BadRequest().json(ApiResponse::<()>::error("No liquidity for a market order".into())),
+        },
+        ("STOPLOSS", Some(stop_price)) => match req.price {
+            Some(price) => ("LIMIT", price, Some(exchange::STOP_WAITING)),
+            None => ("MARKET", exchange::slippage_bound(side, stop_price, slippage), Some(exchange::STOP_WAITING)),
         },
+        ("STOPLOSS", None) => return HttpResponse::BadRequest().json(ApiResponse::<()>::error("Stop-loss orders require a stop_price".into())),
+        _ => return HttpResponse::BadRequest().json(ApiResponse::<()>::error("stop_price is only valid for STOPLOSS orders".into())),
+    };
+
+    let order = Order {
+        id: Uuid::new_v4().to_string(),
+        wallet_id: req.wallet_id.to_string(),
+        pair: req.pair.clone(),
+        side: side.as_str().to_string(),
+        order_type: order_type.to_string(),
+        price,
+        amount: req.amount,
+        filled: 0.0,
+        status: OrderStatus::Pending.as_str().to_string(),
+        held: 0.0,
+        stop_price: req.stop_price,
+        trigger_state: trigger_state.map(str::to_string),
+        time_in_force: tif.as_str().to_string(),
+        expires_at: req.expires_at,
+        post_only,
+        stp_mode: stp_mode.as_str().to_string(),
+    };
+
+    if let Err(resp) = check_order_funds(&data.db, &order, engine.fee_rate(), 0.0).await {
+        return resp;
+    }
+    let matched = match engine.plan(order) {
+        Ok(matched) => matched,
+        Err(e) => return HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string())),
+    };
+
+    // Persist first; the book only changes once the trades are settled
+    if let Err(e) = data.db.record_order_match(&matched).await {
+        log::error!("Failed to record order {}: {}", matched.order.id, e);
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string()));
+    }
+    engine.apply(&matched);
+    let stops = run_triggered_stops(&data.db, &mut engine, &matched.order.pair).await;
+    drop(engine);
+
+    log::info!("Order {} {} ({} trades)", matched.order.id, matched.order.status, matched.trades.len());
+    HttpResponse::Ok().json(ApiResponse::success(serde_json::json!({
+        "order_id": matched.order.id,
+        "order_type": matched.order.order_type,
+        "price": matched.order.price,
+        "status": matched.order.status,
+        "trigger_state": matched.order.trigger_state,
+        "time_in_force": matched.order.time_in_force,
+        "filled": matched.order.filled,
+        "trades": matched.trades,
+        "self_trades_prevented": matched.prevented,
+        "stops_triggered": stops,
+    })))
+}
+
+/// Executes the stop orders of `pair` reached by the last trade or oracle price, including
+/// stops reached by the trades of earlier ones; returns their ids. A stop that cannot be
+/// executed is cancelled so it does not block the others.
+async fn run_triggered_stops(db: &Database, engine: &mut MatchingEngine, pair: &str) -> Vec<String> {
+    let mut executed = Vec::new();
+    while let Some(order_id) = engine.next_triggered(pair).map(|o| o.id.clone()) {
+        let matched = match engine.plan_trigger(&order_id) {
+            Ok(matched) => db.record_order_match(&matched).await.map(|_| matched),
+            Err(e) => Err(e),
+        };
+        match matched {
+            Ok(matched) => {
+                log::info!("Stop order {} triggered: {} ({} trades)", order_id, matched.order.status, matched.trades.len());
+                engine.apply(&matched);
+                executed.push(order_id);
+            },
+            Err(e) => {
+                log::error!("Triggered stop order {} failed, cancelling it: {}", order_id, e);
+                if let Err(e) = db.cancel_orders(std::slice::from_ref(&order_id)).await {
+                    log::error!("Failed to cancel stop order {}: {}", order_id, e);
+                }
+                engine.remove(&order_id);
+            },
+        }
+    }
+    executed
+}
+
+/// Rejects an order whose wallet's available balance cannot cover its unfilled part
+/// released: what the order already holds (an amended order), freed when it is re-placed
+async fn check_order_funds(db: &Database, order: &Order, fee_rate: f64, released: f64) -> Result<(), HttpResponse> {
+    let Some((token, required)) = exchange::required_funds(order, fee_rate) else {
+        return Err(HttpResponse::BadRequest().json(ApiResponse::<()>::error("Invalid order".into())));
+    };
+    let wallet_id = Uuid::parse_str(&order.wallet_id)
+        .map_err(|e| HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string())))?;
+    match db.get_available_balance(wallet_id, token).await.map(|available| available + released) {
+        Ok(balance) if balance + exchange::DUST < required => Err(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            format!("Insufficient {} balance. Required: {}, Available: {}", token, required, balance)
+        ))),
+        Ok(_) => Ok(()),
         Err(e) => {
-            log::error!("Failed to create order: {}", e);
-            HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string()))
+            log::error!("Failed to fetch balance: {}", e);
+            Err(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())))
+        },
+    }
+}
+
+/// Open order `order_id` if it belongs to `wallet_id`
+fn owned_open_order<'a>(engine: &'a MatchingEngine, order_id: &str, wallet_id: &Uuid) -> Result<&'a Order, HttpResponse> {
+    match engine.order(order_id) {
+        None => Err(HttpResponse::NotFound().json(ApiResponse::<()>::error("Order is not open".into()))),
+        Some(order) if order.wallet_id != wallet_id.to_string() => {
+            Err(HttpResponse::Forbidden().json(ApiResponse::<()>::error("Order belongs to another wallet".into())))
         },
+        Some(order) => Ok(order),
+    }
+}
+
+pub async fn cancel_order(data: web::Data<AppState>,
+    path: web::Path<String>,
+    req: web::Json<CancelOrderRequest>
+) -> impl Responder {
+    // Ownership is only meaningful once the wallet is authenticated
+    if let Err(resp) = authorize_wallet(&data.db, req.wallet_id, &req.secret_key).await {
+        return resp;
+    }
+    let order_id = path.into_inner();
+    let mut engine = data.exchange.lock().await;
+    if let Err(resp) = owned_open_order(&engine, &order_id, &req.wallet_id) {
+        return resp;
+    }
+
+    if let Err(e) = data.db.cancel_orders(std::slice::from_ref(&order_id)).await {
+        log::error!("Failed to cancel order {}: {}", order_id, e);
+        return HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string()));
+    }
+    let order = engine.remove(&order_id);
+    drop(engine);
+
+    log::info!("Order cancelled: {}", order_id);
+    HttpResponse::Ok().json(ApiResponse::success(serde_json::json!({
+        "order_id": order_id,
+        "status": OrderStatus::Cancelled.as_str(),
+        "filled": order.map(|o| o.filled).unwrap_or(0.0),
+    })))
+}
+
+pub async fn cancel_all_orders(data: web::Data<AppState>,
+    req: web::Json<CancelAllOrdersRequest>
+) -> impl Responder {
+    if let Some(pair) = &req.pair
+        && let Err(e) = validation::validate_pair(pair)
+    {
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string()));
+    }
+    if let Err(resp) = authorize_wallet(&data.db, req.wallet_id, &req.secret_key).await {
+        return resp;
+    }
+
+    let mut engine = data.exchange.lock().await;
+    let order_ids: Vec<String> = engine.open_orders(&req.wallet_id.to_string(), req.pair.as_deref())
+        .into_iter()
+        .map(|o| o.id.clone())
+        .collect();
+
+    if let Err(e) = data.db.cancel_orders(&order_ids).await {
+        log::error!("Failed to cancel orders of {}: {}", req.wallet_id, e);
+        return HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string()));
+    }
+    for order_id in &order_ids {
+        engine.remove(order_id);
+    }
+    drop(engine);
+
+    log::info!("Cancelled {} order(s) of {}", order_ids.len(), req.wallet_id);
+    HttpResponse::Ok().json(ApiResponse::success(serde_json::json!({
+        "cancelled": order_ids,
+    })))
+}
+
+pub async fn amend_order(data: web::Data<AppState>,
+    path: web::Path<String>,
+    req: web::Json<AmendOrderRequest>
+) -> impl Responder {
+    if req.price.is_none() && req.amount.is_none() {
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error("Nothing to amend: set price and/or amount".into()));
+    }
+    if let Some(price) = req.price
+        && let Err(e) = validation::validate_price(price)
+    {
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string()));
+    }
+    if let Some(amount) = req.amount
+        && let Err(e) = validation::validate_amount(amount)
+    {
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string()));
+    }
+    if let Err(resp) = authorize_wallet(&data.db, req.wallet_id, &req.secret_key).await {
+        return resp;
+    }
+
+    let order_id = path.into_inner();
+    let mut engine = data.exchange.lock().await;
+    let current_hold = match owned_open_order(&engine, &order_id, &req.wallet_id) {
+        Ok(order) => order.held,
+        Err(resp) => return resp,
+    };
+    let matched = match engine.plan_amend(&order_id, req.price, req.amount) {
+        Ok(matched) => matched,
+        Err(e) => return HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string())),
+    };
+    if matched.placement == Placement::Requeued
+        && let Err(resp) = check_order_funds(&data.db, &matched.order, engine.fee_rate(), current_hold).await
+    {
+        return resp;
+    }
+
+    if let Err(e) = data.db.record_order_match(&matched).await {
+        log::error!("Failed to amend order {}: {}", order_id, e);
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string()));
+    }
+    engine.apply(&matched);
+    let stops = run_triggered_stops(&data.db, &mut engine, &matched.order.pair).await;
+    drop(engine);
+
+    log::info!("Order {} amended ({} trades)", order_id, matched.trades.len());
+    HttpResponse::Ok().json(ApiResponse::success(serde_json::json!({
+        "order_id": matched.order.id,
+        "price": matched.order.price,
+        "amount": matched.order.amount,
+        "status": matched.order.status,
+        "filled": matched.order.filled,
+        "priority_kept": matched.placement == Placement::Reduced,
+        "trades": matched.trades,
+        "self_trades_prevented": matched.prevented,
+        "stops_triggered": stops,
+    })))
+}
+
+async fn wallet_orders(data: &AppState, wallet_id: Uuid, open: bool, query: WalletOrdersQuery) -> HttpResponse {
+    let limit = query.limit.unwrap_or(100).clamp(1, 1000);
+    let offset = query.offset.unwrap_or(0).max(0);
+    match data.db.get_wallet_orders(wallet_id, open, query.pair.as_deref(), limit, offset).await {
+        Ok(orders) => HttpResponse::Ok().json(ApiResponse::success(serde_json::json!({
+            "orders": orders,
+            "limit": limit,
+            "offset": offset
+        }))),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
+    }
+}
+
+/// Open (resting) orders of a wallet
+pub async fn get_open_orders(data: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    query: web::Query<WalletOrdersQuery>
+) -> impl Responder {
+    wallet_orders(&data, path.into_inner(), true, query.into_inner()).await
+}
+
+/// Filled, cancelled and expired orders of a wallet
+pub async fn get_order_history(data: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    query: web::Query<WalletOrdersQuery>
+) -> impl Responder {
+    wallet_orders(&data, path.into_inner(), false, query.into_inner()).await
+}
+
+pub async fn get_self_trade_mode(data: web::Data<AppState>,
+    path: web::Path<Uuid>
+) -> impl Responder {
+    match data.db.get_self_trade_mode(path.into_inner()).await {
+        Ok(mode) => HttpResponse::Ok().json(ApiResponse::success(serde_json::json!({ "mode": mode.as_str() }))),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
+    }
+}
+
+/// Sets the mode used by the wallet's new orders when they would trade with its own resting orders
+pub async fn set_self_trade_mode(data: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    req: web::Json<SelfTradeModeRequest>
+) -> impl Responder {
+    let wallet_id = path.into_inner();
+    let Some(mode) = SelfTradeMode::parse(&req.mode) else {
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "Invalid mode. Must be 'CANCEL_NEWEST', 'CANCEL_OLDEST', 'CANCEL_BOTH', or 'DECREMENT_AND_CANCEL'".into()
+        ));
+    };
+    if let Err(resp) = authorize_wallet(&data.db, wallet_id, &req.secret_key).await {
+        return resp;
+    }
+    match data.db.set_self_trade_mode(wallet_id, mode).await {
+        Ok(()) => HttpResponse::Ok().json(ApiResponse::success(serde_json::json!({ "mode": mode.as_str() }))),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
+    }
+}
+
+/// Matches of a wallet that self-trade prevention stopped, newest first
+pub async fn get_self_trade_preventions(data: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    query: web::Query<WalletOrdersQuery>
+) -> impl Responder {
+    let limit = query.limit.unwrap_or(100).clamp(1, 1000);
+    let offset = query.offset.unwrap_or(0).max(0);
+    match data.db.get_self_trade_preventions(path.into_inner(), limit, offset).await {
+        Ok(prevented) => HttpResponse::Ok().json(ApiResponse::success(serde_json::json!({
+            "self_trades_prevented": prevented,
+            "limit": limit,
+            "offset": offset
+        }))),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
     }
 }
 
@@ -771,36 +1990,19 @@ pub async fn get_orderbook(data: web::Data<AppState>,
         return HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string()));
     }
     
-    // Query orders from DB
-    let buy_orders: Vec<Order> = match sqlx::query_as(
-        "SELECT * FROM orders WHERE pair = ? AND side = 'BUY' AND status = 'PENDING' ORDER BY price DESC LIMIT 20"
-    )
-    .bind(&pair)
-    .fetch_all(&data.db.pool)
-    .await {
-        Ok(orders) => orders,
-        Err(e) => {
-            log::error!("Failed to fetch buy orders: {}", e);
-            return HttpResponse::InternalServerError().json(ApiResponse::<()>::error("Failed to fetch orderbook".into()));
-        },
-    };
-    
-    let sell_orders: Vec<Order> = match sqlx::query_as(
-        "SELECT * FROM orders WHERE pair = ? AND side = 'SELL' AND status = 'PENDING' ORDER BY price ASC LIMIT 20"
-    )
-    .bind(&pair)
-    .fetch_all(&data.db.pool)
-    .await {
-        Ok(orders) => orders,
-        Err(e) => {
-            log::error!("Failed to fetch sell orders: {}", e);
-            return HttpResponse::InternalServerError().json(ApiResponse::<()>::error("Failed to fetch orderbook".into()));
-        },
+    let engine = data.exchange.lock().await;
+    let (bids, asks): (Vec<Order>, Vec<Order>) = match engine.book(&pair) {
+        Some(book) => (
+            book.bids().iter().take(20).cloned().collect(),
+            book.asks().iter().take(20).cloned().collect(),
+        ),
+        None => (Vec::new(), Vec::new()),
     };
+    drop(engine);
     
     HttpResponse::Ok().json(ApiResponse::success(serde_json::json!({
-        "bids": buy_orders,
-        "asks": sell_orders
+        "bids": bids,
+        "asks": asks
     })))
 }
 
@@ -839,8 +2041,8 @@ pub async fn bridge_assets(data: web::Data<AppState>,
          VALUES (?, ?, ?, ?, ?, ?, 'PENDING')"
     )
     .bind(&bridge_id)
-    .bind(&req.source_chain.to_lowercase())
-    .bind(&req.target_chain.to_lowercase())
+    .bind(req.source_chain.to_lowercase())
+    .bind(req.target_chain.to_lowercase())
     .bind(req.wallet_id.to_string())
     .bind(&req.token_symbol)
     .bind(req.amount)
@@ -884,7 +2086,7 @@ pub async fn get_block(data: web::Data<AppState>,
     }
 }
 
-pub async fn search_explorer(data: web::Data<AppState>,
+pub async fn search_explorer(_data: web::Data<AppState>,
     query: web::Query<std::collections::HashMap<String, String>>
 ) -> impl Responder {
     let search_term = query.get("q").unwrap_or(&"".to_string()).clone();
@@ -943,6 +2145,17 @@ pub async fn get_price(data: web::Data<AppState>,
 pub async fn update_price_feed(data: web::Data<AppState>,
     req: web::Json<UpdatePriceRequest>
 ) -> impl Responder {
+    // Prices move the aggregate and fire stop orders, so only configured oracle sources may post
+    let authorized = data.oracle_keys.get(&req.source)
+        .is_some_and(|key| crate::crypto::constant_time_eq(key.as_bytes(), req.api_key.as_bytes()));
+    if !authorized {
+        log::warn!("Rejected price feed from unauthorized source {}", req.source);
+        return HttpResponse::Unauthorized().json(ApiResponse::<()>::error("Unauthorized oracle source".into()));
+    }
+    if !req.price.is_finite() || req.price <= 0.0 {
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error("Price must be positive".into()));
+    }
+
     let feed_id = Uuid::new_v4().to_string();
     
     // Add price feed
@@ -978,6 +2191,23 @@ pub async fn update_price_feed(data: web::Data<AppState>,
     if let Err(e) = result2 {
         return HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string()));
     }
+
+    // Stop orders also trigger on the oracle price of their pair (base price / quote price)
+    let mut engine = data.exchange.lock().await;
+    for pair in engine.stop_pairs() {
+        let Some((base, quote)) = pair.split_once('/') else { continue };
+        if base != req.token_symbol && quote != req.token_symbol {
+            continue;
+        }
+        if let (Ok(Some(base_price)), Ok(Some(quote_price))) =
+            (data.db.get_aggregated_price(base).await, data.db.get_aggregated_price(quote).await)
+            && quote_price > 0.0
+        {
+            engine.set_oracle_price(&pair, base_price / quote_price);
+            run_triggered_stops(&data.db, &mut engine, &pair).await;
+        }
+    }
+    drop(engine);
     
     HttpResponse::Ok().json(ApiResponse::success("Price updated"))
 }
@@ -998,20 +2228,45 @@ pub async fn create_proposal(data: web::Data<AppState>,
         return HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string()));
     }
     
+    // An upgrade proposal names the governance-controlled contract and the exact new code
+    match (&req.upgrade_contract_address, &req.upgrade_compiled_contract_id) {
+        (None, None) => {},
+        (Some(address), Some(compiled_id)) => {
+            match data.db.get_deployed_contract(address).await {
+                Ok(Some(d)) if d.upgradeable && d.upgrade_authority_type.as_deref() == Some("GOVERNANCE") => {},
+                Ok(Some(_)) => return HttpResponse::BadRequest().json(ApiResponse::<()>::error("Contract is not upgraded by governance".into())),
+                Ok(None) => return HttpResponse::NotFound().json(ApiResponse::<()>::error("Contract not found".into())),
+                Err(e) => return HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
+            }
+            match data.db.get_compiled_contract(compiled_id).await {
+                Ok(Some(_)) => {},
+                Ok(None) => return HttpResponse::NotFound().json(ApiResponse::<()>::error("Compiled contract not found".into())),
+                Err(e) => return HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
+            }
+        },
+        _ => return HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "upgrade_contract_address and upgrade_compiled_contract_id must be given together".into()
+        )),
+    }
+
     log::info!("Creating proposal: {} by {}", req.title, req.proposer_wallet_id);
     
     let proposal_id = format!("QIP-{}", uuid::Uuid::new_v4().to_string().chars().take(8).collect::<String>());
     let id = Uuid::new_v4().to_string();
     
     match sqlx::query(
-        "INSERT INTO proposals (id, proposal_id, title, description, proposer_wallet_id, status)
-         VALUES (?, ?, ?, ?, ?, 'PENDING')"
+        "INSERT INTO proposals (id, proposal_id, title, description, proposer_wallet_id, status, voting_ends_at,
+                                upgrade_contract_address, upgrade_compiled_contract_id)
+         VALUES (?, ?, ?, ?, ?, 'PENDING', datetime('now', '+' || ? || ' seconds'), ?, ?)"
     )
     .bind(&id)
     .bind(&proposal_id)
     .bind(&req.title)
     .bind(&req.description)
     .bind(req.proposer_wallet_id.to_string())
+    .bind(data.voting_period_secs as i64)
+    .bind(&req.upgrade_contract_address)
+    .bind(&req.upgrade_compiled_contract_id)
     .execute(&data.db.pool)
     .await {
         Ok(_) => {
@@ -1058,6 +2313,16 @@ pub async fn vote_proposal(data: web::Data<AppState>,
     if proposal.status != "ACTIVE" && proposal.status != "PENDING" {
         return HttpResponse::BadRequest().json(ApiResponse::<()>::error("Proposal is not active for voting".into()));
     }
+    let voting_open: bool = sqlx::query_scalar(
+        "SELECT voting_ends_at IS NULL OR voting_ends_at > datetime('now') FROM proposals WHERE proposal_id = ?"
+    )
+    .bind(&req.proposal_id)
+    .fetch_one(&data.db.pool)
+    .await
+    .unwrap_or(false);
+    if !voting_open {
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error("Voting period has ended".into()));
+    }
     
     // Check if already voted
     let existing_vote: Option<String> = sqlx::query_scalar(
@@ -1139,6 +2404,23 @@ pub async fn vote_proposal(data: web::Data<AppState>,
     }
 }
 
+/// Closes voting once the period ended and records whether the proposal passed
+pub async fn finalize_proposal(data: web::Data<AppState>,
+    path: web::Path<String>
+) -> impl Responder {
+    let proposal_id = path.into_inner();
+    match data.db.finalize_proposal(&proposal_id).await {
+        Ok(Some(proposal)) => {
+            log::info!("Proposal {} finalized: {}", proposal_id, proposal.status);
+            HttpResponse::Ok().json(ApiResponse::success(proposal))
+        },
+        Ok(None) => HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "Proposal not found, already finalized or still open for voting".into()
+        )),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
+    }
+}
+
 pub async fn get_proposals(data: web::Data<AppState>
 ) -> impl Responder {
     match sqlx::query_as::<_, crate::models::Proposal>(
@@ -1319,10 +2601,94 @@ pub async fn create_multisig_wallet(data: web::Data<AppState>,
     HttpResponse::Ok().json(ApiResponse::success(multisig))
 }
 
+/// Opens a multisig transaction approving one contract upgrade; it needs the multisig's threshold of signatures
+pub async fn create_multisig_upgrade(data: web::Data<AppState>,
+    req: web::Json<CreateMultiSigUpgradeRequest>
+) -> impl Responder {
+    let wallet = match authorize_wallet(&data.db, req.proposer_wallet_id, &req.secret_key).await {
+        Ok(wallet) => wallet,
+        Err(resp) => return resp,
+    };
+    if !is_multisig_signer(&data.db, &req.multisig_id, &wallet.id).await {
+        return HttpResponse::Forbidden().json(ApiResponse::<()>::error("Wallet is not a signer of this multisig".into()));
+    }
+
+    match data.db.get_deployed_contract(&req.contract_address).await {
+        Ok(Some(d)) if d.upgradeable
+            && d.upgrade_authority_type.as_deref() == Some("MULTISIG")
+            && d.upgrade_authority_id.as_deref() == Some(req.multisig_id.as_str()) => {},
+        Ok(Some(_)) => return HttpResponse::BadRequest().json(ApiResponse::<()>::error("Contract is not upgraded by this multisig".into())),
+        Ok(None) => return HttpResponse::NotFound().json(ApiResponse::<()>::error("Contract not found".into())),
+        Err(e) => return HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
+    }
+    match data.db.get_compiled_contract(&req.new_compiled_contract_id).await {
+        Ok(Some(_)) => {},
+        Ok(None) => return HttpResponse::NotFound().json(ApiResponse::<()>::error("Compiled contract not found".into())),
+        Err(e) => return HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
+    }
+
+    let multisig_tx_id = Uuid::new_v4().to_string();
+    let result = sqlx::query(
+        "INSERT INTO multisig_transactions (id, multisig_id, status, required_signatures, upgrade_contract_address, upgrade_compiled_contract_id)
+         SELECT ?, id, 'PENDING', threshold, ?, ? FROM multisig_wallets WHERE id = ?"
+    )
+    .bind(&multisig_tx_id)
+    .bind(&req.contract_address)
+    .bind(&req.new_compiled_contract_id)
+    .bind(&req.multisig_id)
+    .execute(&data.db.pool)
+    .await;
+
+    match result {
+        Ok(r) if r.rows_affected() == 1 => HttpResponse::Ok().json(ApiResponse::success(serde_json::json!({
+            "multisig_tx_id": multisig_tx_id,
+            "contract_address": req.contract_address,
+            "new_compiled_contract_id": req.new_compiled_contract_id,
+            "status": "PENDING"
+        }))),
+        Ok(_) => HttpResponse::NotFound().json(ApiResponse::<()>::error("Multisig wallet not found".into())),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
+    }
+}
+
+/// Whether `wallet_id` is one of the multisig's signers
+async fn is_multisig_signer(db: &Database, multisig_id: &str, wallet_id: &Uuid) -> bool {
+    sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM multisig_signers WHERE multisig_id = ? AND wallet_id = ?)")
+        .bind(multisig_id)
+        .bind(wallet_id.to_string())
+        .fetch_one(&db.pool)
+        .await
+        .unwrap_or(false)
+}
+
 pub async fn sign_multisig_transaction(data: web::Data<AppState>,
     req: web::Json<SignMultiSigRequest>
 ) -> impl Responder {
+    let wallet = match authorize_wallet(&data.db, req.signer_wallet_id, &req.secret_key).await {
+        Ok(wallet) => wallet,
+        Err(resp) => return resp,
+    };
+
+    // Only signers of the owning multisig can sign, and only while it is pending
+    let (multisig_id, status): (String, String) = match sqlx::query_as(
+        "SELECT multisig_id, status FROM multisig_transactions WHERE id = ?"
+    )
+    .bind(&req.multisig_tx_id)
+    .fetch_optional(&data.db.pool)
+    .await {
+        Ok(Some(row)) => row,
+        Ok(None) => return HttpResponse::NotFound().json(ApiResponse::<()>::error("Multisig transaction not found".into())),
+        Err(e) => return HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
+    };
+    if status != "PENDING" {
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error(format!("Multisig transaction is {}", status)));
+    }
+    if !is_multisig_signer(&data.db, &multisig_id, &wallet.id).await {
+        return HttpResponse::Forbidden().json(ApiResponse::<()>::error("Wallet is not a signer of this multisig".into()));
+    }
+
     let signature_id = Uuid::new_v4().to_string();
+    let signature = wallet.sign_spend(&req.secret_key, &format!("multisig:{}", req.multisig_tx_id));
     
     // Add signature
     let result = sqlx::query(
@@ -1332,7 +2698,7 @@ pub async fn sign_multisig_transaction(data: web::Data<AppState>,
     .bind(&signature_id)
     .bind(&req.multisig_tx_id)
     .bind(req.signer_wallet_id.to_string())
-    .bind(&req.signature)
+    .bind(&signature)
     .execute(&data.db.pool)
     .await;
     
@@ -1448,21 +2814,63 @@ pub async fn scan_qr_code(
 pub async fn compile_contract(data: web::Data<AppState>,
     req: web::Json<CompileContractRequest>
 ) -> impl Responder {
-    // Validate source
-    if let Err(e) = ContractCompiler::validate_source(&req.source_code) {
-        return HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string()));
-    }
-    
-    // Compile
-    let compiled = match ContractCompiler::compile_rust_contract(&req.source_code, &req.contract_name) {
-        Ok(c) => c,
-        Err(e) => return HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
+    let compiled = match (&req.source_code, &req.wasm_hex) {
+        (Some(source), None) if req.language == SourceLanguage::Wat => {
+            let compiled = match ContractCompiler::compile_wat_contract(source, &req.contract_name) {
+                Ok(c) => c,
+                // Syntax errors carry line/column for the editor
+                Err(e) => return match e.downcast::<SyntaxError>() {
+                    Ok(syntax) => HttpResponse::BadRequest().json(ApiResponse {
+                        success: false,
+                        error: Some(syntax.to_string()),
+                        data: Some(*syntax),
+                    }),
+                    Err(e) => HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string())),
+                },
+            };
+
+            let wasm_bytes = hex::decode(&compiled.wasm_hex).unwrap_or_default();
+            let report = WasmValidator::validate(&wasm_bytes, &ValidationLimits::default());
+            if !report.valid {
+                return rejected_module(report);
+            }
+            compiled
+        },
+        (Some(source), None) => {
+            // Validate source
+            if let Err(e) = ContractCompiler::validate_source(source) {
+                return HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string()));
+            }
+
+            // Compile
+            match ContractCompiler::compile_rust_contract(source, &req.contract_name) {
+                Ok(c) => c,
+                Err(e) => return HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
+            }
+        },
+        (None, Some(wasm_hex)) => {
+            let wasm_bytes = match hex::decode(wasm_hex.trim_start_matches("0x")) {
+                Ok(b) => b,
+                Err(_) => return HttpResponse::BadRequest().json(ApiResponse::<()>::error("Invalid Hex".into())),
+            };
+
+            let report = WasmValidator::validate(&wasm_bytes, &ValidationLimits::default());
+            if !report.valid {
+                return rejected_module(report);
+            }
+
+            match ContractCompiler::from_wasm_binary(&wasm_bytes, &req.contract_name) {
+                Ok(c) => c,
+                Err(e) => return HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string())),
+            }
+        },
+        _ => return HttpResponse::BadRequest().json(ApiResponse::<()>::error("Provide either source_code or wasm_hex".into())),
     };
     
     // Save to DB
     let result = sqlx::query(
-        "INSERT INTO compiled_contracts (id, contract_name, wasm_hex, source_code, compiler_version, gas_estimate)
-         VALUES (?, ?, ?, ?, ?, ?)"
+        "INSERT INTO compiled_contracts (id, contract_name, wasm_hex, source_code, compiler_version, gas_estimate, abi_json)
+         VALUES (?, ?, ?, ?, ?, ?, ?)"
     )
     .bind(&compiled.id)
     .bind(&compiled.contract_name)
@@ -1470,6 +2878,7 @@ pub async fn compile_contract(data: web::Data<AppState>,
     .bind(compiled.source_code.as_ref())
     .bind(compiled.compiler_version.as_ref())
     .bind(compiled.gas_estimate)
+    .bind(compiled.abi_json.as_ref())
     .execute(&data.db.pool)
     .await;
     
@@ -1480,6 +2889,15 @@ pub async fn compile_contract(data: web::Data<AppState>,
     HttpResponse::Ok().json(ApiResponse::success(compiled))
 }
 
+/// 400 with the full validation report as data
+fn rejected_module(report: ValidationReport) -> HttpResponse {
+    HttpResponse::BadRequest().json(ApiResponse {
+        success: false,
+        error: Some(report.summary()),
+        data: Some(report),
+    })
+}
+
 pub async fn verify_contract(data: web::Data<AppState>,
     req: web::Json<VerifyContractRequest>
 ) -> impl Responder {
@@ -1497,13 +2915,39 @@ pub async fn verify_contract(data: web::Data<AppState>,
         Some(c) => {
             match FormalVerifier::verify_contract(&c, req.properties.clone()) {
                 Ok(result) => HttpResponse::Ok().json(ApiResponse::success(result)),
-                Err(e) => HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
+                Err(e) => HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string())),
             }
         },
         None => HttpResponse::NotFound().json(ApiResponse::<()>::error("Contract not found".into())),
     }
 }
 
+/// Rejects an upgrade authority that does not exist: a typo would lock the contract's upgrades forever
+async fn check_upgrade_authority(db: &Database, kind: UpgradeAuthorityKind, id: Option<&str>) -> Result<(), HttpResponse> {
+    let exists = match (kind, id) {
+        (UpgradeAuthorityKind::Wallet, Some(id)) => match Uuid::parse_str(id) {
+            Ok(wallet_id) => db.get_wallet(wallet_id).await.map(|w| w.is_some()),
+            Err(_) => Ok(false),
+        },
+        (UpgradeAuthorityKind::Multisig, Some(id)) => {
+            sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM multisig_wallets WHERE id = ?)")
+                .bind(id)
+                .fetch_one(&db.pool)
+                .await
+                .map_err(|e| e.into())
+        }
+        (UpgradeAuthorityKind::Governance, _) => Ok(true),
+        (_, None) => Ok(false),
+    };
+    match exists {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            format!("Upgrade authority {} {} not found", kind.as_str(), id.unwrap_or_default())
+        ))),
+        Err(e) => Err(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string()))),
+    }
+}
+
 pub async fn deploy_contract(data: web::Data<AppState>,
     req: web::Json<DeployContractRequest>
 ) -> impl Responder {
@@ -1519,35 +2963,57 @@ pub async fn deploy_contract(data: web::Data<AppState>,
     
     match compiled {
         Some(c) => {
-            let contract_id = format!("CONTRACT-{}", Uuid::new_v4().to_string().chars().take(8).collect::<String>());
-            let address = format!("0x{}", hex::encode(&contract_id.as_bytes()[..20]));
-            let deploy_id = Uuid::new_v4().to_string();
-            
-            // Save deployed contract
-            let result = sqlx::query(
-                "INSERT INTO deployed_contracts (id, contract_id, compiled_contract_id, deployer_wallet_id, address)
-                 VALUES (?, ?, ?, ?, ?)"
-            )
-            .bind(&deploy_id)
-            .bind(&contract_id)
-            .bind(&req.compiled_contract_id)
-            .bind(req.deployer_wallet_id.to_string())
-            .bind(&address)
-            .execute(&data.db.pool)
-            .await;
-            
-            if let Err(e) = result {
-                return HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string()));
+            // Re-check at deploy: rows may predate the validator or limits may have tightened
+            let wasm_bytes = hex::decode(&c.wasm_hex).unwrap_or_default();
+            let report = WasmValidator::validate(&wasm_bytes, &ValidationLimits::default());
+            if !report.valid {
+                return rejected_module(report);
             }
-            
+
+            // Upgrade authority: wallet defaults to the deployer
+            let (authority_type, authority_id) = if req.upgradeable {
+                match (req.upgrade_authority.unwrap_or(UpgradeAuthorityKind::Wallet), &req.upgrade_authority_id) {
+                    (UpgradeAuthorityKind::Wallet, id) => (
+                        Some(UpgradeAuthorityKind::Wallet),
+                        Some(id.clone().unwrap_or_else(|| req.deployer_wallet_id.to_string())),
+                    ),
+                    (UpgradeAuthorityKind::Multisig, Some(id)) => (Some(UpgradeAuthorityKind::Multisig), Some(id.clone())),
+                    (UpgradeAuthorityKind::Multisig, None) => {
+                        return HttpResponse::BadRequest().json(ApiResponse::<()>::error("Multisig authority requires upgrade_authority_id".into()));
+                    }
+                    (UpgradeAuthorityKind::Governance, _) => (Some(UpgradeAuthorityKind::Governance), None),
+                }
+            } else {
+                (None, None)
+            };
+            if let Some(kind) = authority_type
+                && let Err(resp) = check_upgrade_authority(&data.db, kind, authority_id.as_deref()).await
+            {
+                return resp;
+            }
+
+            let contract_id = format!("CONTRACT-{}", Uuid::new_v4().to_string().chars().take(8).collect::<String>());
+            // 20-byte address derived from the ID (the ID itself is only 17 bytes)
+            let address = format!("0x{}", hex::encode(&Sha256::digest(contract_id.as_bytes())[..20]));
+
             let deployed = DeployedContract {
-                id: deploy_id,
+                id: Uuid::new_v4().to_string(),
                 contract_id,
                 compiled_contract_id: Some(req.compiled_contract_id.clone()),
                 deployer_wallet_id: req.deployer_wallet_id.to_string(),
                 address,
                 deployment_tx_id: None,
+                upgradeable: req.upgradeable,
+                upgrade_authority_type: authority_type.map(|k| k.as_str().to_string()),
+                upgrade_authority_id: authority_id,
+                current_version: 1,
+                reentrancy_guard: req.reentrancy_guard,
             };
+
+            // Save deployed contract
+            if let Err(e) = data.db.insert_deployed_contract(&deployed).await {
+                return HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string()));
+            }
             
             HttpResponse::Ok().json(ApiResponse::success(deployed))
         },
@@ -1558,30 +3024,187 @@ pub async fn deploy_contract(data: web::Data<AppState>,
 pub async fn generate_sdk(data: web::Data<AppState>,
     req: web::Json<GenerateSDKRequest>
 ) -> impl Responder {
-    // Get deployed contract
-    let contract: Option<DeployedContract> = sqlx::query_as(
-        "SELECT * FROM deployed_contracts WHERE contract_id = ?"
-    )
-    .bind(&req.contract_id)
-    .fetch_optional(&data.db.pool)
-    .await
-    .ok()
-    .flatten();
-    
-    match contract {
-        Some(c) => {
-            let sdk_code = match req.language.as_str() {
-                "js" | "javascript" => SDKGenerator::generate_js_sdk(&c),
-                "rust" => SDKGenerator::generate_rust_sdk(&c),
-                _ => return HttpResponse::BadRequest().json(ApiResponse::<()>::error("Unsupported language".into())),
+    let language = match SdkLanguage::parse(&req.language) {
+        Some(l) => l,
+        None => return HttpResponse::BadRequest().json(ApiResponse::<()>::error("Unsupported language (typescript, rust or python)".into())),
+    };
+
+    let contract = match data.db.get_deployed_contract(&req.contract_id).await {
+        Ok(Some(c)) => c,
+        Ok(None) => return HttpResponse::NotFound().json(ApiResponse::<()>::error("Contract not found".into())),
+        Err(e) => return HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
+    };
+
+    let compiled = match contract.compiled_contract_id.as_deref() {
+        Some(id) => match data.db.get_compiled_contract(id).await {
+            Ok(c) => c,
+            Err(e) => return HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
+        },
+        None => None,
+    };
+    let abi = match compiled.as_ref().and_then(|c| c.abi_json.as_deref()) {
+        Some(json) => match ContractAbi::from_json(json.as_bytes()) {
+            Ok(abi) => abi,
+            Err(e) => return HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
+        },
+        None => return HttpResponse::BadRequest().json(ApiResponse::<()>::error("Contract has no ABI; SDKs are generated from the qverse.abi section".into())),
+    };
+    let name = compiled.as_ref().map(|c| c.contract_name.as_str()).unwrap_or("Contract");
+    let base_url = req.base_url.as_deref().unwrap_or(DEFAULT_SDK_BASE_URL);
+
+    match SDKGenerator::generate(&contract, name, &abi, language, base_url) {
+        Ok(code) => HttpResponse::Ok().json(ApiResponse::success(serde_json::json!({
+            "language": language.as_str(),
+            "code": code
+        }))),
+        Err(e) => HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string())),
+    }
+}
+
+pub async fn get_contract_abi(data: web::Data<AppState>,
+    path: web::Path<String>
+) -> impl Responder {
+    let address = path.into_inner();
+
+    match data.db.get_contract_abi(&address).await {
+        Ok(Some(abi_json)) => match serde_json::from_str::<ContractAbi>(&abi_json) {
+            Ok(abi) => HttpResponse::Ok().json(ApiResponse::success(abi)),
+            Err(e) => HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
+        },
+        Ok(None) => HttpResponse::NotFound().json(ApiResponse::<()>::error("Contract not found or has no ABI".into())),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
+    }
+}
+
+pub async fn upgrade_contract(data: web::Data<AppState>,
+    path: web::Path<String>,
+    req: web::Json<UpgradeContractRequest>
+) -> impl Responder {
+    let address = path.into_inner();
+    let req = req.into_inner();
+
+    let deployed = match data.db.get_deployed_contract(&address).await {
+        Ok(Some(d)) => d,
+        Ok(None) => return HttpResponse::NotFound().json(ApiResponse::<()>::error("Contract not found".into())),
+        Err(e) => return HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
+    };
+    if !deployed.upgradeable {
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error("Contract is not upgradeable".into()));
+    }
+
+    // The approval must come from the authority fixed at deploy time
+    let approval = match (deployed.upgrade_authority_type.as_deref(), &req.caller_wallet_id, &req.multisig_tx_id, &req.proposal_id) {
+        (Some("WALLET"), Some(caller), _, _) => {
+            if deployed.upgrade_authority_id.as_deref() != Some(caller.to_string().as_str()) {
+                return HttpResponse::Forbidden().json(ApiResponse::<()>::error("Caller is not the upgrade authority".into()));
+            }
+            // The authority ID is public: only its spend key proves the caller holds it
+            if let Err(resp) = authorize_wallet(&data.db, *caller, req.secret_key.as_deref().unwrap_or_default()).await {
+                return resp;
+            }
+            UpgradeApproval::Wallet(caller.to_string())
+        },
+        (Some("MULTISIG"), _, Some(multisig_tx_id), _) => UpgradeApproval::Multisig(multisig_tx_id.clone()),
+        (Some("GOVERNANCE"), _, _, Some(proposal_id)) => UpgradeApproval::Governance(proposal_id.clone()),
+        (authority, _, _, _) => {
+            let needed = match authority {
+                Some("MULTISIG") => "multisig_tx_id",
+                Some("GOVERNANCE") => "proposal_id",
+                _ => "caller_wallet_id",
             };
-            
+            return HttpResponse::Forbidden().json(ApiResponse::<()>::error(format!("Upgrade requires {}", needed)));
+        },
+    };
+
+    if deployed.compiled_contract_id.as_deref() == Some(req.new_compiled_contract_id.as_str()) {
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error("Contract already runs this code".into()));
+    }
+    let compiled = match data.db.get_compiled_contract(&req.new_compiled_contract_id).await {
+        Ok(Some(c)) => c,
+        Ok(None) => return HttpResponse::NotFound().json(ApiResponse::<()>::error("Compiled contract not found".into())),
+        Err(e) => return HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
+    };
+
+    let wasm_bytes = hex::decode(&compiled.wasm_hex).unwrap_or_default();
+    let report = WasmValidator::validate(&wasm_bytes, &ValidationLimits::default());
+    if !report.valid {
+        return rejected_module(report);
+    }
+
+    let block_number = match data.db.current_block_number().await {
+        Ok(n) => n,
+        Err(e) => return HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
+    };
+
+    // Optional migration: new code, existing storage, committed with the upgrade
+    let caller = match &approval {
+        UpgradeApproval::Wallet(wallet_id) => Some(wallet_id.clone()),
+        _ => None,
+    };
+    let tx_id = Uuid::new_v4().to_string();
+    let migration = match &req.migrate_function {
+        Some(function) => {
+            let signature = compiled.abi_json.as_deref()
+                .and_then(|json| serde_json::from_str::<ContractAbi>(json).ok())
+                .and_then(|abi| abi.function(function).map(|f| f.signature()));
+            let ctx = CallContext::new(deployed.address.clone(), Arc::new(DbState::new(data.db.clone())))
+                .with_block_height(block_number.max(0) as u64);
+
+            match data.vm.call_contract(wasm_bytes, function.clone(), req.migrate_args, signature, ctx).await {
+                Ok(output) => Some(output),
+                Err(e) => return HttpResponse::BadRequest().json(ApiResponse::<()>::error(format!("Migration failed: {}", e))),
+            }
+        },
+        None => None,
+    };
+    let record = match (&migration, &req.migrate_function) {
+        (Some(output), Some(function)) => Some(ContractCallRecord {
+            tx_id: &tx_id,
+            caller_wallet_id: caller.as_deref(),
+            contract_address: &deployed.address,
+            function,
+            gas_used: output.gas_used,
+            block_number,
+            events: &output.events,
+            state_diff: &output.state_diff,
+            gas_fee: None,
+            fee_rewards: &[],
+            error: None,
+        }),
+        _ => None,
+    };
+
+    match data.db.upgrade_contract(&deployed, &compiled.id, &approval, record.as_ref()).await {
+        Ok(version) => {
+            log::info!("Contract {} upgraded to version {} ({})", deployed.address, version.version, compiled.id);
             HttpResponse::Ok().json(ApiResponse::success(serde_json::json!({
-                "language": req.language,
-                "code": sdk_code
+                "version": version,
+                "migration": migration,
             })))
         },
-        None => HttpResponse::NotFound().json(ApiResponse::<()>::error("Contract not found".into())),
+        Err(e) => HttpResponse::BadRequest().json(ApiResponse::<()>::error(e.to_string())),
+    }
+}
+
+pub async fn get_contract_versions(data: web::Data<AppState>,
+    path: web::Path<String>
+) -> impl Responder {
+    let deployed = match data.db.get_deployed_contract(&path.into_inner()).await {
+        Ok(Some(d)) => d,
+        Ok(None) => return HttpResponse::NotFound().json(ApiResponse::<()>::error("Contract not found".into())),
+        Err(e) => return HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
+    };
+
+    match data.db.get_contract_versions(&deployed.address).await {
+        Ok(versions) => HttpResponse::Ok().json(ApiResponse::success(serde_json::json!({
+            "address": deployed.address,
+            "upgradeable": deployed.upgradeable,
+            "upgrade_authority_type": deployed.upgrade_authority_type,
+            "upgrade_authority_id": deployed.upgrade_authority_id,
+            "current_version": deployed.current_version,
+            "versions": versions,
+        }))),
+        Err(e) => HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e.to_string())),
     }
 }
 
@@ -1851,9 +3474,15 @@ pub fn config(cfg: &mut web::ServiceConfig) {
             .service(
                 web::resource("/users").route(web::post().to(create_user))
             )
+            .service(
+                web::resource("/wallets/{id}/rekey").route(web::post().to(rekey_wallet))
+            )
             .service(
                 web::resource("/wallets/{id}/balance/{token}").route(web::get().to(get_balance))
             )
+            .service(
+                web::resource("/wallets/{id}/balance/{token}/holds").route(web::get().to(get_balance_holds))
+            )
             .service(
                 web::resource("/wallets/{id}/stake").route(web::get().to(get_stake_info))
             )
@@ -1866,6 +3495,18 @@ pub fn config(cfg: &mut web::ServiceConfig) {
             .service(
                 web::resource("/stake").route(web::post().to(stake))
             )
+            .service(
+                web::resource("/stake/unstake").route(web::post().to(unstake))
+            )
+            .service(
+                web::resource("/stake/claim").route(web::post().to(claim_rewards))
+            )
+            .service(
+                web::resource("/contracts/native").route(web::get().to(list_native_contracts))
+            )
+            .service(
+                web::resource("/fees").route(web::get().to(get_fee_info))
+            )
             // Network & VM Routes
             .service(
                 web::resource("/network/status").route(web::get().to(get_network_status))
@@ -1873,6 +3514,21 @@ pub fn config(cfg: &mut web::ServiceConfig) {
             .service(
                 web::resource("/contracts/execute").route(web::post().to(execute_contract))
             )
+            .service(
+                web::resource("/contracts/simulate").route(web::post().to(simulate_contract))
+            )
+            .service(
+                web::resource("/contracts/{address}/abi").route(web::get().to(get_contract_abi))
+            )
+            .service(
+                web::resource("/contracts/{address}/events").route(web::get().to(get_contract_events))
+            )
+            .service(
+                web::resource("/contracts/{address}/upgrade").route(web::post().to(upgrade_contract))
+            )
+            .service(
+                web::resource("/contracts/{address}/versions").route(web::get().to(get_contract_versions))
+            )
             .service(
                 web::resource("/compliance/iso20022").route(web::post().to(verify_iso20022))
             )
@@ -1884,11 +3540,51 @@ pub fn config(cfg: &mut web::ServiceConfig) {
                 web::resource("/exchange/swap").route(web::post().to(swap_tokens))
             )
             .service(
-                web::resource("/exchange/pools").route(web::get().to(get_liquidity_pools))
+                web::resource("/exchange/pools")
+                    .route(web::get().to(get_liquidity_pools))
+                    .route(web::post().to(create_liquidity_pool))
+            )
+            .service(
+                web::resource("/exchange/quote").route(web::get().to(quote_swap))
+            )
+            .service(
+                web::resource("/exchange/liquidity/add").route(web::post().to(add_liquidity))
+            )
+            .service(
+                web::resource("/exchange/liquidity/remove").route(web::post().to(remove_liquidity))
+            )
+            .service(
+                web::resource("/exchange/liquidity/transfer").route(web::post().to(transfer_lp_shares))
+            )
+            .service(
+                web::resource("/wallets/{id}/liquidity").route(web::get().to(get_liquidity_positions))
             )
             .service(
                 web::resource("/exchange/orders").route(web::post().to(create_order))
             )
+            .service(
+                web::resource("/exchange/orders/cancel_all").route(web::post().to(cancel_all_orders))
+            )
+            .service(
+                web::resource("/exchange/orders/{id}/cancel").route(web::post().to(cancel_order))
+            )
+            .service(
+                web::resource("/exchange/orders/{id}/amend").route(web::post().to(amend_order))
+            )
+            .service(
+                web::resource("/wallets/{id}/orders").route(web::get().to(get_open_orders))
+            )
+            .service(
+                web::resource("/wallets/{id}/orders/history").route(web::get().to(get_order_history))
+            )
+            .service(
+                web::resource("/wallets/{id}/self_trade_prevention")
+                    .route(web::get().to(get_self_trade_mode))
+                    .route(web::post().to(set_self_trade_mode))
+            )
+            .service(
+                web::resource("/wallets/{id}/self_trades").route(web::get().to(get_self_trade_preventions))
+            )
             .service(
                 web::resource("/exchange/orderbook/{pair}").route(web::get().to(get_orderbook))
             )
@@ -1920,6 +3616,9 @@ pub fn config(cfg: &mut web::ServiceConfig) {
             .service(
                 web::resource("/governance/vote").route(web::post().to(vote_proposal))
             )
+            .service(
+                web::resource("/governance/proposal/{proposal_id}/finalize").route(web::post().to(finalize_proposal))
+            )
             // Yield Farming Routes
             .service(
                 web::resource("/yield/pools").route(web::get().to(get_yield_pools))
@@ -1938,6 +3637,9 @@ pub fn config(cfg: &mut web::ServiceConfig) {
             .service(
                 web::resource("/wallets/multisig/sign").route(web::post().to(sign_multisig_transaction))
             )
+            .service(
+                web::resource("/wallets/multisig/upgrade").route(web::post().to(create_multisig_upgrade))
+            )
             .service(
                 web::resource("/wallets/payment/create").route(web::post().to(create_payment_request))
             )
@@ -1983,3 +3685,349 @@ pub fn config(cfg: &mut web::ServiceConfig) {
         web::resource("/ws").route(web::get().to(crate::websocket::websocket_handler))
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::tests::{funded_wallet, memory_db};
+    use actix_web::http::StatusCode;
+    use serde_json::{json, Value};
+
+    fn test_state(db: Database) -> web::Data<AppState> {
+        let mut registry = crate::contracts::ContractRegistry::new();
+        registry.register(Box::new(crate::contracts::StakingContract::new()));
+        web::Data::new(AppState {
+            db,
+            vm: crate::vm::QVM::default(),
+            ai: Arc::new(std::sync::Mutex::new(crate::ai::QMind::new())),
+            network_tx: tokio::sync::mpsc::channel(1).0,
+            connected_peers: Arc::new(std::sync::Mutex::new(Vec::new())),
+            cache: crate::cache::CacheManager::new(),
+            metrics: crate::metrics::Metrics::new(),
+            rate_limiter: crate::middleware::RateLimiter::new(1000, 60),
+            contracts: Arc::new(tokio::sync::Mutex::new(registry)),
+            gas: crate::fees::GasSchedule::default(),
+            exchange: Arc::new(tokio::sync::Mutex::new(MatchingEngine::new(0.001))),
+            max_swap_hops: 3,
+            voting_period_secs: 3600,
+            oracle_keys: std::collections::HashMap::new(),
+        })
+    }
+
+    /// Status and JSON body of a handler's response
+    async fn respond(responder: impl Responder) -> (StatusCode, Value) {
+        let resp = responder.respond_to(&actix_web::test::TestRequest::default().to_http_request()).map_into_boxed_body();
+        let status = resp.status();
+        let body = actix_web::body::to_bytes(resp.into_body()).await.unwrap_or_default();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    fn request<T: serde::de::DeserializeOwned>(body: Value) -> web::Json<T> {
+        web::Json(serde_json::from_value(body).unwrap())
+    }
+
+    async fn compiled_contract(db: &Database, wat: &str) -> String {
+        let id = Uuid::new_v4().to_string();
+        let wasm = wasmer::wat2wasm(wat.as_bytes()).unwrap();
+        sqlx::query("INSERT INTO compiled_contracts (id, contract_name, wasm_hex) VALUES (?, 'Test', ?)")
+            .bind(&id)
+            .bind(hex::encode(&wasm))
+            .execute(&db.pool).await.unwrap();
+        id
+    }
+
+    async fn deploy(data: &web::Data<AppState>, body: Value) -> (StatusCode, Value) {
+        respond(deploy_contract(data.clone(), request(body)).await).await
+    }
+
+    async fn upgrade(data: &web::Data<AppState>, address: &str, body: Value) -> (StatusCode, Value) {
+        respond(upgrade_contract(data.clone(), web::Path::from(address.to_string()), request(body)).await).await
+    }
+
+    const V1: &str = r#"(module (func (export "version") (result i32) (i32.const 1)))"#;
+
+    // migrate(): copies storage slot "a" into slot "b"
+    const V2: &str = r#"(module
+        (import "qverse" "storage_read" (func $sr (param i32 i32 i32 i32) (result i32)))
+        (import "qverse" "storage_write" (func $sw (param i32 i32 i32 i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "ab")
+        (func (export "version") (result i32) (i32.const 2))
+        (func (export "migrate")
+            (call $sw (i32.const 1) (i32.const 1) (i32.const 16)
+                (call $sr (i32.const 0) (i32.const 1) (i32.const 16) (i32.const 8)))))"#;
+
+    #[tokio::test]
+    async fn test_wallet_authority_upgrade_keeps_storage() {
+        let db = memory_db().await;
+        let data = test_state(db.clone());
+        let (owner, owner_sk) = funded_wallet(&db, &[]).await;
+        let (other, other_sk) = funded_wallet(&db, &[]).await;
+        let (v1, v2) = (compiled_contract(&db, V1).await, compiled_contract(&db, V2).await);
+
+        let (status, body) = deploy(&data, json!({
+            "compiled_contract_id": v1, "deployer_wallet_id": owner.id, "upgradeable": true
+        })).await;
+        assert_eq!(status, StatusCode::OK);
+        let address = body["data"]["address"].as_str().unwrap().to_string();
+        sqlx::query("INSERT INTO contract_storage (contract_address, storage_key, storage_value) VALUES (?, '61', '07')")
+            .bind(&address)
+            .execute(&db.pool).await.unwrap();
+
+        // Only the authority, proven by its spend key, may upgrade
+        let (status, _) = upgrade(&data, &address, json!({
+            "new_compiled_contract_id": v2, "caller_wallet_id": other.id, "secret_key": other_sk
+        })).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = upgrade(&data, &address, json!({
+            "new_compiled_contract_id": v2, "caller_wallet_id": owner.id, "secret_key": other_sk
+        })).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, body) = upgrade(&data, &address, json!({
+            "new_compiled_contract_id": v2, "caller_wallet_id": owner.id, "secret_key": owner_sk,
+            "migrate_function": "migrate"
+        })).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["data"]["version"]["version"], json!(2));
+
+        // Storage belongs to the address: the old slot survives and the migration read it
+        assert_eq!(db.get_contract_storage(&address, "61").await.unwrap().as_deref(), Some("07"));
+        assert_eq!(db.get_contract_storage(&address, "62").await.unwrap().as_deref(), Some("07"));
+
+        let versions = db.get_contract_versions(&address).await.unwrap();
+        let history: Vec<_> = versions.iter()
+            .map(|v| (v.version, v.compiled_contract_id.clone(), v.approved_by.clone()))
+            .collect();
+        assert_eq!(history, vec![
+            (1, v1, "DEPLOY".to_string()),
+            (2, v2, format!("WALLET:{}", owner.id)),
+        ]);
+        assert!(versions[1].migration_tx_id.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_multisig_and_governance_approvals_are_single_use() {
+        let db = memory_db().await;
+        let data = test_state(db.clone());
+        let (owner, _) = funded_wallet(&db, &[]).await;
+        let (v1, v2) = (compiled_contract(&db, V1).await, compiled_contract(&db, V2).await);
+
+        sqlx::query("INSERT INTO multisig_wallets (id, address, threshold, total_signers) VALUES ('ms1', 'qvrms1', 1, 1)")
+            .execute(&db.pool).await.unwrap();
+        let (_, body) = deploy(&data, json!({
+            "compiled_contract_id": v1, "deployer_wallet_id": owner.id, "upgradeable": true,
+            "upgrade_authority": "multisig", "upgrade_authority_id": "ms1"
+        })).await;
+        let multisig_address = body["data"]["address"].as_str().unwrap().to_string();
+        let (_, body) = deploy(&data, json!({
+            "compiled_contract_id": v1, "deployer_wallet_id": owner.id, "upgradeable": true,
+            "upgrade_authority": "governance"
+        })).await;
+        let governance_address = body["data"]["address"].as_str().unwrap().to_string();
+
+        for (id, status, code) in [("mtx-pending", "PENDING", &v2), ("mtx-other", "APPROVED", &v1), ("mtx-ok", "APPROVED", &v2)] {
+            sqlx::query(
+                "INSERT INTO multisig_transactions (id, multisig_id, status, required_signatures, upgrade_contract_address, upgrade_compiled_contract_id)
+                 VALUES (?, 'ms1', ?, 1, ?, ?)"
+            )
+            .bind(id).bind(status).bind(&multisig_address).bind(code)
+            .execute(&db.pool).await.unwrap();
+        }
+        for (id, status) in [("prop-open", "PENDING"), ("prop-ok", "PASSED")] {
+            sqlx::query(
+                "INSERT INTO proposals (id, proposal_id, title, description, proposer_wallet_id, status, upgrade_contract_address, upgrade_compiled_contract_id)
+                 VALUES (?, ?, 'Upgrade', '', ?, ?, ?, ?)"
+            )
+            .bind(id).bind(id).bind(owner.id.to_string()).bind(status).bind(&governance_address).bind(&v2)
+            .execute(&db.pool).await.unwrap();
+        }
+
+        // Unapproved, or approving other code
+        for tx in ["mtx-pending", "mtx-other"] {
+            let (status, _) = upgrade(&data, &multisig_address, json!({ "new_compiled_contract_id": v2, "multisig_tx_id": tx })).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+        }
+        let (status, _) = upgrade(&data, &governance_address, json!({ "new_compiled_contract_id": v2, "proposal_id": "prop-open" })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) = upgrade(&data, &multisig_address, json!({ "new_compiled_contract_id": v2, "multisig_tx_id": "mtx-ok" })).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = upgrade(&data, &governance_address, json!({ "new_compiled_contract_id": v2, "proposal_id": "prop-ok" })).await;
+        assert_eq!(status, StatusCode::OK);
+
+        // Consumed: neither approval authorizes the way back
+        let (status, _) = upgrade(&data, &multisig_address, json!({ "new_compiled_contract_id": v1, "multisig_tx_id": "mtx-ok" })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = upgrade(&data, &governance_address, json!({ "new_compiled_contract_id": v1, "proposal_id": "prop-ok" })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let executed: Vec<String> = sqlx::query_scalar("SELECT status FROM multisig_transactions WHERE id = 'mtx-ok' UNION ALL SELECT status FROM proposals WHERE proposal_id = 'prop-ok'")
+            .fetch_all(&db.pool).await.unwrap();
+        assert_eq!(executed, vec!["EXECUTED", "EXECUTED"]);
+        assert_eq!(db.get_contract_versions(&multisig_address).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_deploy_rejects_unknown_upgrade_authority() {
+        let db = memory_db().await;
+        let data = test_state(db.clone());
+        let (owner, _) = funded_wallet(&db, &[]).await;
+        let v1 = compiled_contract(&db, V1).await;
+
+        for (kind, id) in [("wallet", Uuid::new_v4().to_string()), ("wallet", "not-a-uuid".to_string()), ("multisig", "ms-missing".to_string())] {
+            let (status, _) = deploy(&data, json!({
+                "compiled_contract_id": v1, "deployer_wallet_id": owner.id, "upgradeable": true,
+                "upgrade_authority": kind, "upgrade_authority_id": id
+            })).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{} {}", kind, id);
+        }
+    }
+
+    fn buy(wallet: &Wallet, price: f64, amount: f64) -> Order {
+        Order {
+            id: Uuid::new_v4().to_string(),
+            wallet_id: wallet.id.to_string(),
+            pair: "QVR/USDT".to_string(),
+            side: "BUY".to_string(),
+            order_type: "LIMIT".to_string(),
+            price,
+            amount,
+            filled: 0.0,
+            status: OrderStatus::Pending.as_str().to_string(),
+            held: 0.0,
+            stop_price: None,
+            trigger_state: None,
+            time_in_force: "GTC".to_string(),
+            expires_at: None,
+            post_only: false,
+            stp_mode: "CANCEL_NEWEST".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_amend_reprices_a_fully_funded_order() {
+        let db = memory_db().await;
+        let mut engine = MatchingEngine::new(0.0);
+        let (wallet, _) = funded_wallet(&db, &[("USDT", 100.0)]).await;
+
+        // The whole balance is held by the resting order
+        let order = buy(&wallet, 10.0, 10.0);
+        assert!(check_order_funds(&db, &order, engine.fee_rate(), 0.0).await.is_ok());
+        let matched = engine.plan(order).unwrap();
+        db.record_order_match(&matched).await.unwrap();
+        engine.apply(&matched);
+        assert_eq!(db.get_available_balance(wallet.id, "USDT").await.unwrap(), 0.0);
+
+        // Re-pricing counts the hold it releases
+        let held = engine.order(&matched.order.id).unwrap().held;
+        let repriced = engine.plan_amend(&matched.order.id, Some(9.0), None).unwrap();
+        assert_eq!(repriced.placement, Placement::Requeued);
+        assert!(check_order_funds(&db, &repriced.order, engine.fee_rate(), 0.0).await.is_err());
+        assert!(check_order_funds(&db, &repriced.order, engine.fee_rate(), held).await.is_ok());
+        db.record_order_match(&repriced).await.unwrap();
+        engine.apply(&repriced);
+        assert_eq!(db.get_available_balance(wallet.id, "USDT").await.unwrap(), 10.0);
+
+        // More than the released hold plus the free balance is still refused
+        let held = engine.order(&repriced.order.id).unwrap().held;
+        let raised = engine.plan_amend(&repriced.order.id, Some(11.0), None).unwrap();
+        assert!(check_order_funds(&db, &raised.order, engine.fee_rate(), held).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_legacy_wallet_rekey() {
+        let db = memory_db().await;
+        let data = test_state(db.clone());
+        let http_req = actix_web::test::TestRequest::default().to_http_request();
+
+        // Created before spend key hashes: the public key is the secret's first half
+        let legacy_sk = Uuid::new_v4().simple().to_string();
+        let user = db.create_user("legacy").await.unwrap();
+        let (mut wallet, _, _) = Wallet::new(user.id);
+        wallet.spend_public_key = format!("spk_{}", &legacy_sk[0..16]);
+        wallet.spend_key_hash = None;
+        db.save_wallet(&wallet).await.unwrap();
+        assert!(authorize_wallet(&db, wallet.id, &legacy_sk).await.is_err());
+
+        let rekey = |secret_key: &str| {
+            let body = request(json!({ "secret_key": secret_key }));
+            rekey_wallet(data.clone(), http_req.clone(), web::Path::from(wallet.id), body)
+        };
+        let (status, _) = respond(rekey(&format!("{}{}", &legacy_sk[0..16], "0".repeat(15))).await).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = respond(rekey(&Uuid::new_v4().simple().to_string()).await).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, body) = respond(rekey(&legacy_sk).await).await;
+        assert_eq!(status, StatusCode::OK);
+        let new_sk = body["data"]["spend_secret_key"].as_str().unwrap().to_string();
+        assert!(authorize_wallet(&db, wallet.id, &new_sk).await.is_ok());
+        assert!(authorize_wallet(&db, wallet.id, &legacy_sk).await.is_err());
+
+        // One time only
+        let (status, _) = respond(rekey(&legacy_sk).await).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn test_price_feed_needs_the_source_key() {
+        let db = memory_db().await;
+        let mut state = Arc::try_unwrap(test_state(db.clone()).into_inner()).ok().unwrap();
+        state.oracle_keys.insert("chainlink".into(), "secret".into());
+        let data = web::Data::new(state);
+
+        let post = |source: &str, api_key: &str| {
+            let body = request(json!({ "token_symbol": "QVR", "price": 2.0, "source": source, "api_key": api_key }));
+            update_price_feed(data.clone(), body)
+        };
+        for (source, api_key) in [("chainlink", "secreT"), ("chainlink", "secret2"), ("other", "secret"), ("chainlink", "")] {
+            let (status, _) = respond(post(source, api_key).await).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED, "{}:{}", source, api_key);
+        }
+
+        let (status, _) = respond(post("chainlink", "secret").await).await;
+        assert_eq!(status, StatusCode::OK);
+        let price: f64 = sqlx::query_scalar("SELECT price FROM aggregated_prices WHERE token_symbol = 'QVR'")
+            .fetch_one(&db.pool).await.unwrap();
+        assert_eq!(price, 2.0);
+    }
+
+    #[tokio::test]
+    async fn test_pool_creation_and_slippage_bounds() {
+        let db = memory_db().await;
+        let data = test_state(db.clone());
+        let http_req = actix_web::test::TestRequest::default().to_http_request();
+        let (wallet, secret_key) = funded_wallet(&db, &[("QVR", 100.0), ("USDT", 100.0)]).await;
+
+        let create = |secret_key: &str| {
+            let body = request(json!({ "wallet_id": wallet.id, "secret_key": secret_key, "token_a": "QVR", "token_b": "USDT" }));
+            create_liquidity_pool(data.clone(), body)
+        };
+        let (status, _) = respond(create("wrong").await).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = respond(create(&secret_key).await).await;
+        assert_eq!(status, StatusCode::OK);
+
+        // NaN compares false with everything, so it would disable the minimums
+        let mut swap = request::<SwapRequest>(json!({
+            "wallet_id": wallet.id, "secret_key": secret_key, "token_in": "QVR", "token_out": "USDT", "amount_in": 1.0
+        }));
+        swap.min_amount_out = Some(f64::NAN);
+        let (status, _) = respond(swap_tokens(data.clone(), http_req.clone(), swap).await).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let mut add = request::<AddLiquidityRequest>(json!({
+            "wallet_id": wallet.id, "secret_key": secret_key, "token_a": "QVR", "token_b": "USDT", "amount_a": 10.0, "amount_b": 10.0
+        }));
+        add.amount_b_min = Some(f64::NAN);
+        let (status, _) = respond(add_liquidity(data.clone(), add).await).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let mut remove = request::<RemoveLiquidityRequest>(json!({
+            "wallet_id": wallet.id, "secret_key": secret_key, "token_a": "QVR", "token_b": "USDT", "shares": 1.0
+        }));
+        remove.amount_a_min = Some(f64::INFINITY);
+        let (status, _) = respond(remove_liquidity(data.clone(), remove).await).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
@@ -16,7 +16,6 @@
  *   MIT License
  * ============================================== */
 
-use crate::models::{Transaction, TokenSymbol};
 use crate::db::Database;
 use std::error::Error;
 use uuid::Uuid;
@@ -35,9 +34,9 @@ impl BatchOperations {
 
         // Validate all transfers first
         for transfer in &transfers {
-            // Check balances
+            // Check balances (funds held by open orders are not spendable)
             let balance: f64 = sqlx::query_scalar(
-                "SELECT amount FROM balances WHERE wallet_id = ? AND token_symbol = ?"
+                "SELECT amount - held FROM balances WHERE wallet_id = ? AND token_symbol = ?"
             )
             .bind(transfer.from_wallet_id.to_string())
             .bind(&transfer.token_symbol)
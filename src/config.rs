@@ -16,6 +16,7 @@
  *   MIT License
  * ============================================== */
 
+use std::collections::HashMap;
 use std::env;
 use dotenv::dotenv;
 
@@ -28,6 +29,17 @@ pub struct Config {
     pub node_id: Option<String>,
     pub enable_cors: bool,
     pub max_connections: u32,
+    pub vm_workers: usize,
+    pub contract_timeout_ms: u64,
+    pub contract_gas_limit: u64,
+    pub staking_epoch_secs: u64,
+    pub unstake_cooldown_secs: u64,
+    pub min_gas_price: f64,          // QVR per gas unit
+    pub gas_fee_treasury_share: f64, // Treasury's share of gas fees, rest to stakers
+    pub trading_fee_rate: f64,       // Taker fee on order book trades (share of notional)
+    pub max_swap_hops: usize,        // Most pools a routed swap may pass through
+    pub voting_period_secs: u64,     // How long a governance proposal is open for votes
+    pub oracle_keys: HashMap<String, String>, // Price feed source -> API key allowed to post for it
 }
 
 impl Config {
@@ -54,6 +66,54 @@ impl Config {
                 .unwrap_or_else(|_| "5".to_string())
                 .parse()
                 .unwrap_or(5),
+            vm_workers: env::var("VM_WORKERS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_else(num_cpus::get),
+            contract_timeout_ms: env::var("CONTRACT_TIMEOUT_MS")
+                .unwrap_or_else(|_| "5000".to_string())
+                .parse()
+                .unwrap_or(5000),
+            contract_gas_limit: env::var("CONTRACT_GAS_LIMIT")
+                .unwrap_or_else(|_| "10000000".to_string())
+                .parse()
+                .unwrap_or(10_000_000),
+            staking_epoch_secs: env::var("STAKING_EPOCH_SECS")
+                .unwrap_or_else(|_| "86400".to_string())
+                .parse()
+                .unwrap_or(86_400),
+            unstake_cooldown_secs: env::var("UNSTAKE_COOLDOWN_SECS")
+                .unwrap_or_else(|_| "604800".to_string())
+                .parse()
+                .unwrap_or(604_800),
+            min_gas_price: env::var("MIN_GAS_PRICE")
+                .unwrap_or_else(|_| "0.00000001".to_string())
+                .parse()
+                .unwrap_or(0.000_000_01),
+            gas_fee_treasury_share: env::var("GAS_FEE_TREASURY_SHARE")
+                .unwrap_or_else(|_| "0.5".to_string())
+                .parse()
+                .unwrap_or(0.5),
+            trading_fee_rate: env::var("TRADING_FEE_RATE")
+                .unwrap_or_else(|_| "0.001".to_string())
+                .parse()
+                .unwrap_or(0.001),
+            max_swap_hops: env::var("MAX_SWAP_HOPS")
+                .unwrap_or_else(|_| "3".to_string())
+                .parse()
+                .unwrap_or(3),
+            voting_period_secs: env::var("VOTING_PERIOD_SECS")
+                .unwrap_or_else(|_| "259200".to_string())
+                .parse()
+                .unwrap_or(259_200),
+            // "source:key,source:key"; with none configured price feeds cannot be posted
+            oracle_keys: env::var("ORACLE_KEYS")
+                .unwrap_or_default()
+                .split(',')
+                .filter_map(|entry| entry.trim().split_once(':'))
+                .filter(|(source, key)| !source.is_empty() && !key.is_empty())
+                .map(|(source, key)| (source.to_string(), key.to_string()))
+                .collect(),
         }
     }
 
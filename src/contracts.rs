@@ -17,41 +17,242 @@
  *   MIT License
  * ============================================== */
 
-use crate::models::{Transaction, TokenSymbol};
+use crate::config::Config;
+use crate::db::Database;
+use crate::models::Transaction;
+use serde::Serialize;
+use std::collections::HashMap;
 use std::error::Error;
 
-pub trait SmartContract {
+pub const STAKING_CONTRACT_ADDRESS: &str = "contract_staking_v1";
+pub const DEFAULT_EPOCH_SECS: u64 = 86_400;
+pub const DEFAULT_UNSTAKE_COOLDOWN_SECS: u64 = 7 * 86_400;
+const SECONDS_PER_YEAR: f64 = 365.0 * 86_400.0;
+
+pub trait SmartContract: Send + Sync {
     /// Returns the unique address/ID of the contract
     fn address(&self) -> String;
 
-    /// Executes a transaction sent to this contract
-    fn execute(&self, tx: &Transaction) -> Result<ExecutionResult, Box<dyn Error>>;
+    /// Executes a transaction sent to this contract.
+    /// Must not mutate: the returned changes are persisted first, then `apply`'d.
+    fn execute(&self, tx: &Transaction, call: &NativeCall) -> Result<ExecutionResult, Box<dyn Error>>;
+
+    /// Runs a named operation that moves no funds into the contract (e.g. `unstake`)
+    fn invoke(&self, method: &str, _call: &NativeCall) -> Result<ExecutionResult, Box<dyn Error>> {
+        Err(format!("Method '{}' not supported by {}", method, self.address()).into())
+    }
+
+    /// Changes due after `epochs` protocol epochs have elapsed
+    fn on_epoch(&self, _epochs: u64) -> Vec<StateChange> {
+        Vec::new()
+    }
+
+    /// Changes crediting this contract's share of `amount` QVR in gas fees
+    fn on_fees(&self, _amount: f64) -> Vec<StateChange> {
+        Vec::new()
+    }
+
+    /// Applies committed changes to the in-memory state
+    fn apply(&mut self, changes: &[StateChange]);
 
     /// Returns the contract's state (serialized)
     fn get_state(&self) -> String;
 }
 
-#[derive(Debug)]
+/// Decoded view of a transaction addressed to a native contract
+#[derive(Debug, Clone)]
+pub struct NativeCall {
+    pub sender: String, // WalletID
+    pub amount: f64,
+    pub timestamp: i64, // unix seconds
+}
+
+/// State change requested by a native contract, committed with the transaction
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StateChange {
+    Stake { wallet_id: String, amount: f64 },
+    /// Moves `amount` out of the stake into the cooldown queue until `unlock_at` (unix seconds)
+    Unstake { wallet_id: String, amount: f64, unlock_at: i64 },
+    Reward { wallet_id: String, amount: f64 },
+    /// Pays accrued rewards out to the wallet's QVR balance
+    ClaimRewards { wallet_id: String, amount: f64 },
+    /// Epoch start: every current stake becomes eligible for the next epoch's rewards
+    SnapshotStakes,
+}
+
+#[derive(Debug, Serialize)]
 pub struct ExecutionResult {
     pub success: bool,
     pub logs: Vec<String>,
     pub gas_used: f64,
+    pub changes: Vec<StateChange>,
+}
+
+/// Protocol-level contracts addressable like deployed ones (e.g. `contract_staking_v1`)
+pub struct ContractRegistry {
+    contracts: HashMap<String, Box<dyn SmartContract>>,
+    epoch_secs: u64,
+}
+
+impl ContractRegistry {
+    pub fn new() -> Self {
+        Self { contracts: HashMap::new(), epoch_secs: DEFAULT_EPOCH_SECS }
+    }
+
+    /// Registry with every built-in protocol contract
+    pub fn with_defaults(config: &Config) -> Self {
+        let mut registry = Self::new();
+        registry.epoch_secs = config.staking_epoch_secs.max(1);
+        let mut staking = StakingContract::new();
+        staking.epoch_secs = registry.epoch_secs;
+        staking.cooldown_secs = config.unstake_cooldown_secs;
+        registry.register(Box::new(staking));
+        registry
+    }
+
+    pub fn register(&mut self, contract: Box<dyn SmartContract>) {
+        self.contracts.insert(contract.address(), contract);
+    }
+
+    pub fn contains(&self, address: &str) -> bool {
+        self.contracts.contains_key(address)
+    }
+
+    /// (address, state) of every registered contract, sorted by address
+    pub fn states(&self) -> Vec<(String, String)> {
+        let mut states: Vec<(String, String)> = self.contracts.iter()
+            .map(|(address, contract)| (address.clone(), contract.get_state()))
+            .collect();
+        states.sort();
+        states
+    }
+
+    /// Rebuilds in-memory contract state from the ledger at startup
+    pub async fn load(&mut self, db: &Database) -> Result<(), Box<dyn Error>> {
+        let stakes = db.get_all_stakes().await?;
+        // Replays the eligible part of each stake, the snapshot, then what was staked since
+        let eligible = stakes.iter()
+            .map(|(wallet_id, _, _, eligible)| StateChange::Stake { wallet_id: wallet_id.clone(), amount: *eligible });
+        let since = stakes.iter()
+            .flat_map(|(wallet_id, amount, rewards, eligible)| [
+                StateChange::Stake { wallet_id: wallet_id.clone(), amount: amount - eligible },
+                StateChange::Reward { wallet_id: wallet_id.clone(), amount: *rewards },
+            ]);
+        let stakes: Vec<StateChange> = eligible
+            .chain(std::iter::once(StateChange::SnapshotStakes))
+            .chain(since)
+            .collect();
+        if let Some(staking) = self.contracts.get_mut(STAKING_CONTRACT_ADDRESS) {
+            staking.apply(&stakes);
+        }
+        Ok(())
+    }
+
+    /// Routes `tx` to the contract named by its first output and persists the result.
+    /// Callers hold the registry lock across the commit, so plan and state never diverge.
+    pub async fn dispatch(
+        &mut self,
+        db: &Database,
+        tx: &Transaction,
+        sender_wallet_id: &str,
+    ) -> Result<ExecutionResult, Box<dyn Error>> {
+        let address = tx.outputs.first()
+            .map(|o| o.target_key.as_str())
+            .ok_or("Transaction has no outputs")?;
+        let contract = self.contracts.get_mut(address)
+            .ok_or_else(|| format!("Unknown native contract: {}", address))?;
+        let amount = tx.public_amount()
+            .ok_or("Native contract calls require a public amount")?;
+
+        let call = NativeCall { sender: sender_wallet_id.to_string(), amount, timestamp: tx.created_at };
+        let result = contract.execute(tx, &call)?;
+        db.record_native_call(tx, &call, &result.changes).await?;
+        contract.apply(&result.changes);
+        Ok(result)
+    }
+
+    /// Runs `method` on the contract at `address` and persists its changes
+    pub async fn invoke(
+        &mut self,
+        db: &Database,
+        address: &str,
+        method: &str,
+        call: &NativeCall,
+    ) -> Result<ExecutionResult, Box<dyn Error>> {
+        let contract = self.contracts.get_mut(address)
+            .ok_or_else(|| format!("Unknown native contract: {}", address))?;
+        let result = contract.invoke(method, call)?;
+        db.apply_native_changes(&result.changes).await?;
+        contract.apply(&result.changes);
+        Ok(result)
+    }
+
+    /// Settles every epoch completed since the last run (at most once per epoch, across restarts).
+    /// Returns the number of epochs settled.
+    pub async fn advance_epoch(&mut self, db: &Database, now: i64) -> Result<u64, Box<dyn Error>> {
+        let epoch = now / self.epoch_secs as i64;
+        let elapsed = match db.last_staking_epoch().await? {
+            Some(last) if epoch > last => (epoch - last) as u64,
+            Some(_) => return Ok(0),
+            None => 0, // ilk çalıştırma: sadece başlangıç epoch'unu kaydet
+        };
+
+        let changes: Vec<StateChange> = self.contracts.values()
+            .flat_map(|contract| contract.on_epoch(elapsed))
+            .collect();
+        db.record_staking_epoch(epoch, &changes).await?;
+        self.apply_changes(&changes);
+        Ok(elapsed)
+    }
+
+    /// Plans the distribution of `amount` QVR in gas fees; empty if nobody can receive it.
+    /// Callers persist the changes, then `apply_changes` them while holding the lock.
+    pub fn fee_rewards(&self, amount: f64) -> Vec<StateChange> {
+        if amount <= 0.0 {
+            return Vec::new();
+        }
+        self.contracts.values()
+            .flat_map(|contract| contract.on_fees(amount))
+            .collect()
+    }
+
+    /// Applies committed changes to every registered contract
+    pub fn apply_changes(&mut self, changes: &[StateChange]) {
+        for contract in self.contracts.values_mut() {
+            contract.apply(changes);
+        }
+    }
+}
+
+impl Default for ContractRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 // 🏦 Example: Staking Contract
 // Allows users to stake QVR and earn rewards
 pub struct StakingContract {
     pub address: String,
-    pub staked_balances: std::collections::HashMap<String, f64>, // WalletID -> Amount
-    pub reward_rate: f64, // e.g. 0.05 (5%)
+    pub staked_balances: HashMap<String, f64>, // WalletID -> Amount
+    pub eligible_balances: HashMap<String, f64>, // WalletID -> stake held since the epoch started
+    pub pending_rewards: HashMap<String, f64>, // WalletID -> accrued, unclaimed QVR
+    pub reward_rate: f64, // yearly, e.g. 0.05 (5%)
+    pub epoch_secs: u64,
+    pub cooldown_secs: u64,
 }
 
 impl StakingContract {
     pub fn new() -> Self {
         Self {
-            address: "contract_staking_v1".to_string(),
-            staked_balances: std::collections::HashMap::new(),
+            address: STAKING_CONTRACT_ADDRESS.to_string(),
+            staked_balances: HashMap::new(),
+            eligible_balances: HashMap::new(),
+            pending_rewards: HashMap::new(),
             reward_rate: 0.05,
+            epoch_secs: DEFAULT_EPOCH_SECS,
+            cooldown_secs: DEFAULT_UNSTAKE_COOLDOWN_SECS,
         }
     }
 
@@ -60,14 +261,33 @@ impl StakingContract {
         *balance += amount;
     }
 
-    pub fn distribute_rewards(&mut self) -> Vec<(String, f64)> {
+    /// Rewards for one epoch: the yearly `reward_rate` pro-rated to `epoch_secs`,
+    /// paid only on stake held since the epoch started
+    pub fn distribute_rewards(&self) -> Vec<(String, f64)> {
+        let epoch_rate = self.reward_rate * self.epoch_secs as f64 / SECONDS_PER_YEAR;
         let mut rewards = Vec::new();
-        for (wallet, amount) in &self.staked_balances {
-            let reward = amount * self.reward_rate;
-            rewards.push((wallet.clone(), reward));
+        for (wallet, amount) in &self.eligible_balances {
+            let reward = amount * epoch_rate;
+            if reward > 0.0 {
+                rewards.push((wallet.clone(), reward));
+            }
         }
         rewards
     }
+
+    pub fn staked(&self, wallet_id: &str) -> f64 {
+        self.staked_balances.get(wallet_id).copied().unwrap_or(0.0)
+    }
+
+    pub fn pending(&self, wallet_id: &str) -> f64 {
+        self.pending_rewards.get(wallet_id).copied().unwrap_or(0.0)
+    }
+}
+
+impl Default for StakingContract {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl SmartContract for StakingContract {
@@ -75,26 +295,241 @@ impl SmartContract for StakingContract {
         self.address.clone()
     }
 
-    fn execute(&self, tx: &Transaction) -> Result<ExecutionResult, Box<dyn Error>> {
-        // In a real implementation, this would mutate state based on tx data
-        // For now, we simulate success
-        if tx.token_symbol == "QVR" {
-            // Amount is hidden in commitment, so we use a placeholder or decode it if public
-            let amount_placeholder = "HIDDEN_AMOUNT"; 
-            let sender_placeholder = "ANONYMOUS";
-            
-            Ok(ExecutionResult {
-                success: true,
-                logs: vec![format!("Staked {} QVR from {}", amount_placeholder, sender_placeholder)],
-                gas_used: 0.002,
-            })
-        } else {
-            Err("Only QVR can be staked".into())
+    fn execute(&self, tx: &Transaction, call: &NativeCall) -> Result<ExecutionResult, Box<dyn Error>> {
+        if tx.token_symbol != "QVR" {
+            return Err("Only QVR can be staked".into());
+        }
+        if call.amount <= 0.0 {
+            return Err("Stake amount must be positive".into());
+        }
+
+        Ok(ExecutionResult {
+            success: true,
+            logs: vec![format!("Staked {} QVR from {}", call.amount, call.sender)],
+            gas_used: 0.002,
+            changes: vec![StateChange::Stake { wallet_id: call.sender.clone(), amount: call.amount }],
+        })
+    }
+
+    fn invoke(&self, method: &str, call: &NativeCall) -> Result<ExecutionResult, Box<dyn Error>> {
+        let (log, change) = match method {
+            "unstake" => {
+                let staked = self.staked(&call.sender);
+                if call.amount <= 0.0 || call.amount > staked {
+                    return Err(format!("Cannot unstake {} QVR, staked: {}", call.amount, staked).into());
+                }
+                let unlock_at = call.timestamp + self.cooldown_secs as i64;
+                (
+                    format!("Unstaking {} QVR for {}, unlocks at {}", call.amount, call.sender, unlock_at),
+                    StateChange::Unstake { wallet_id: call.sender.clone(), amount: call.amount, unlock_at },
+                )
+            }
+            "claim_rewards" => {
+                let pending = self.pending(&call.sender);
+                if pending <= 0.0 {
+                    return Err("No rewards to claim".into());
+                }
+                (
+                    format!("Claimed {} QVR rewards for {}", pending, call.sender),
+                    StateChange::ClaimRewards { wallet_id: call.sender.clone(), amount: pending },
+                )
+            }
+            _ => return Err(format!("Method '{}' not supported by {}", method, self.address).into()),
+        };
+
+        Ok(ExecutionResult { success: true, logs: vec![log], gas_used: 0.002, changes: vec![change] })
+    }
+
+    /// Stake added after the last settlement earns from the next one on
+    fn on_epoch(&self, epochs: u64) -> Vec<StateChange> {
+        self.distribute_rewards()
+            .into_iter()
+            .map(|(wallet_id, reward)| StateChange::Reward { wallet_id, amount: reward * epochs as f64 })
+            .chain(std::iter::once(StateChange::SnapshotStakes))
+            .collect()
+    }
+
+    /// Fees are shared pro rata to stake
+    fn on_fees(&self, amount: f64) -> Vec<StateChange> {
+        let total: f64 = self.staked_balances.values().sum();
+        if total <= 0.0 {
+            return Vec::new();
+        }
+        self.staked_balances.iter()
+            .map(|(wallet_id, staked)| StateChange::Reward { wallet_id: wallet_id.clone(), amount: amount * staked / total })
+            .collect()
+    }
+
+    fn apply(&mut self, changes: &[StateChange]) {
+        for change in changes {
+            match change {
+                StateChange::Stake { wallet_id, amount } => self.stake(wallet_id.clone(), *amount),
+                StateChange::Unstake { wallet_id, amount, .. } => {
+                    self.stake(wallet_id.clone(), -amount);
+                    let staked = self.staked(wallet_id);
+                    if let Some(eligible) = self.eligible_balances.get_mut(wallet_id) {
+                        *eligible = eligible.min(staked);
+                    }
+                    if staked <= 0.0 {
+                        self.staked_balances.remove(wallet_id);
+                        self.eligible_balances.remove(wallet_id);
+                    }
+                }
+                StateChange::Reward { wallet_id, amount } => {
+                    *self.pending_rewards.entry(wallet_id.clone()).or_insert(0.0) += amount;
+                }
+                StateChange::ClaimRewards { wallet_id, .. } => {
+                    self.pending_rewards.remove(wallet_id);
+                }
+                StateChange::SnapshotStakes => {
+                    self.eligible_balances = self.staked_balances.clone();
+                }
+            }
         }
     }
 
     fn get_state(&self) -> String {
-        format!("Total Stakers: {}", self.staked_balances.len())
+        let total: f64 = self.staked_balances.values().sum();
+        serde_json::json!({
+            "stakers": self.staked_balances.len(),
+            "total_staked": total,
+            "reward_rate": self.reward_rate,
+        }).to_string()
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TokenSymbol;
+
+    #[test]
+    fn test_staking_execute_uses_decoded_call() {
+        let mut staking = StakingContract::new();
+        let tx = Transaction::new_public("w1", STAKING_CONTRACT_ADDRESS, TokenSymbol::QVR, 25.0, 0.0);
+        let call = NativeCall { sender: "w1".into(), amount: tx.public_amount().unwrap(), timestamp: tx.created_at };
+
+        let result = staking.execute(&tx, &call).unwrap();
+        assert_eq!(result.logs, vec!["Staked 25 QVR from w1".to_string()]);
+        assert!(staking.staked_balances.is_empty()); // execute plans, apply commits
+
+        staking.apply(&result.changes);
+        assert_eq!(staking.staked_balances.get("w1"), Some(&25.0));
+    }
+
+    #[test]
+    fn test_unstake_rewards_and_claim() {
+        let mut staking = StakingContract::new();
+        staking.epoch_secs = 365 * 86_400; // one epoch = one year -> reward = 5%
+        staking.apply(&[StateChange::Stake { wallet_id: "w1".into(), amount: 100.0 }, StateChange::SnapshotStakes]);
+
+        let rewards = staking.on_epoch(2);
+        assert_eq!(rewards, vec![StateChange::Reward { wallet_id: "w1".into(), amount: 10.0 }, StateChange::SnapshotStakes]);
+        staking.apply(&rewards);
+
+        let call = NativeCall { sender: "w1".into(), amount: 0.0, timestamp: 1_000 };
+        let claim = staking.invoke("claim_rewards", &call).unwrap();
+        assert_eq!(claim.changes, vec![StateChange::ClaimRewards { wallet_id: "w1".into(), amount: 10.0 }]);
+        staking.apply(&claim.changes);
+        assert!(staking.invoke("claim_rewards", &call).is_err());
+
+        let call = NativeCall { amount: 150.0, ..call.clone() };
+        assert!(staking.invoke("unstake", &call).is_err());
+        let call = NativeCall { amount: 100.0, ..call };
+        let unstake = staking.invoke("unstake", &call).unwrap();
+        assert_eq!(
+            unstake.changes,
+            vec![StateChange::Unstake { wallet_id: "w1".into(), amount: 100.0, unlock_at: 1_000 + DEFAULT_UNSTAKE_COOLDOWN_SECS as i64 }]
+        );
+        staking.apply(&unstake.changes);
+        assert!(staking.staked_balances.is_empty());
+    }
+
+    #[test]
+    fn test_mid_epoch_stake_earns_from_the_next_epoch() {
+        let mut staking = StakingContract::new();
+        staking.epoch_secs = 365 * 86_400;
+        staking.apply(&[StateChange::Stake { wallet_id: "w1".into(), amount: 100.0 }, StateChange::SnapshotStakes]);
+
+        // w2 stakes and w1 withdraws half after the epoch started
+        let call = NativeCall { sender: "w1".into(), amount: 50.0, timestamp: 1_000 };
+        staking.apply(&staking.invoke("unstake", &call).unwrap().changes);
+        staking.apply(&[StateChange::Stake { wallet_id: "w2".into(), amount: 100.0 }]);
+
+        let rewards = staking.on_epoch(1);
+        assert_eq!(rewards, vec![StateChange::Reward { wallet_id: "w1".into(), amount: 2.5 }, StateChange::SnapshotStakes]);
+        staking.apply(&rewards);
+
+        // Both stakes were held for the whole next epoch
+        let mut rewards = staking.on_epoch(1);
+        rewards.sort_by(|a, b| format!("{:?}", a).cmp(&format!("{:?}", b)));
+        assert_eq!(rewards, vec![
+            StateChange::Reward { wallet_id: "w1".into(), amount: 2.5 },
+            StateChange::Reward { wallet_id: "w2".into(), amount: 5.0 },
+            StateChange::SnapshotStakes,
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_eligible_stake_survives_a_reload() {
+        let db = crate::db::tests::memory_db().await;
+        let (w1, _) = crate::db::tests::funded_wallet(&db, &[]).await;
+        let (w2, _) = crate::db::tests::funded_wallet(&db, &[]).await;
+        let (w1, w2) = (w1.id.to_string(), w2.id.to_string());
+
+        let mut registry = ContractRegistry::new();
+        registry.epoch_secs = 365 * 86_400;
+        let mut staking = StakingContract::new();
+        staking.epoch_secs = registry.epoch_secs;
+        registry.register(Box::new(staking));
+
+        let stake = StateChange::Stake { wallet_id: w1.clone(), amount: 100.0 };
+        db.apply_native_changes(std::slice::from_ref(&stake)).await.unwrap();
+        registry.apply_changes(&[stake]);
+        assert_eq!(registry.advance_epoch(&db, 0).await.unwrap(), 0); // first run: snapshot only
+        let stake = StateChange::Stake { wallet_id: w2.clone(), amount: 100.0 };
+        db.apply_native_changes(std::slice::from_ref(&stake)).await.unwrap();
+
+        // A restarted node still knows w2 staked mid-epoch
+        let mut reloaded = ContractRegistry::new();
+        reloaded.epoch_secs = registry.epoch_secs;
+        let mut staking = StakingContract::new();
+        staking.epoch_secs = registry.epoch_secs;
+        reloaded.register(Box::new(staking));
+        reloaded.load(&db).await.unwrap();
+        assert_eq!(reloaded.advance_epoch(&db, 365 * 86_400).await.unwrap(), 1);
+
+        let stakes = db.get_all_stakes().await.unwrap();
+        let rewards: HashMap<_, _> = stakes.iter().map(|(w, _, rewards, _)| (w.clone(), *rewards)).collect();
+        assert_eq!(rewards[&w1], 5.0);
+        assert_eq!(rewards[&w2], 0.0);
+        assert!(stakes.iter().all(|(_, amount, _, eligible)| eligible == amount));
+    }
+
+    #[test]
+    fn test_registry_routes_by_address() {
+        let mut registry = ContractRegistry::new();
+        registry.register(Box::new(StakingContract::new()));
+        assert!(registry.contains(STAKING_CONTRACT_ADDRESS));
+        assert!(!registry.contains("contract_unknown"));
+        assert_eq!(registry.states()[0].0, STAKING_CONTRACT_ADDRESS);
+    }
+
+    #[test]
+    fn test_fee_rewards_pro_rata_to_stake() {
+        let mut registry = ContractRegistry::new();
+        registry.register(Box::new(StakingContract::new()));
+        assert!(registry.fee_rewards(1.0).is_empty()); // no stakers
+
+        registry.apply_changes(&[
+            StateChange::Stake { wallet_id: "w1".into(), amount: 30.0 },
+            StateChange::Stake { wallet_id: "w2".into(), amount: 10.0 },
+        ]);
+        let mut rewards = registry.fee_rewards(2.0);
+        rewards.sort_by(|a, b| format!("{:?}", a).cmp(&format!("{:?}", b)));
+        assert_eq!(rewards, vec![
+            StateChange::Reward { wallet_id: "w1".into(), amount: 1.5 },
+            StateChange::Reward { wallet_id: "w2".into(), amount: 0.5 },
+        ]);
+    }
+}
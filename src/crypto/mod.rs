@@ -60,4 +60,9 @@ impl QuantumCrypto {
             Err(_) => Ok(false),
         }
     }
-}
\ No newline at end of file
+}
+
+/// Equality whose timing does not depend on where the inputs differ (only on their length)
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -33,6 +33,12 @@ pub struct ZKPManager {
     bp_gens: BulletproofGens,
 }
 
+impl Default for ZKPManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ZKPManager {
     /// Initializes ZKP generators
     pub fn new() -> Self {
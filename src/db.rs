@@ -16,10 +16,44 @@
  *   MIT License
  * ============================================== */
 
-use sqlx::{sqlite::SqlitePoolOptions, Pool, Sqlite, Row};
+use sqlx::{sqlite::SqlitePoolOptions, Pool, Sqlite, SqliteConnection, Row};
 use std::error::Error;
 use uuid::Uuid;
-use crate::models::{User, Wallet, TokenSymbol};
+use crate::models::{User, Wallet, CurveType, LiquidityPool, Order, OrderStatus, SelfTradeMode, SelfTradePrevention};
+use crate::contracts::{NativeCall, StateChange};
+use crate::exchange::{LiquidityQuote, OrderMatch, SwapRoute, AMM, DUST};
+use crate::fees::{GasFee, TREASURY_ADDRESS};
+
+/// A finished contract call, ready to be recorded
+#[derive(Clone, Copy)]
+pub struct ContractCallRecord<'a> {
+    pub tx_id: &'a str,
+    pub caller_wallet_id: Option<&'a str>,
+    pub contract_address: &'a str,
+    pub function: &'a str,
+    pub gas_used: u64,
+    pub block_number: i64,
+    pub events: &'a [crate::host::EmittedEvent],
+    pub state_diff: &'a crate::state::StateDiff,
+    pub gas_fee: Option<&'a GasFee>,     // Settles the caller's reservation
+    pub fee_rewards: &'a [StateChange],  // Stakers' share of the fee
+    pub error: Option<&'a str>,          // Failed call: only the fee is settled
+}
+
+/// A contract storage slot changed after the call read it; re-run the call on fresh state
+#[derive(Debug)]
+pub struct StorageConflict {
+    pub contract: String,
+    pub key: String,
+}
+
+impl std::fmt::Display for StorageConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Storage slot {} of {} changed concurrently, retry", self.key, self.contract)
+    }
+}
+
+impl Error for StorageConflict {}
 
 #[derive(Clone)] // Added Clone derive
 pub struct Database {
@@ -77,6 +111,7 @@ impl Database {
                 created_at DATETIME DEFAULT CURRENT_TIMESTAMP
             );"
         ).execute(&self.pool).await?;
+        sqlx::query("ALTER TABLE wallets ADD COLUMN spend_key_hash TEXT").execute(&self.pool).await.ok();
 
         // Balances Table
         sqlx::query(
@@ -119,6 +154,34 @@ impl Database {
                 PRIMARY KEY (wallet_id)
             );"
         ).execute(&self.pool).await?;
+        // Stake held since the last settled epoch started; only it earns that epoch's rewards.
+        // Stakes predating the column count as held since the current epoch started.
+        if sqlx::query("ALTER TABLE stakes ADD COLUMN eligible_amount REAL DEFAULT 0.0").execute(&self.pool).await.is_ok() {
+            sqlx::query("UPDATE stakes SET eligible_amount = amount").execute(&self.pool).await?;
+        }
+
+        // Staking: unstake cooldown queue (unlock_at = unix seconds)
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS unstake_requests (
+                id TEXT PRIMARY KEY,
+                wallet_id TEXT NOT NULL REFERENCES wallets(id),
+                amount REAL NOT NULL,
+                unlock_at INTEGER NOT NULL,
+                status TEXT NOT NULL DEFAULT 'PENDING', -- PENDING, RELEASED
+                requested_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );"
+        ).execute(&self.pool).await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_unstake_pending ON unstake_requests(status, unlock_at)")
+            .execute(&self.pool).await?;
+
+        // Staking: settled reward epochs (one row per epoch, makes accrual idempotent)
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS staking_epochs (
+                epoch INTEGER PRIMARY KEY,
+                total_rewards REAL NOT NULL DEFAULT 0.0,
+                settled_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );"
+        ).execute(&self.pool).await?;
 
         // Exchange: Liquidity Pools
         sqlx::query(
@@ -133,6 +196,15 @@ impl Database {
                 created_at DATETIME DEFAULT CURRENT_TIMESTAMP
             );"
         ).execute(&self.pool).await?;
+        // Swap fees accrued to the LPs, per token
+        sqlx::query("ALTER TABLE liquidity_pools ADD COLUMN fees_a REAL DEFAULT 0.0").execute(&self.pool).await.ok();
+        sqlx::query("ALTER TABLE liquidity_pools ADD COLUMN fees_b REAL DEFAULT 0.0").execute(&self.pool).await.ok();
+        // Pricing curve: CONSTANT_PRODUCT or STABLE_SWAP with its amplification
+        sqlx::query("ALTER TABLE liquidity_pools ADD COLUMN curve_type TEXT DEFAULT 'CONSTANT_PRODUCT'").execute(&self.pool).await.ok();
+        sqlx::query("ALTER TABLE liquidity_pools ADD COLUMN amplification REAL DEFAULT 0.0").execute(&self.pool).await.ok();
+        // One pool per pair, in either token order
+        sqlx::query("CREATE UNIQUE INDEX IF NOT EXISTS idx_pools_pair ON liquidity_pools(MIN(token_a, token_b), MAX(token_a, token_b))")
+            .execute(&self.pool).await.ok();
 
         // Exchange: Orders (Limit Orders)
         sqlx::query(
@@ -166,6 +238,43 @@ impl Database {
                 created_at DATETIME DEFAULT CURRENT_TIMESTAMP
             );"
         ).execute(&self.pool).await?;
+        sqlx::query("ALTER TABLE trades ADD COLUMN maker_order_id TEXT REFERENCES orders(id)").execute(&self.pool).await.ok();
+        // Holds: funds reserved by resting orders (available = amount - held)
+        sqlx::query("ALTER TABLE orders ADD COLUMN held REAL DEFAULT 0.0").execute(&self.pool).await.ok();
+        sqlx::query("ALTER TABLE balances ADD COLUMN held REAL DEFAULT 0.0").execute(&self.pool).await.ok();
+        // Stop orders: trigger price and WAITING / TRIGGERED
+        sqlx::query("ALTER TABLE orders ADD COLUMN stop_price REAL").execute(&self.pool).await.ok();
+        sqlx::query("ALTER TABLE orders ADD COLUMN trigger_state TEXT").execute(&self.pool).await.ok();
+        // Time in force (GTC, IOC, FOK, GTD with expires_at in unix seconds) and maker-only orders
+        sqlx::query("ALTER TABLE orders ADD COLUMN time_in_force TEXT DEFAULT 'GTC'").execute(&self.pool).await.ok();
+        sqlx::query("ALTER TABLE orders ADD COLUMN expires_at INTEGER").execute(&self.pool).await.ok();
+        sqlx::query("ALTER TABLE orders ADD COLUMN post_only BOOLEAN DEFAULT FALSE").execute(&self.pool).await.ok();
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_orders_wallet ON orders(wallet_id, status)")
+            .execute(&self.pool).await?;
+        // Self-trade prevention: per wallet mode, copied onto each order, and the prevented matches
+        sqlx::query("ALTER TABLE orders ADD COLUMN stp_mode TEXT DEFAULT 'CANCEL_NEWEST'").execute(&self.pool).await.ok();
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS trading_settings (
+                wallet_id TEXT PRIMARY KEY REFERENCES wallets(id),
+                stp_mode TEXT NOT NULL DEFAULT 'CANCEL_NEWEST',
+                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );"
+        ).execute(&self.pool).await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS self_trade_preventions (
+                id TEXT PRIMARY KEY,
+                wallet_id TEXT NOT NULL REFERENCES wallets(id),
+                pair TEXT NOT NULL,
+                taker_order_id TEXT NOT NULL,
+                maker_order_id TEXT NOT NULL,
+                mode TEXT NOT NULL,
+                amount REAL NOT NULL,
+                price REAL NOT NULL,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );"
+        ).execute(&self.pool).await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_stp_wallet ON self_trade_preventions(wallet_id, created_at)")
+            .execute(&self.pool).await?;
 
         // Bridge: Cross-Chain Transactions
         sqlx::query(
@@ -262,6 +371,8 @@ impl Database {
                 executed_at DATETIME
             );"
         ).execute(&self.pool).await?;
+        sqlx::query("ALTER TABLE proposals ADD COLUMN upgrade_contract_address TEXT").execute(&self.pool).await.ok();
+        sqlx::query("ALTER TABLE proposals ADD COLUMN upgrade_compiled_contract_id TEXT").execute(&self.pool).await.ok();
 
         // Governance: Votes
         sqlx::query(
@@ -314,10 +425,14 @@ impl Database {
                 compiler_version TEXT,
                 compiled_by TEXT REFERENCES wallets(id),
                 gas_estimate INTEGER,
+                abi_json TEXT,
                 created_at DATETIME DEFAULT CURRENT_TIMESTAMP
             );"
         ).execute(&self.pool).await?;
 
+        // Migration: ABI metadata for contracts compiled before it existed
+        sqlx::query("ALTER TABLE compiled_contracts ADD COLUMN abi_json TEXT").execute(&self.pool).await.ok();
+
         // Developer: Deployed Contracts
         sqlx::query(
             "CREATE TABLE IF NOT EXISTS deployed_contracts (
@@ -327,10 +442,95 @@ impl Database {
                 deployer_wallet_id TEXT NOT NULL REFERENCES wallets(id),
                 address TEXT NOT NULL UNIQUE,
                 deployment_tx_id TEXT REFERENCES transactions(id),
+                upgradeable BOOLEAN NOT NULL DEFAULT FALSE,
+                upgrade_authority_type TEXT, -- WALLET | MULTISIG | GOVERNANCE
+                upgrade_authority_id TEXT,
+                current_version INTEGER NOT NULL DEFAULT 1,
+                reentrancy_guard BOOLEAN NOT NULL DEFAULT TRUE,
                 created_at DATETIME DEFAULT CURRENT_TIMESTAMP
             );"
         ).execute(&self.pool).await?;
 
+        // Migration: upgradeable deployments
+        sqlx::query("ALTER TABLE deployed_contracts ADD COLUMN upgradeable BOOLEAN NOT NULL DEFAULT FALSE").execute(&self.pool).await.ok();
+        sqlx::query("ALTER TABLE deployed_contracts ADD COLUMN upgrade_authority_type TEXT").execute(&self.pool).await.ok();
+        sqlx::query("ALTER TABLE deployed_contracts ADD COLUMN upgrade_authority_id TEXT").execute(&self.pool).await.ok();
+        sqlx::query("ALTER TABLE deployed_contracts ADD COLUMN current_version INTEGER NOT NULL DEFAULT 1").execute(&self.pool).await.ok();
+
+        // Migration: per-contract reentrancy guard (on by default)
+        sqlx::query("ALTER TABLE deployed_contracts ADD COLUMN reentrancy_guard BOOLEAN NOT NULL DEFAULT TRUE").execute(&self.pool).await.ok();
+
+        // Developer: Contract Versions (code history behind a stable address)
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS contract_versions (
+                contract_address TEXT NOT NULL,
+                version INTEGER NOT NULL,
+                compiled_contract_id TEXT NOT NULL REFERENCES compiled_contracts(id),
+                approved_by TEXT NOT NULL, -- DEPLOY, WALLET:<id>, MULTISIG:<tx id>, GOVERNANCE:<proposal id>
+                migration_tx_id TEXT REFERENCES transactions(id),
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (contract_address, version)
+            );"
+        ).execute(&self.pool).await?;
+
+        // Migration: version 1 for contracts deployed before history existed
+        sqlx::query(
+            "INSERT INTO contract_versions (contract_address, version, compiled_contract_id, approved_by)
+             SELECT d.address, 1, d.compiled_contract_id, 'DEPLOY' FROM deployed_contracts d
+             WHERE d.compiled_contract_id IS NOT NULL
+               AND NOT EXISTS (SELECT 1 FROM contract_versions v WHERE v.contract_address = d.address)"
+        ).execute(&self.pool).await?;
+
+        // Developer: Contract Events
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS contract_events (
+                id TEXT PRIMARY KEY,
+                transaction_id TEXT NOT NULL REFERENCES transactions(id),
+                block_number INTEGER NOT NULL,
+                contract_address TEXT NOT NULL,
+                log_index INTEGER NOT NULL,
+                topic0 TEXT,
+                topic1 TEXT,
+                topic2 TEXT,
+                topic3 TEXT,
+                data TEXT NOT NULL,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                UNIQUE(transaction_id, log_index)
+            );"
+        ).execute(&self.pool).await?;
+
+        for index in [
+            "CREATE INDEX IF NOT EXISTS idx_contract_events_contract_block ON contract_events (contract_address, block_number, log_index)",
+            "CREATE INDEX IF NOT EXISTS idx_contract_events_topic0 ON contract_events (topic0, block_number)",
+            "CREATE INDEX IF NOT EXISTS idx_contract_events_topic1 ON contract_events (topic1)",
+            "CREATE INDEX IF NOT EXISTS idx_contract_events_topic2 ON contract_events (topic2)",
+            "CREATE INDEX IF NOT EXISTS idx_contract_events_topic3 ON contract_events (topic3)",
+        ] {
+            sqlx::query(index).execute(&self.pool).await?;
+        }
+
+        // Developer: Contract Storage (hex key/value slots)
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS contract_storage (
+                contract_address TEXT NOT NULL,
+                storage_key TEXT NOT NULL,
+                storage_value TEXT NOT NULL,
+                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (contract_address, storage_key)
+            );"
+        ).execute(&self.pool).await?;
+
+        // Developer: Contract Balances (contracts have no wallet row)
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS contract_balances (
+                contract_address TEXT NOT NULL,
+                token_symbol TEXT NOT NULL,
+                amount REAL DEFAULT 0.0,
+                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (contract_address, token_symbol)
+            );"
+        ).execute(&self.pool).await?;
+
         // Enterprise: Dark Pool Orders
         sqlx::query(
             "CREATE TABLE IF NOT EXISTS dark_pool_orders (
@@ -424,6 +624,8 @@ impl Database {
                 created_at DATETIME DEFAULT CURRENT_TIMESTAMP
             );"
         ).execute(&self.pool).await?;
+        sqlx::query("ALTER TABLE multisig_transactions ADD COLUMN upgrade_contract_address TEXT").execute(&self.pool).await.ok();
+        sqlx::query("ALTER TABLE multisig_transactions ADD COLUMN upgrade_compiled_contract_id TEXT").execute(&self.pool).await.ok();
 
         // Wallet: Multi-Sig Signatures
         sqlx::query(
@@ -521,16 +723,27 @@ impl Database {
 
     pub async fn save_wallet(&self, wallet: &Wallet) -> Result<(), Box<dyn Error>> {
         sqlx::query(
-            "INSERT INTO wallets (id, user_id, address, public_key) VALUES (?, ?, ?, ?)"
+            "INSERT INTO wallets (id, user_id, address, public_key, spend_key_hash) VALUES (?, ?, ?, ?, ?)"
         )
         .bind(wallet.id.to_string())
         .bind(wallet.user_id.to_string())
         .bind(&wallet.address)
         .bind(&wallet.spend_public_key)
+        .bind(&wallet.spend_key_hash)
         .execute(&self.pool).await?;
         Ok(())
     }
 
+    /// Sets the spend key hash of a wallet that has none yet; false if it already had one
+    pub async fn set_spend_key_hash(&self, wallet_id: Uuid, spend_key_hash: &str) -> Result<bool, Box<dyn Error>> {
+        let updated = sqlx::query("UPDATE wallets SET spend_key_hash = ? WHERE id = ? AND spend_key_hash IS NULL")
+            .bind(spend_key_hash)
+            .bind(wallet_id.to_string())
+            .execute(&self.pool).await?
+            .rows_affected();
+        Ok(updated > 0)
+    }
+
     pub async fn set_balance(&self, wallet_id: Uuid, token: &str, amount: f64) -> Result<(), Box<dyn Error>> {
         sqlx::query(
             "INSERT INTO balances (wallet_id, token_symbol, amount) VALUES (?, ?, ?)
@@ -557,6 +770,23 @@ impl Database {
         }
     }
 
+    /// (total, held) of a token; available = total - held
+    pub async fn get_balance_holds(&self, wallet_id: Uuid, token: &str) -> Result<(f64, f64), Box<dyn Error>> {
+        let row: Option<(f64, f64)> = sqlx::query_as(
+            "SELECT amount, held FROM balances WHERE wallet_id = ? AND token_symbol = ?"
+        )
+        .bind(wallet_id.to_string())
+        .bind(token)
+        .fetch_optional(&self.pool).await?;
+        Ok(row.unwrap_or((0.0, 0.0)))
+    }
+
+    /// Balance not held by open orders
+    pub async fn get_available_balance(&self, wallet_id: Uuid, token: &str) -> Result<f64, Box<dyn Error>> {
+        let (amount, held) = self.get_balance_holds(wallet_id, token).await?;
+        Ok(amount - held)
+    }
+
     /// Executes an Atomic Transfer (Balance Check -> Decrement -> Increment -> Record Tx)
     pub async fn process_transfer(
         &self, 
@@ -565,9 +795,9 @@ impl Database {
     ) -> Result<(), Box<dyn Error>> {
         let mut db_tx = self.pool.begin().await?;
 
-        // 1. Check Sender Balance
+        // 1. Check Sender Balance (funds held by open orders are not spendable)
         let sender_balance: f64 = sqlx::query_scalar(
-            "SELECT amount FROM balances WHERE wallet_id = ? AND token_symbol = ?"
+            "SELECT amount - held FROM balances WHERE wallet_id = ? AND token_symbol = ?"
         )
         .bind(sender_wallet_id)
         .bind(&tx.token_symbol)
@@ -576,7 +806,7 @@ impl Database {
 
         // Calculate total amount from outputs (simplified for Public tx)
         // In a real private tx, this would involve range proofs
-        let total_amount: f64 = 0.0; // Placeholder: In Public mode we trust the 'amount' passed implicitly or use metadata
+        let _total_amount: f64 = 0.0; // Placeholder: In Public mode we trust the 'amount' passed implicitly or use metadata
         // For migration compatibility, we assume the API validated the amount passed in separate logic
         // But wait, the Transaction struct doesn't have 'amount' field anymore directly exposed as 'amount' 
         // It has 'fee'. The amount is in the output commitment.
@@ -586,15 +816,7 @@ impl Database {
         // We need to decode the amount from the commitment if it's public.
         // In new_public, we set commitment = "PUBLIC_{amount}".
         
-        let amount = if let Some(output) = tx.outputs.first() {
-            if output.commitment.starts_with("PUBLIC_") {
-                output.commitment.trim_start_matches("PUBLIC_").parse::<f64>().unwrap_or(0.0)
-            } else {
-                0.0
-            }
-        } else {
-            0.0
-        };
+        let amount = tx.public_amount().unwrap_or(0.0);
 
         let total_required = amount + tx.fee;
         if sender_balance < total_required {
@@ -602,11 +824,10 @@ impl Database {
         }
 
         // 2. Decrement Sender
-        let new_sender_bal = sender_balance - total_required;
         sqlx::query(
-            "UPDATE balances SET amount = ? WHERE wallet_id = ? AND token_symbol = ?"
+            "UPDATE balances SET amount = amount - ? WHERE wallet_id = ? AND token_symbol = ?"
         )
-        .bind(new_sender_bal)
+        .bind(total_required)
         .bind(sender_wallet_id)
         .bind(&tx.token_symbol)
         .execute(&mut *db_tx).await?;
@@ -647,59 +868,209 @@ impl Database {
         Ok(())
     }
 
-    /// Executes a Staking Transaction (Balance Decrement -> Stake Increment)
-    pub async fn stake_tokens(
+    /// Persists a native contract call atomically (Balance Check -> Decrement -> Changes -> Record Tx)
+    pub async fn record_native_call(
         &self,
-        wallet_id: Uuid,
-        amount: f64
+        tx: &crate::models::Transaction,
+        call: &NativeCall,
+        changes: &[StateChange],
     ) -> Result<(), Box<dyn Error>> {
         let mut db_tx = self.pool.begin().await?;
 
-        // 1. Check Balance
-        let current_balance: f64 = sqlx::query_scalar(
-            "SELECT amount FROM balances WHERE wallet_id = ? AND token_symbol = 'QVR'"
+        let total_required = call.amount + tx.fee;
+        let debited = sqlx::query(
+            "UPDATE balances SET amount = amount - ?
+             WHERE wallet_id = ? AND token_symbol = ? AND amount - held >= ?"
         )
-        .bind(wallet_id.to_string())
-        .fetch_optional(&mut *db_tx).await?
-        .unwrap_or(0.0);
-
-        if current_balance < amount {
-            return Err("Insufficient QVR balance for staking".into());
+        .bind(total_required)
+        .bind(&call.sender)
+        .bind(&tx.token_symbol)
+        .bind(total_required)
+        .execute(&mut *db_tx).await?
+        .rows_affected();
+        if debited == 0 {
+            return Err(format!("Insufficient {} balance. Required: {}", tx.token_symbol, total_required).into());
         }
 
-        // 2. Deduct Balance
-        let new_balance = current_balance - amount;
+        Self::write_native_changes(&mut db_tx, changes).await?;
+
+        // to_wallet_id kalır NULL: hedef adres data JSON'unda (outputs[0].target_key)
+        let tx_json = serde_json::to_string(tx)?;
         sqlx::query(
-            "UPDATE balances SET amount = ? WHERE wallet_id = ? AND token_symbol = 'QVR'"
+            "INSERT INTO transactions (id, from_wallet_id, to_wallet_id, token_symbol, amount, fee, status, signature, data)
+             VALUES (?, ?, NULL, ?, ?, ?, 'COMPLETED', ?, ?)"
         )
-        .bind(new_balance)
-        .bind(wallet_id.to_string())
+        .bind(&tx.id)
+        .bind(&call.sender)
+        .bind(&tx.token_symbol)
+        .bind(call.amount)
+        .bind(tx.fee)
+        .bind(&tx.signature)
+        .bind(&tx_json)
         .execute(&mut *db_tx).await?;
 
-        // 3. Add to Stakes
-        sqlx::query(
-            "INSERT INTO stakes (wallet_id, amount) VALUES (?, ?)
-             ON CONFLICT(wallet_id) DO UPDATE SET amount = amount + ?"
+        db_tx.commit().await?;
+        Ok(())
+    }
+
+    /// Persists changes from a native contract call that moves no funds in (atomic)
+    pub async fn apply_native_changes(&self, changes: &[StateChange]) -> Result<(), Box<dyn Error>> {
+        let mut db_tx = self.pool.begin().await?;
+        Self::write_native_changes(&mut db_tx, changes).await?;
+        db_tx.commit().await?;
+        Ok(())
+    }
+
+    async fn write_native_changes(conn: &mut SqliteConnection, changes: &[StateChange]) -> Result<(), Box<dyn Error>> {
+        for change in changes {
+            match change {
+                StateChange::Stake { wallet_id, amount } => {
+                    sqlx::query(
+                        "INSERT INTO stakes (wallet_id, amount) VALUES (?, ?)
+                         ON CONFLICT(wallet_id) DO UPDATE SET amount = amount + excluded.amount"
+                    )
+                    .bind(wallet_id)
+                    .bind(amount)
+                    .execute(&mut *conn).await?;
+                }
+                StateChange::Unstake { wallet_id, amount, unlock_at } => {
+                    let updated = sqlx::query(
+                        "UPDATE stakes SET amount = amount - ?, eligible_amount = MIN(eligible_amount, amount - ?)
+                         WHERE wallet_id = ? AND amount >= ?"
+                    )
+                        .bind(amount)
+                        .bind(amount)
+                        .bind(wallet_id)
+                        .bind(amount)
+                        .execute(&mut *conn).await?
+                        .rows_affected();
+                    if updated == 0 {
+                        return Err(format!("Stake of {} is below {}", wallet_id, amount).into());
+                    }
+
+                    sqlx::query("INSERT INTO unstake_requests (id, wallet_id, amount, unlock_at) VALUES (?, ?, ?, ?)")
+                        .bind(Uuid::new_v4().to_string())
+                        .bind(wallet_id)
+                        .bind(amount)
+                        .bind(unlock_at)
+                        .execute(&mut *conn).await?;
+                }
+                StateChange::Reward { wallet_id, amount } => {
+                    sqlx::query("UPDATE stakes SET rewards_accrued = COALESCE(rewards_accrued, 0.0) + ? WHERE wallet_id = ?")
+                        .bind(amount)
+                        .bind(wallet_id)
+                        .execute(&mut *conn).await?;
+                }
+                StateChange::ClaimRewards { wallet_id, amount } => {
+                    let updated = sqlx::query(
+                        "UPDATE stakes SET rewards_accrued = 0.0 WHERE wallet_id = ? AND COALESCE(rewards_accrued, 0.0) > 0"
+                    )
+                    .bind(wallet_id)
+                    .execute(&mut *conn).await?
+                    .rows_affected();
+                    if updated == 0 {
+                        return Err(format!("No rewards to claim for {}", wallet_id).into());
+                    }
+
+                    sqlx::query(
+                        "INSERT INTO balances (wallet_id, token_symbol, amount) VALUES (?, 'QVR', ?)
+                         ON CONFLICT(wallet_id, token_symbol) DO UPDATE SET amount = amount + excluded.amount"
+                    )
+                    .bind(wallet_id)
+                    .bind(amount)
+                    .execute(&mut *conn).await?;
+                }
+                StateChange::SnapshotStakes => {
+                    sqlx::query("UPDATE stakes SET eligible_amount = amount").execute(&mut *conn).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// (wallet_id, staked amount, accrued rewards, amount eligible for the current epoch) for every open stake
+    pub async fn get_all_stakes(&self) -> Result<Vec<(String, f64, f64, f64)>, Box<dyn Error>> {
+        let rows = sqlx::query_as::<_, (String, f64, f64, f64)>(
+            "SELECT wallet_id, amount, COALESCE(rewards_accrued, 0.0), MIN(COALESCE(eligible_amount, 0.0), amount) FROM stakes
+             WHERE amount > 0 OR COALESCE(rewards_accrued, 0.0) > 0"
         )
-        .bind(wallet_id.to_string())
-        .bind(amount)
-        .bind(amount)
-        .execute(&mut *db_tx).await?;
+        .fetch_all(&self.pool).await?;
+        Ok(rows)
+    }
+
+    /// Last settled reward epoch, `None` before the first settlement
+    pub async fn last_staking_epoch(&self) -> Result<Option<i64>, Box<dyn Error>> {
+        let epoch: Option<i64> = sqlx::query_scalar("SELECT MAX(epoch) FROM staking_epochs")
+            .fetch_one(&self.pool).await?;
+        Ok(epoch)
+    }
 
+    /// Marks `epoch` settled together with its rewards; fails if it already was
+    pub async fn record_staking_epoch(&self, epoch: i64, changes: &[StateChange]) -> Result<(), Box<dyn Error>> {
+        let total: f64 = changes.iter()
+            .map(|c| match c {
+                StateChange::Reward { amount, .. } => *amount,
+                _ => 0.0,
+            })
+            .sum();
+
+        let mut db_tx = self.pool.begin().await?;
+        sqlx::query("INSERT INTO staking_epochs (epoch, total_rewards) VALUES (?, ?)")
+            .bind(epoch)
+            .bind(total)
+            .execute(&mut *db_tx).await?;
+        Self::write_native_changes(&mut db_tx, changes).await?;
         db_tx.commit().await?;
         Ok(())
     }
 
-    pub async fn get_stake(&self, wallet_id: Uuid) -> Result<f64, Box<dyn Error>> {
+    /// Pays out every unstake whose cooldown ended by `now`; returns how many were released
+    pub async fn release_unlocked_stakes(&self, now: i64) -> Result<u64, Box<dyn Error>> {
+        let mut db_tx = self.pool.begin().await?;
+        let due: Vec<(String, String, f64)> = sqlx::query_as(
+            "SELECT id, wallet_id, amount FROM unstake_requests WHERE status = 'PENDING' AND unlock_at <= ?"
+        )
+        .bind(now)
+        .fetch_all(&mut *db_tx).await?;
+
+        for (id, wallet_id, amount) in &due {
+            sqlx::query("UPDATE unstake_requests SET status = 'RELEASED' WHERE id = ? AND status = 'PENDING'")
+                .bind(id)
+                .execute(&mut *db_tx).await?;
+            sqlx::query(
+                "INSERT INTO balances (wallet_id, token_symbol, amount) VALUES (?, 'QVR', ?)
+                 ON CONFLICT(wallet_id, token_symbol) DO UPDATE SET amount = amount + excluded.amount"
+            )
+            .bind(wallet_id)
+            .bind(amount)
+            .execute(&mut *db_tx).await?;
+        }
+
+        db_tx.commit().await?;
+        Ok(due.len() as u64)
+    }
+
+    /// (amount, unlock_at) of a wallet's pending unstakes, soonest first
+    pub async fn get_unlocking_stakes(&self, wallet_id: Uuid) -> Result<Vec<(f64, i64)>, Box<dyn Error>> {
+        let rows = sqlx::query_as::<_, (f64, i64)>(
+            "SELECT amount, unlock_at FROM unstake_requests WHERE wallet_id = ? AND status = 'PENDING' ORDER BY unlock_at"
+        )
+        .bind(wallet_id.to_string())
+        .fetch_all(&self.pool).await?;
+        Ok(rows)
+    }
+
+    /// (staked amount, accrued unclaimed rewards)
+    pub async fn get_stake(&self, wallet_id: Uuid) -> Result<(f64, f64), Box<dyn Error>> {
         let row = sqlx::query(
-            "SELECT amount FROM stakes WHERE wallet_id = ?"
+            "SELECT amount, COALESCE(rewards_accrued, 0.0) AS rewards_accrued FROM stakes WHERE wallet_id = ?"
         )
         .bind(wallet_id.to_string())
         .fetch_optional(&self.pool).await?;
 
         match row {
-            Some(r) => Ok(r.try_get("amount")?),
-            None => Ok(0.0),
+            Some(r) => Ok((r.try_get("amount")?, r.try_get("rewards_accrued")?)),
+            None => Ok((0.0, 0.0)),
         }
     }
 
@@ -744,6 +1115,252 @@ impl Database {
         Ok(pool)
     }
 
+    pub async fn get_all_liquidity_pools(&self) -> Result<Vec<LiquidityPool>, Box<dyn Error>> {
+        let pools = sqlx::query_as("SELECT * FROM liquidity_pools ORDER BY total_supply DESC")
+            .fetch_all(&self.pool).await?;
+        Ok(pools)
+    }
+
+    /// Executes a swap through the pools of `pool_ids` in order, atomically: re-quotes every hop on
+    /// the pools' current reserves, debits `amount_in` from the available balance, credits the
+    /// final output, moves the reserves of each pool and records one transaction. Fails without
+    /// changes if the output is below `min_amount_out` or a pool changed while the swap ran.
+    pub async fn execute_swap_route(
+        &self,
+        wallet_id: &str,
+        pool_ids: &[String],
+        token_in: &str,
+        amount_in: f64,
+        min_amount_out: f64,
+        signature: &str,
+    ) -> Result<(String, SwapRoute), Box<dyn Error>> {
+        let mut db_tx = self.pool.begin().await?;
+
+        let mut pools: Vec<LiquidityPool> = Vec::with_capacity(pool_ids.len());
+        for pool_id in pool_ids {
+            let pool = sqlx::query_as("SELECT * FROM liquidity_pools WHERE id = ?")
+                .bind(pool_id)
+                .fetch_optional(&mut *db_tx).await?
+                .ok_or("Liquidity pool not found")?;
+            pools.push(pool);
+        }
+        let route = AMM::quote_route(&pools, token_in, amount_in)?;
+        if route.amount_out < min_amount_out {
+            return Err(format!("Slippage too high: expected min {}, got {}", min_amount_out, route.amount_out).into());
+        }
+
+        let token_out = &route.hops[route.hops.len() - 1].token_out;
+        Self::debit_balance(&mut db_tx, wallet_id, token_in, amount_in).await?;
+        Self::credit_balance(&mut db_tx, wallet_id, token_out, route.amount_out).await?;
+
+        for (pool, quote) in pools.iter().zip(&route.hops) {
+            let a_in = pool.token_a == quote.token_in;
+            let (reserve_a, reserve_b) = if a_in { (quote.reserve_in, quote.reserve_out) } else { (quote.reserve_out, quote.reserve_in) };
+            let (fee_a, fee_b) = if a_in { (quote.fee, 0.0) } else { (0.0, quote.fee) };
+            let updated = sqlx::query(
+                "UPDATE liquidity_pools SET reserve_a = ?, reserve_b = ?, fees_a = fees_a + ?, fees_b = fees_b + ?
+                 WHERE id = ? AND reserve_a = ? AND reserve_b = ?"
+            )
+            .bind(reserve_a)
+            .bind(reserve_b)
+            .bind(fee_a)
+            .bind(fee_b)
+            .bind(&pool.id)
+            .bind(pool.reserve_a)
+            .bind(pool.reserve_b)
+            .execute(&mut *db_tx).await?
+            .rows_affected();
+            if updated == 0 {
+                return Err("Pool reserves changed during the swap, retry".into());
+            }
+        }
+
+        // The fee column is in token_symbol (token_in); later hops charge in other tokens, so every
+        // token's fee is recorded in the data JSON
+        let tx_id = Uuid::new_v4().to_string();
+        let fees = route.fees();
+        let data = serde_json::json!({ "type": "SWAP", "route": route, "fees": fees });
+        sqlx::query(
+            "INSERT INTO transactions (id, from_wallet_id, to_wallet_id, token_symbol, amount, fee, status, signature, data)
+             VALUES (?, ?, NULL, ?, ?, ?, 'COMPLETED', ?, ?)"
+        )
+        .bind(&tx_id)
+        .bind(wallet_id)
+        .bind(token_in)
+        .bind(amount_in)
+        .bind(fees.get(token_in).copied().unwrap_or(0.0))
+        .bind(signature)
+        .bind(data.to_string())
+        .execute(&mut *db_tx).await?;
+
+        db_tx.commit().await?;
+        Ok((tx_id, route))
+    }
+
+    /// Creates an empty pool for a pair; the first deposit sets its price
+    pub async fn create_liquidity_pool(
+        &self,
+        token_a: &str,
+        token_b: &str,
+        fee_rate: f64,
+        curve: CurveType,
+        amplification: f64,
+    ) -> Result<LiquidityPool, Box<dyn Error>> {
+        if self.get_liquidity_pool(token_a, token_b).await?.is_some() {
+            return Err(format!("A {}/{} pool already exists", token_a, token_b).into());
+        }
+        let pool = LiquidityPool {
+            id: Uuid::new_v4().to_string(),
+            token_a: token_a.to_string(),
+            token_b: token_b.to_string(),
+            reserve_a: 0.0,
+            reserve_b: 0.0,
+            total_supply: 0.0,
+            fee_rate,
+            fees_a: 0.0,
+            fees_b: 0.0,
+            curve_type: curve.as_str().to_string(),
+            amplification,
+        };
+        sqlx::query(
+            "INSERT INTO liquidity_pools (id, token_a, token_b, fee_rate, curve_type, amplification) VALUES (?, ?, ?, ?, ?, ?)"
+        )
+        .bind(&pool.id)
+        .bind(&pool.token_a)
+        .bind(&pool.token_b)
+        .bind(pool.fee_rate)
+        .bind(&pool.curve_type)
+        .bind(pool.amplification)
+        .execute(&self.pool).await?;
+        Ok(pool)
+    }
+
+    /// Deposits into `pool_id` as quoted by `quote` on its current reserves: debits both tokens,
+    /// mints the LP shares to the wallet and grows reserves and share supply, in one transaction
+    pub async fn add_liquidity<F>(&self, wallet_id: &str, pool_id: &str, signature: &str, quote: F) -> Result<LiquidityQuote, Box<dyn Error>>
+    where
+        F: FnOnce(&LiquidityPool) -> Result<LiquidityQuote, Box<dyn Error>>,
+    {
+        self.change_liquidity(wallet_id, pool_id, signature, true, quote).await
+    }
+
+    /// Burns LP shares for the tokens quoted by `quote`, in one transaction
+    pub async fn remove_liquidity<F>(&self, wallet_id: &str, pool_id: &str, signature: &str, quote: F) -> Result<LiquidityQuote, Box<dyn Error>>
+    where
+        F: FnOnce(&LiquidityPool) -> Result<LiquidityQuote, Box<dyn Error>>,
+    {
+        self.change_liquidity(wallet_id, pool_id, signature, false, quote).await
+    }
+
+    async fn change_liquidity<F>(&self, wallet_id: &str, pool_id: &str, signature: &str, deposit: bool, quote: F) -> Result<LiquidityQuote, Box<dyn Error>>
+    where
+        F: FnOnce(&LiquidityPool) -> Result<LiquidityQuote, Box<dyn Error>>,
+    {
+        let mut db_tx = self.pool.begin().await?;
+        let pool: LiquidityPool = sqlx::query_as("SELECT * FROM liquidity_pools WHERE id = ?")
+            .bind(pool_id)
+            .fetch_optional(&mut *db_tx).await?
+            .ok_or("Liquidity pool not found")?;
+        let quote = quote(&pool)?;
+        let lp_token = pool.lp_token();
+
+        let (reserve_a, reserve_b, total_supply) = if deposit {
+            Self::debit_balance(&mut db_tx, wallet_id, &pool.token_a, quote.amount_a).await?;
+            Self::debit_balance(&mut db_tx, wallet_id, &pool.token_b, quote.amount_b).await?;
+            Self::credit_balance(&mut db_tx, wallet_id, &lp_token, quote.shares).await?;
+            (pool.reserve_a + quote.amount_a, pool.reserve_b + quote.amount_b, pool.total_supply + quote.shares + quote.locked)
+        } else {
+            Self::debit_balance(&mut db_tx, wallet_id, &lp_token, quote.shares).await?;
+            Self::credit_balance(&mut db_tx, wallet_id, &pool.token_a, quote.amount_a).await?;
+            Self::credit_balance(&mut db_tx, wallet_id, &pool.token_b, quote.amount_b).await?;
+            (pool.reserve_a - quote.amount_a, pool.reserve_b - quote.amount_b, pool.total_supply - quote.shares)
+        };
+
+        let updated = sqlx::query(
+            "UPDATE liquidity_pools SET reserve_a = ?, reserve_b = ?, total_supply = ?
+             WHERE id = ? AND reserve_a = ? AND reserve_b = ? AND total_supply = ?"
+        )
+        .bind(reserve_a)
+        .bind(reserve_b)
+        .bind(total_supply)
+        .bind(pool_id)
+        .bind(pool.reserve_a)
+        .bind(pool.reserve_b)
+        .bind(pool.total_supply)
+        .execute(&mut *db_tx).await?
+        .rows_affected();
+        if updated == 0 {
+            return Err("Pool changed during the operation, retry".into());
+        }
+
+        let data = serde_json::json!({
+            "type": if deposit { "ADD_LIQUIDITY" } else { "REMOVE_LIQUIDITY" },
+            "liquidity": quote,
+        });
+        sqlx::query(
+            "INSERT INTO transactions (id, from_wallet_id, to_wallet_id, token_symbol, amount, fee, status, signature, data)
+             VALUES (?, ?, NULL, ?, ?, 0.0, 'COMPLETED', ?, ?)"
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(wallet_id)
+        .bind(&lp_token)
+        .bind(quote.shares)
+        .bind(signature)
+        .bind(data.to_string())
+        .execute(&mut *db_tx).await?;
+
+        db_tx.commit().await?;
+        Ok(quote)
+    }
+
+    /// Moves LP shares of `pool` between wallets; returns the transaction id
+    pub async fn transfer_lp_shares(
+        &self,
+        pool: &LiquidityPool,
+        from_wallet_id: &str,
+        to_wallet_id: &str,
+        shares: f64,
+        signature: &str,
+    ) -> Result<String, Box<dyn Error>> {
+        let lp_token = pool.lp_token();
+        let mut db_tx = self.pool.begin().await?;
+        Self::debit_balance(&mut db_tx, from_wallet_id, &lp_token, shares).await?;
+        Self::credit_balance(&mut db_tx, to_wallet_id, &lp_token, shares).await?;
+
+        let tx_id = Uuid::new_v4().to_string();
+        sqlx::query(
+            "INSERT INTO transactions (id, from_wallet_id, to_wallet_id, token_symbol, amount, fee, status, signature, data)
+             VALUES (?, ?, ?, ?, ?, 0.0, 'COMPLETED', ?, ?)"
+        )
+        .bind(&tx_id)
+        .bind(from_wallet_id)
+        .bind(to_wallet_id)
+        .bind(&lp_token)
+        .bind(shares)
+        .bind(signature)
+        .bind(serde_json::json!({ "type": "LP_TRANSFER", "pool_id": pool.id }).to_string())
+        .execute(&mut *db_tx).await?;
+
+        db_tx.commit().await?;
+        Ok(tx_id)
+    }
+
+    /// Pools a wallet holds LP shares of, with its share count
+    pub async fn get_liquidity_positions(&self, wallet_id: Uuid) -> Result<Vec<(LiquidityPool, f64)>, Box<dyn Error>> {
+        let rows = sqlx::query(
+            "SELECT p.*, b.amount AS shares FROM balances b JOIN liquidity_pools p ON b.token_symbol = 'LP-' || p.id
+             WHERE b.wallet_id = ? AND b.amount > 0 ORDER BY b.amount DESC"
+        )
+        .bind(wallet_id.to_string())
+        .fetch_all(&self.pool).await?;
+
+        let mut positions = Vec::with_capacity(rows.len());
+        for row in rows {
+            positions.push((sqlx::FromRow::from_row(&row)?, row.get("shares")));
+        }
+        Ok(positions)
+    }
+
     pub async fn get_wallet(&self, wallet_id: Uuid) -> Result<Option<Wallet>, Box<dyn Error>> {
         let wallet: Option<Wallet> = sqlx::query_as(
             "SELECT * FROM wallets WHERE id = ?"
@@ -754,6 +1371,759 @@ impl Database {
         Ok(wallet)
     }
 
+    // --- Developer Helpers ---
+
+    /// ABI JSON of the code behind a deployed contract address
+    pub async fn get_contract_abi(&self, address: &str) -> Result<Option<String>, Box<dyn Error>> {
+        let abi: Option<Option<String>> = sqlx::query_scalar(
+            "SELECT c.abi_json FROM deployed_contracts d
+             JOIN compiled_contracts c ON c.id = d.compiled_contract_id
+             WHERE d.address = ? OR d.contract_id = ?"
+        )
+        .bind(address)
+        .bind(address)
+        .fetch_optional(&self.pool).await?;
+
+        Ok(abi.flatten())
+    }
+
+    /// Deployed contract by address or contract ID
+    pub async fn get_deployed_contract(&self, address: &str) -> Result<Option<crate::models::DeployedContract>, Box<dyn Error>> {
+        let deployed: Option<crate::models::DeployedContract> = sqlx::query_as(
+            "SELECT * FROM deployed_contracts WHERE address = ? OR contract_id = ?"
+        )
+        .bind(address)
+        .bind(address)
+        .fetch_optional(&self.pool).await?;
+
+        Ok(deployed)
+    }
+
+    pub async fn get_compiled_contract(&self, id: &str) -> Result<Option<crate::models::CompiledContract>, Box<dyn Error>> {
+        let compiled: Option<crate::models::CompiledContract> = sqlx::query_as(
+            "SELECT * FROM compiled_contracts WHERE id = ?"
+        )
+        .bind(id)
+        .fetch_optional(&self.pool).await?;
+
+        Ok(compiled)
+    }
+
+    /// Height of the latest block (0 before the first block)
+    pub async fn current_block_number(&self) -> Result<i64, Box<dyn Error>> {
+        let height: i64 = sqlx::query_scalar("SELECT COALESCE(MAX(block_number), 0) FROM blocks")
+            .fetch_one(&self.pool).await?;
+        Ok(height)
+    }
+
+    /// Records a contract call transaction, its events and its state changes (atomic)
+    pub async fn record_contract_call(&self, call: &ContractCallRecord<'_>) -> Result<(), Box<dyn Error>> {
+        let mut db_tx = self.pool.begin().await?;
+        Self::write_contract_call(&mut db_tx, call).await?;
+        db_tx.commit().await?;
+        Ok(())
+    }
+
+    async fn write_contract_call(conn: &mut SqliteConnection, call: &ContractCallRecord<'_>) -> Result<(), Box<dyn Error>> {
+        let ContractCallRecord {
+            tx_id, caller_wallet_id, contract_address, function, gas_used, block_number, events, state_diff,
+            gas_fee, fee_rewards, error,
+        } = *call;
+
+        let call_json = serde_json::json!({
+            "type": "CONTRACT_CALL",
+            "contract_address": contract_address,
+            "function": function,
+            "gas_used": gas_used,
+            "block_number": block_number,
+            "gas": gas_fee,
+            "error": error,
+        });
+
+        sqlx::query(
+            "INSERT INTO transactions (id, from_wallet_id, to_wallet_id, token_symbol, amount, fee, status, signature, data)
+             VALUES (?, ?, NULL, 'QVR', 0.0, ?, ?, '', ?)"
+        )
+        .bind(tx_id)
+        .bind(caller_wallet_id)
+        .bind(gas_fee.map_or(0.0, |g| g.fee))
+        .bind(if error.is_some() { "FAILED" } else { "COMPLETED" })
+        .bind(call_json.to_string())
+        .execute(&mut *conn).await?;
+
+        if let Some(gas_fee) = gas_fee {
+            let payer = caller_wallet_id.ok_or("Gas fee without a paying wallet")?;
+            Self::settle_gas_fee(conn, payer, gas_fee, fee_rewards).await?;
+        }
+
+        for (log_index, event) in events.iter().enumerate() {
+            let topic = |i: usize| event.topics.get(i).cloned();
+            sqlx::query(
+                "INSERT INTO contract_events (id, transaction_id, block_number, contract_address, log_index, topic0, topic1, topic2, topic3, data)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(tx_id)
+            .bind(block_number)
+            .bind(&event.contract) // Nested calls emit under their own address
+            .bind(log_index as i64)
+            .bind(topic(0))
+            .bind(topic(1))
+            .bind(topic(2))
+            .bind(topic(3))
+            .bind(&event.data)
+            .execute(&mut *conn).await?;
+        }
+
+        // Compare-and-set against the value the call saw, so a concurrent call's write is never lost
+        for write in &state_diff.storage_writes {
+            let updated = match &write.previous {
+                Some(previous) => sqlx::query(
+                    "UPDATE contract_storage SET storage_value = ?, updated_at = CURRENT_TIMESTAMP
+                     WHERE contract_address = ? AND storage_key = ? AND storage_value = ?"
+                )
+                .bind(&write.value)
+                .bind(&write.contract)
+                .bind(&write.key)
+                .bind(previous),
+                None => sqlx::query(
+                    "INSERT INTO contract_storage (contract_address, storage_key, storage_value) VALUES (?, ?, ?)
+                     ON CONFLICT(contract_address, storage_key) DO NOTHING"
+                )
+                .bind(&write.contract)
+                .bind(&write.key)
+                .bind(&write.value),
+            }
+            .execute(&mut *conn).await?
+            .rows_affected();
+            if updated == 0 {
+                return Err(Box::new(StorageConflict { contract: write.contract.clone(), key: write.key.clone() }));
+            }
+        }
+        // Slots the result was computed from; checked after the writes, which hold the write lock
+        for read in &state_diff.storage_reads {
+            let unchanged: bool = sqlx::query_scalar(
+                "SELECT (SELECT storage_value FROM contract_storage WHERE contract_address = ? AND storage_key = ?) IS ?"
+            )
+            .bind(&read.contract)
+            .bind(&read.key)
+            .bind(&read.value)
+            .fetch_one(&mut *conn).await?;
+            if !unchanged {
+                return Err(Box::new(StorageConflict { contract: read.contract.clone(), key: read.key.clone() }));
+            }
+        }
+
+        // Deltas, not absolute values: concurrent calls must not overwrite each other
+        for change in &state_diff.balance_deltas {
+            let is_contract: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM deployed_contracts WHERE address = ?)")
+                .bind(&change.account)
+                .fetch_one(&mut *conn).await?;
+
+            let updated = if is_contract {
+                sqlx::query(
+                    "INSERT INTO contract_balances (contract_address, token_symbol, amount) VALUES (?, ?, ?)
+                     ON CONFLICT(contract_address, token_symbol) DO UPDATE SET amount = amount + excluded.amount, updated_at = CURRENT_TIMESTAMP
+                     WHERE amount + excluded.amount >= 0"
+                )
+                .bind(&change.account)
+                .bind(&change.token)
+                .bind(change.delta)
+                .execute(&mut *conn).await?
+                .rows_affected()
+            } else {
+                sqlx::query(
+                    "INSERT INTO balances (wallet_id, token_symbol, amount)
+                     SELECT id, ?, ? FROM wallets WHERE address = ?
+                     ON CONFLICT(wallet_id, token_symbol) DO UPDATE SET amount = amount + excluded.amount, updated_at = CURRENT_TIMESTAMP
+                     WHERE excluded.amount >= 0 OR amount - held + excluded.amount >= 0"
+                )
+                .bind(&change.token)
+                .bind(change.delta)
+                .bind(&change.account)
+                .execute(&mut *conn).await?
+                .rows_affected()
+            };
+
+            // Missing wallet, or the balance dropped below the debit since the call read it
+            if updated == 0 {
+                return Err(format!("Balance of {} could not be updated", change.account).into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reserves the maximum gas fee from a wallet's QVR balance before a call
+    pub async fn reserve_gas_fee(&self, wallet_id: &str, amount: f64) -> Result<(), Box<dyn Error>> {
+        let reserved = sqlx::query(
+            "UPDATE balances SET amount = amount - ?, updated_at = CURRENT_TIMESTAMP
+             WHERE wallet_id = ? AND token_symbol = 'QVR' AND amount - held >= ?"
+        )
+        .bind(amount)
+        .bind(wallet_id)
+        .bind(amount)
+        .execute(&self.pool).await?
+        .rows_affected();
+        if reserved == 0 {
+            return Err(format!("Insufficient QVR balance for gas. Required: {}", amount).into());
+        }
+        Ok(())
+    }
+
+    /// Returns a whole reservation when its call could not be recorded
+    pub async fn release_gas_fee(&self, wallet_id: &str, amount: f64) -> Result<(), Box<dyn Error>> {
+        sqlx::query("UPDATE balances SET amount = amount + ?, updated_at = CURRENT_TIMESTAMP WHERE wallet_id = ? AND token_symbol = 'QVR'")
+            .bind(amount)
+            .bind(wallet_id)
+            .execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// Refunds unused gas and credits the fee to the treasury and stakers
+    async fn settle_gas_fee(conn: &mut SqliteConnection, payer: &str, gas_fee: &GasFee, fee_rewards: &[StateChange]) -> Result<(), Box<dyn Error>> {
+        if gas_fee.refund > 0.0 {
+            sqlx::query("UPDATE balances SET amount = amount + ?, updated_at = CURRENT_TIMESTAMP WHERE wallet_id = ? AND token_symbol = 'QVR'")
+                .bind(gas_fee.refund)
+                .bind(payer)
+                .execute(&mut *conn).await?;
+        }
+        Self::credit_treasury(conn, "QVR", gas_fee.treasury).await?;
+        Self::write_native_changes(conn, fee_rewards).await
+    }
+
+    async fn credit_treasury(conn: &mut SqliteConnection, token: &str, amount: f64) -> Result<(), Box<dyn Error>> {
+        if amount > 0.0 {
+            sqlx::query(
+                "INSERT INTO contract_balances (contract_address, token_symbol, amount) VALUES (?, ?, ?)
+                 ON CONFLICT(contract_address, token_symbol) DO UPDATE SET amount = amount + excluded.amount, updated_at = CURRENT_TIMESTAMP"
+            )
+            .bind(TREASURY_ADDRESS)
+            .bind(token)
+            .bind(amount)
+            .execute(&mut *conn).await?;
+        }
+        Ok(())
+    }
+
+    /// Open orders of every pair (waiting stops included), oldest first; rebuilds the order books at startup
+    pub async fn get_open_orders(&self) -> Result<Vec<Order>, Box<dyn Error>> {
+        let orders = sqlx::query_as(
+            "SELECT * FROM orders WHERE status IN ('PENDING', 'PARTIALLY_FILLED') ORDER BY created_at, rowid"
+        )
+        .fetch_all(&self.pool).await?;
+        Ok(orders)
+    }
+
+    /// Price of the latest trade of every pair
+    pub async fn get_last_trade_prices(&self) -> Result<Vec<(String, f64)>, Box<dyn Error>> {
+        let prices = sqlx::query_as(
+            "SELECT pair, price FROM trades t WHERE rowid = (SELECT MAX(rowid) FROM trades WHERE pair = t.pair)"
+        )
+        .fetch_all(&self.pool).await?;
+        Ok(prices)
+    }
+
+    /// Persists a matched (or amended) order atomically: the order, its trades, maker fills and settlement.
+    /// Per trade the buyer pays quote for base, the seller the reverse; the taker's fee
+    /// (in the quote token) goes to the treasury. The holds of every order involved are
+    /// released first and re-placed for what stays unfilled, so fills consume them.
+    pub async fn record_order_match(&self, matched: &OrderMatch) -> Result<(), Box<dyn Error>> {
+        let order = &matched.order;
+        let (base, quote) = order.pair.split_once('/').ok_or("Invalid pair")?;
+        let hold_token = |o: &Order| if o.side == "BUY" { quote } else { base };
+        let mut db_tx = self.pool.begin().await?;
+
+        for o in std::iter::once(order).chain(&matched.makers) {
+            let prior: Option<f64> = sqlx::query_scalar("SELECT held FROM orders WHERE id = ?")
+                .bind(&o.id)
+                .fetch_optional(&mut *db_tx).await?;
+            Self::release_hold(&mut db_tx, &o.wallet_id, hold_token(o), prior.unwrap_or(0.0)).await?;
+        }
+
+        // Amended orders already have a row
+        sqlx::query(
+            "INSERT INTO orders (id, wallet_id, pair, side, order_type, price, amount, filled, status, held, stop_price, trigger_state,
+                time_in_force, expires_at, post_only, stp_mode)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+             ON CONFLICT(id) DO UPDATE SET price = excluded.price, amount = excluded.amount, filled = excluded.filled,
+                status = excluded.status, held = excluded.held, trigger_state = excluded.trigger_state,
+                updated_at = CURRENT_TIMESTAMP"
+        )
+        .bind(&order.id)
+        .bind(&order.wallet_id)
+        .bind(&order.pair)
+        .bind(&order.side)
+        .bind(&order.order_type)
+        .bind(order.price)
+        .bind(order.amount)
+        .bind(order.filled)
+        .bind(&order.status)
+        .bind(order.held)
+        .bind(order.stop_price)
+        .bind(&order.trigger_state)
+        .bind(&order.time_in_force)
+        .bind(order.expires_at)
+        .bind(order.post_only)
+        .bind(&order.stp_mode)
+        .execute(&mut *db_tx).await?;
+
+        for trade in &matched.trades {
+            sqlx::query(
+                "INSERT INTO trades (id, order_id, maker_order_id, pair, price, amount, side, maker_wallet_id, taker_wallet_id, fee)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+            )
+            .bind(&trade.id)
+            .bind(&trade.order_id)
+            .bind(&trade.maker_order_id)
+            .bind(&trade.pair)
+            .bind(trade.price)
+            .bind(trade.amount)
+            .bind(&trade.side)
+            .bind(&trade.maker_wallet_id)
+            .bind(&trade.taker_wallet_id)
+            .bind(trade.fee)
+            .execute(&mut *db_tx).await?;
+
+            let notional = trade.amount * trade.price;
+            let (buyer, seller, buyer_pays, seller_gets) = if trade.side == "BUY" {
+                (&trade.taker_wallet_id, &trade.maker_wallet_id, notional + trade.fee, notional)
+            } else {
+                (&trade.maker_wallet_id, &trade.taker_wallet_id, notional, notional - trade.fee)
+            };
+            Self::debit_balance(&mut db_tx, buyer, quote, buyer_pays).await?;
+            Self::credit_balance(&mut db_tx, buyer, base, trade.amount).await?;
+            Self::debit_balance(&mut db_tx, seller, base, trade.amount).await?;
+            Self::credit_balance(&mut db_tx, seller, quote, seller_gets).await?;
+            Self::credit_treasury(&mut db_tx, quote, trade.fee).await?;
+        }
+
+        // Makers traded with, or cancelled / decremented by self-trade prevention
+        for maker in &matched.makers {
+            sqlx::query("UPDATE orders SET amount = ?, filled = ?, status = ?, held = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?")
+                .bind(maker.amount)
+                .bind(maker.filled)
+                .bind(&maker.status)
+                .bind(maker.held)
+                .bind(&maker.id)
+                .execute(&mut *db_tx).await?;
+        }
+
+        for o in std::iter::once(order).chain(&matched.makers) {
+            Self::place_hold(&mut db_tx, &o.wallet_id, hold_token(o), o.held).await?;
+        }
+
+        for p in &matched.prevented {
+            sqlx::query(
+                "INSERT INTO self_trade_preventions (id, wallet_id, pair, taker_order_id, maker_order_id, mode, amount, price)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+            )
+            .bind(&p.id)
+            .bind(&p.wallet_id)
+            .bind(&p.pair)
+            .bind(&p.taker_order_id)
+            .bind(&p.maker_order_id)
+            .bind(&p.mode)
+            .bind(p.amount)
+            .bind(p.price)
+            .execute(&mut *db_tx).await?;
+        }
+
+        db_tx.commit().await?;
+        Ok(())
+    }
+
+    /// Marks open orders cancelled and releases their holds in one transaction;
+    /// returns how many were still open
+    pub async fn cancel_orders(&self, order_ids: &[String]) -> Result<u64, Box<dyn Error>> {
+        self.close_orders(order_ids, OrderStatus::Cancelled).await
+    }
+
+    /// Marks open GTD orders expired and releases their holds in one transaction
+    pub async fn expire_orders(&self, order_ids: &[String]) -> Result<u64, Box<dyn Error>> {
+        self.close_orders(order_ids, OrderStatus::Expired).await
+    }
+
+    async fn close_orders(&self, order_ids: &[String], status: OrderStatus) -> Result<u64, Box<dyn Error>> {
+        let mut db_tx = self.pool.begin().await?;
+        let mut closed = 0;
+        for order_id in order_ids {
+            let open: Option<(String, String, String, f64)> = sqlx::query_as(
+                "SELECT wallet_id, pair, side, held FROM orders
+                 WHERE id = ? AND status IN ('PENDING', 'PARTIALLY_FILLED')"
+            )
+            .bind(order_id)
+            .fetch_optional(&mut *db_tx).await?;
+            let Some((wallet_id, pair, side, held)) = open else { continue };
+
+            let (base, quote) = pair.split_once('/').ok_or("Invalid pair")?;
+            let token = if side == "BUY" { quote } else { base };
+            Self::release_hold(&mut db_tx, &wallet_id, token, held).await?;
+            sqlx::query("UPDATE orders SET status = ?, held = 0.0, updated_at = CURRENT_TIMESTAMP WHERE id = ?")
+                .bind(status.as_str())
+                .bind(order_id)
+                .execute(&mut *db_tx).await?;
+            closed += 1;
+        }
+        db_tx.commit().await?;
+        Ok(closed)
+    }
+
+    /// Orders of a wallet, newest first: open ones, or the closed history
+    pub async fn get_wallet_orders(
+        &self,
+        wallet_id: Uuid,
+        open: bool,
+        pair: Option<&str>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<Order>, Box<dyn Error>> {
+        let mut sql = String::from("SELECT * FROM orders WHERE wallet_id = ?");
+        sql.push_str(if open {
+            " AND status IN ('PENDING', 'PARTIALLY_FILLED')"
+        } else {
+            " AND status NOT IN ('PENDING', 'PARTIALLY_FILLED')"
+        });
+        if pair.is_some() {
+            sql.push_str(" AND pair = ?");
+        }
+        sql.push_str(" ORDER BY updated_at DESC, rowid DESC LIMIT ? OFFSET ?");
+
+        let mut query = sqlx::query_as::<_, Order>(&sql).bind(wallet_id.to_string());
+        if let Some(pair) = pair {
+            query = query.bind(pair);
+        }
+        let orders = query.bind(limit).bind(offset).fetch_all(&self.pool).await?;
+        Ok(orders)
+    }
+
+    /// Self-trade prevention mode of a wallet; wallets without a setting use cancel newest
+    pub async fn get_self_trade_mode(&self, wallet_id: Uuid) -> Result<SelfTradeMode, Box<dyn Error>> {
+        let mode: Option<String> = sqlx::query_scalar("SELECT stp_mode FROM trading_settings WHERE wallet_id = ?")
+            .bind(wallet_id.to_string())
+            .fetch_optional(&self.pool).await?;
+        Ok(mode.and_then(|m| SelfTradeMode::parse(&m)).unwrap_or_default())
+    }
+
+    /// Applies to orders placed afterwards; open orders keep the mode they were placed with
+    pub async fn set_self_trade_mode(&self, wallet_id: Uuid, mode: SelfTradeMode) -> Result<(), Box<dyn Error>> {
+        sqlx::query(
+            "INSERT INTO trading_settings (wallet_id, stp_mode) VALUES (?, ?)
+             ON CONFLICT(wallet_id) DO UPDATE SET stp_mode = excluded.stp_mode, updated_at = CURRENT_TIMESTAMP"
+        )
+        .bind(wallet_id.to_string())
+        .bind(mode.as_str())
+        .execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// Matches of a wallet stopped by self-trade prevention, newest first
+    pub async fn get_self_trade_preventions(&self, wallet_id: Uuid, limit: i64, offset: i64) -> Result<Vec<SelfTradePrevention>, Box<dyn Error>> {
+        let prevented = sqlx::query_as(
+            "SELECT id, wallet_id, pair, taker_order_id, maker_order_id, mode, amount, price FROM self_trade_preventions
+             WHERE wallet_id = ? ORDER BY created_at DESC, rowid DESC LIMIT ? OFFSET ?"
+        )
+        .bind(wallet_id.to_string())
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool).await?;
+        Ok(prevented)
+    }
+
+    /// Reserves `amount` of the available balance for an order
+    /// Holds part of the available balance; a shortfall up to DUST (float rounding) holds what is available
+    async fn place_hold(conn: &mut SqliteConnection, wallet_id: &str, token: &str, amount: f64) -> Result<(), Box<dyn Error>> {
+        if amount <= 0.0 {
+            return Ok(());
+        }
+        let placed = sqlx::query(
+            "UPDATE balances SET held = held + MIN(?, amount - held), updated_at = CURRENT_TIMESTAMP
+             WHERE wallet_id = ? AND token_symbol = ? AND amount - held + ? >= ?"
+        )
+        .bind(amount)
+        .bind(wallet_id)
+        .bind(token)
+        .bind(DUST)
+        .bind(amount)
+        .execute(&mut *conn).await?
+        .rows_affected();
+        if placed == 0 {
+            return Err(format!("Insufficient available {} balance in wallet {}. Required: {}", token, wallet_id, amount).into());
+        }
+        Ok(())
+    }
+
+    async fn release_hold(conn: &mut SqliteConnection, wallet_id: &str, token: &str, amount: f64) -> Result<(), Box<dyn Error>> {
+        if amount > 0.0 {
+            sqlx::query(
+                "UPDATE balances SET held = MAX(held - ?, 0.0), updated_at = CURRENT_TIMESTAMP
+                 WHERE wallet_id = ? AND token_symbol = ?"
+            )
+            .bind(amount)
+            .bind(wallet_id)
+            .bind(token)
+            .execute(&mut *conn).await?;
+        }
+        Ok(())
+    }
+
+    /// Debits the available (unheld) balance, with the same DUST tolerance as `place_hold`
+    async fn debit_balance(conn: &mut SqliteConnection, wallet_id: &str, token: &str, amount: f64) -> Result<(), Box<dyn Error>> {
+        let debited = sqlx::query(
+            "UPDATE balances SET amount = amount - MIN(?, amount - held), updated_at = CURRENT_TIMESTAMP
+             WHERE wallet_id = ? AND token_symbol = ? AND amount - held + ? >= ?"
+        )
+        .bind(amount)
+        .bind(wallet_id)
+        .bind(token)
+        .bind(DUST)
+        .bind(amount)
+        .execute(&mut *conn).await?
+        .rows_affected();
+        if debited == 0 {
+            return Err(format!("Insufficient available {} balance in wallet {}. Required: {}", token, wallet_id, amount).into());
+        }
+        Ok(())
+    }
+
+    async fn credit_balance(conn: &mut SqliteConnection, wallet_id: &str, token: &str, amount: f64) -> Result<(), Box<dyn Error>> {
+        sqlx::query(
+            "INSERT INTO balances (wallet_id, token_symbol, amount) VALUES (?, ?, ?)
+             ON CONFLICT(wallet_id, token_symbol) DO UPDATE SET amount = amount + excluded.amount, updated_at = CURRENT_TIMESTAMP"
+        )
+        .bind(wallet_id)
+        .bind(token)
+        .bind(amount)
+        .execute(&mut *conn).await?;
+        Ok(())
+    }
+
+    /// QVR collected by the treasury from gas fees
+    pub async fn get_treasury_balance(&self) -> Result<f64, Box<dyn Error>> {
+        let amount: Option<f64> = sqlx::query_scalar(
+            "SELECT amount FROM contract_balances WHERE contract_address = ? AND token_symbol = 'QVR'"
+        )
+        .bind(TREASURY_ADDRESS)
+        .fetch_optional(&self.pool).await?;
+        Ok(amount.unwrap_or(0.0))
+    }
+
+    /// Saves a new deployment together with its version 1 history entry
+    pub async fn insert_deployed_contract(&self, deployed: &crate::models::DeployedContract) -> Result<(), Box<dyn Error>> {
+        let mut db_tx = self.pool.begin().await?;
+
+        sqlx::query(
+            "INSERT INTO deployed_contracts (id, contract_id, compiled_contract_id, deployer_wallet_id, address, upgradeable, upgrade_authority_type, upgrade_authority_id, current_version, reentrancy_guard)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, 1, ?)"
+        )
+        .bind(&deployed.id)
+        .bind(&deployed.contract_id)
+        .bind(&deployed.compiled_contract_id)
+        .bind(&deployed.deployer_wallet_id)
+        .bind(&deployed.address)
+        .bind(deployed.upgradeable)
+        .bind(&deployed.upgrade_authority_type)
+        .bind(&deployed.upgrade_authority_id)
+        .bind(deployed.reentrancy_guard)
+        .execute(&mut *db_tx).await?;
+
+        sqlx::query(
+            "INSERT INTO contract_versions (contract_address, version, compiled_contract_id, approved_by) VALUES (?, 1, ?, 'DEPLOY')"
+        )
+        .bind(&deployed.address)
+        .bind(&deployed.compiled_contract_id)
+        .execute(&mut *db_tx).await?;
+
+        db_tx.commit().await?;
+        Ok(())
+    }
+
+    /// Points an upgradeable address at new code (atomic)
+    ///
+    /// The approval is consumed here (multisig tx / proposal marked EXECUTED),
+    /// so it cannot authorize a second upgrade. Storage is keyed by address
+    /// and carries over unchanged; `migration` is the optional migrate call
+    /// already executed against the new code.
+    pub async fn upgrade_contract(
+        &self,
+        deployed: &crate::models::DeployedContract,
+        new_compiled_contract_id: &str,
+        approval: &crate::models::UpgradeApproval,
+        migration: Option<&ContractCallRecord<'_>>,
+    ) -> Result<crate::models::ContractVersion, Box<dyn Error>> {
+        use crate::models::UpgradeApproval;
+
+        let mut db_tx = self.pool.begin().await?;
+
+        let approved_by = match approval {
+            UpgradeApproval::Wallet(wallet_id) => format!("WALLET:{}", wallet_id),
+            UpgradeApproval::Multisig(multisig_tx_id) => {
+                let consumed = sqlx::query(
+                    "UPDATE multisig_transactions SET status = 'EXECUTED'
+                     WHERE id = ? AND multisig_id = ? AND status = 'APPROVED'
+                       AND upgrade_contract_address = ? AND upgrade_compiled_contract_id = ?"
+                )
+                .bind(multisig_tx_id)
+                .bind(&deployed.upgrade_authority_id)
+                .bind(&deployed.address)
+                .bind(new_compiled_contract_id)
+                .execute(&mut *db_tx).await?
+                .rows_affected();
+                if consumed == 0 {
+                    return Err("Multisig transaction is not an approved, unused approval of this upgrade by the upgrade authority".into());
+                }
+                format!("MULTISIG:{}", multisig_tx_id)
+            }
+            UpgradeApproval::Governance(proposal_id) => {
+                let consumed = sqlx::query(
+                    "UPDATE proposals SET status = 'EXECUTED', executed_at = CURRENT_TIMESTAMP
+                     WHERE proposal_id = ? AND status = 'PASSED'
+                       AND upgrade_contract_address = ? AND upgrade_compiled_contract_id = ?"
+                )
+                .bind(proposal_id)
+                .bind(&deployed.address)
+                .bind(new_compiled_contract_id)
+                .execute(&mut *db_tx).await?
+                .rows_affected();
+                if consumed == 0 {
+                    return Err("Proposal is not a passed, unexecuted proposal for this upgrade".into());
+                }
+                format!("GOVERNANCE:{}", proposal_id)
+            }
+        };
+
+        // Optimistic check: a concurrent upgrade bumps the version first
+        let version = deployed.current_version + 1;
+        let updated = sqlx::query(
+            "UPDATE deployed_contracts SET compiled_contract_id = ?, current_version = ?
+             WHERE id = ? AND current_version = ?"
+        )
+        .bind(new_compiled_contract_id)
+        .bind(version)
+        .bind(&deployed.id)
+        .bind(deployed.current_version)
+        .execute(&mut *db_tx).await?
+        .rows_affected();
+        if updated == 0 {
+            return Err("Contract was upgraded concurrently, retry".into());
+        }
+
+        if let Some(call) = migration {
+            Self::write_contract_call(&mut db_tx, call).await?;
+        }
+
+        sqlx::query(
+            "INSERT INTO contract_versions (contract_address, version, compiled_contract_id, approved_by, migration_tx_id)
+             VALUES (?, ?, ?, ?, ?)"
+        )
+        .bind(&deployed.address)
+        .bind(version)
+        .bind(new_compiled_contract_id)
+        .bind(&approved_by)
+        .bind(migration.map(|m| m.tx_id))
+        .execute(&mut *db_tx).await?;
+
+        let row: crate::models::ContractVersion = sqlx::query_as(
+            "SELECT * FROM contract_versions WHERE contract_address = ? AND version = ?"
+        )
+        .bind(&deployed.address)
+        .bind(version)
+        .fetch_one(&mut *db_tx).await?;
+
+        db_tx.commit().await?;
+        Ok(row)
+    }
+
+    /// Version history of a contract address, oldest first
+    pub async fn get_contract_versions(&self, contract_address: &str) -> Result<Vec<crate::models::ContractVersion>, Box<dyn Error>> {
+        let versions: Vec<crate::models::ContractVersion> = sqlx::query_as(
+            "SELECT * FROM contract_versions WHERE contract_address = ? ORDER BY version ASC"
+        )
+        .bind(contract_address)
+        .fetch_all(&self.pool).await?;
+
+        Ok(versions)
+    }
+
+    /// Committed value of one contract storage slot (hex)
+    pub async fn get_contract_storage(&self, contract_address: &str, key_hex: &str) -> Result<Option<String>, Box<dyn Error>> {
+        let value: Option<String> = sqlx::query_scalar(
+            "SELECT storage_value FROM contract_storage WHERE contract_address = ? AND storage_key = ?"
+        )
+        .bind(contract_address)
+        .bind(key_hex)
+        .fetch_optional(&self.pool).await?;
+
+        Ok(value)
+    }
+
+    /// Balance of a contract or wallet address; None if the address is unknown
+    pub async fn get_account_balance(&self, account: &str, token: &str) -> Result<Option<f64>, Box<dyn Error>> {
+        let is_contract: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM deployed_contracts WHERE address = ?)")
+            .bind(account)
+            .fetch_one(&self.pool).await?;
+
+        if is_contract {
+            let amount: Option<f64> = sqlx::query_scalar(
+                "SELECT amount FROM contract_balances WHERE contract_address = ? AND token_symbol = ?"
+            )
+            .bind(account)
+            .bind(token)
+            .fetch_optional(&self.pool).await?;
+            return Ok(Some(amount.unwrap_or(0.0)));
+        }
+
+        let wallet: Option<(String, Option<f64>)> = sqlx::query_as(
+            "SELECT w.id, b.amount - b.held FROM wallets w
+             LEFT JOIN balances b ON b.wallet_id = w.id AND b.token_symbol = ?
+             WHERE w.address = ?"
+        )
+        .bind(token)
+        .bind(account)
+        .fetch_optional(&self.pool).await?;
+
+        Ok(wallet.map(|(_, amount)| amount.unwrap_or(0.0)))
+    }
+
+    /// Event query: `topics[i]` filters on topic position i
+    pub async fn get_contract_events(
+        &self,
+        contract_address: &str,
+        topics: &[Option<String>; 4],
+        from_block: Option<i64>,
+        to_block: Option<i64>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<crate::models::ContractEventLog>, Box<dyn Error>> {
+        let mut sql = String::from("SELECT * FROM contract_events WHERE contract_address = ?");
+        for (i, topic) in topics.iter().enumerate() {
+            if topic.is_some() {
+                sql.push_str(&format!(" AND topic{} = ?", i));
+            }
+        }
+        if from_block.is_some() {
+            sql.push_str(" AND block_number >= ?");
+        }
+        if to_block.is_some() {
+            sql.push_str(" AND block_number <= ?");
+        }
+        sql.push_str(" ORDER BY block_number ASC, created_at ASC, log_index ASC LIMIT ? OFFSET ?");
+
+        let mut query = sqlx::query_as::<_, crate::models::ContractEventLog>(&sql).bind(contract_address);
+        for topic in topics.iter().flatten() {
+            query = query.bind(topic);
+        }
+        if let Some(from) = from_block {
+            query = query.bind(from);
+        }
+        if let Some(to) = to_block {
+            query = query.bind(to);
+        }
+
+        let events = query.bind(limit).bind(offset).fetch_all(&self.pool).await?;
+        Ok(events)
+    }
+
     // --- Governance Helpers ---
     
     pub async fn get_proposal(&self, proposal_id: &str) -> Result<Option<crate::models::Proposal>, Box<dyn Error>> {
@@ -766,6 +2136,23 @@ impl Database {
         Ok(proposal)
     }
 
+    /// Closes voting on a proposal whose period ended: PASSED if more voting power is for it, else REJECTED
+    pub async fn finalize_proposal(&self, proposal_id: &str) -> Result<Option<crate::models::Proposal>, Box<dyn Error>> {
+        let updated = sqlx::query(
+            "UPDATE proposals
+             SET status = CASE WHEN voting_power_for > voting_power_against THEN 'PASSED' ELSE 'REJECTED' END
+             WHERE proposal_id = ? AND status IN ('PENDING', 'ACTIVE')
+               AND voting_ends_at IS NOT NULL AND voting_ends_at <= datetime('now')"
+        )
+        .bind(proposal_id)
+        .execute(&self.pool).await?
+        .rows_affected();
+        if updated == 0 {
+            return Ok(None);
+        }
+        self.get_proposal(proposal_id).await
+    }
+
     // --- Oracle Helpers ---
     
     pub async fn get_aggregated_price(&self, token_symbol: &str) -> Result<Option<f64>, Box<dyn Error>> {
@@ -790,3 +2177,143 @@ impl Database {
         Ok(block)
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Fresh in-memory database; one connection, since each one would see its own memory
+    pub(crate) async fn memory_db() -> Database {
+        let db = Database::connect_with_url("sqlite::memory:", 1).await.unwrap();
+        db.init_schema().await.unwrap();
+        db
+    }
+
+    /// Saved wallet holding `balances`, with its spend secret
+    pub(crate) async fn funded_wallet(db: &Database, balances: &[(&str, f64)]) -> (Wallet, String) {
+        let user = db.create_user(&format!("user{}", &Uuid::new_v4().simple().to_string()[..8])).await.unwrap();
+        let (wallet, spend_sk, _) = Wallet::new(user.id);
+        db.save_wallet(&wallet).await.unwrap();
+        for (token, amount) in balances {
+            db.set_balance(wallet.id, token, *amount).await.unwrap();
+        }
+        (wallet, spend_sk)
+    }
+
+    fn call_record<'a>(tx_id: &'a str, events: &'a [crate::host::EmittedEvent], state_diff: &'a crate::state::StateDiff) -> ContractCallRecord<'a> {
+        ContractCallRecord {
+            tx_id,
+            caller_wallet_id: None,
+            contract_address: "0xa",
+            function: "run",
+            gas_used: 1,
+            block_number: 1,
+            events,
+            state_diff,
+            gas_fee: None,
+            fee_rewards: &[],
+            error: None,
+        }
+    }
+
+    fn event(contract: &str, topics: &[&str], data: &str) -> crate::host::EmittedEvent {
+        crate::host::EmittedEvent {
+            contract: contract.to_string(),
+            topics: topics.iter().map(|t| t.repeat(64)).collect(),
+            data: data.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_contract_events_persist_and_filter() {
+        let db = memory_db().await;
+        let diff = crate::state::StateDiff::default();
+
+        // Three calls on blocks 1..3; block 2's call also emits from a nested contract
+        let blocks = [
+            (1, vec![event("0xa", &["1"], "01"), event("0xa", &["2", "3"], "02")]),
+            (2, vec![event("0xa", &["1", "3"], "03"), event("0xb", &["1"], "04")]),
+            (3, vec![event("0xa", &["1"], "05")]),
+        ];
+        for (block_number, events) in &blocks {
+            let tx_id = Uuid::new_v4().to_string();
+            db.record_contract_call(&ContractCallRecord {
+                tx_id: &tx_id,
+                caller_wallet_id: None,
+                contract_address: "0xa",
+                function: "run",
+                gas_used: 1,
+                block_number: *block_number,
+                events,
+                state_diff: &diff,
+                gas_fee: None,
+                fee_rewards: &[],
+                error: None,
+            }).await.unwrap();
+        }
+
+        let data = |events: Vec<crate::models::ContractEventLog>| events.into_iter().map(|e| e.data).collect::<Vec<_>>();
+        let topic = |t: &str| Some(t.repeat(64));
+
+        // Indexed by the emitting contract, in block and log order
+        let all = db.get_contract_events("0xa", &[None, None, None, None], None, None, 100, 0).await.unwrap();
+        assert_eq!(all.iter().map(|e| (e.block_number, e.log_index)).collect::<Vec<_>>(), vec![(1, 0), (1, 1), (2, 0), (3, 0)]);
+        assert_eq!(all[1].topics, vec!["2".repeat(64), "3".repeat(64)]);
+        assert_eq!(data(db.get_contract_events("0xb", &[None, None, None, None], None, None, 100, 0).await.unwrap()), vec!["04"]);
+
+        // Topics match by position
+        let by_topic0 = db.get_contract_events("0xa", &[topic("1"), None, None, None], None, None, 100, 0).await.unwrap();
+        assert_eq!(data(by_topic0), vec!["01", "03", "05"]);
+        let by_topic1 = db.get_contract_events("0xa", &[None, topic("3"), None, None], None, None, 100, 0).await.unwrap();
+        assert_eq!(data(by_topic1), vec!["02", "03"]);
+
+        // Block range is inclusive, then paged
+        let range = db.get_contract_events("0xa", &[topic("1"), None, None, None], Some(2), Some(3), 100, 0).await.unwrap();
+        assert_eq!(data(range), vec!["03", "05"]);
+        let page = db.get_contract_events("0xa", &[None, None, None, None], Some(1), None, 2, 1).await.unwrap();
+        assert_eq!(data(page), vec!["02", "03"]);
+    }
+
+    #[tokio::test]
+    async fn test_stale_read_is_a_storage_conflict() {
+        use crate::state::{DbState, StateDiff, StateOverlay, StateReader};
+        let db = memory_db().await;
+        let state: std::sync::Arc<dyn StateReader> = std::sync::Arc::new(DbState::new(db.clone()));
+
+        // Overlay reads go through `block_on`, so calls run off the runtime like in the VM
+        let run = |call: fn(&mut StateOverlay)| {
+            let state = state.clone();
+            async move {
+                tokio::task::spawn_blocking(move || {
+                    let mut overlay = StateOverlay::new(state);
+                    call(&mut overlay);
+                    overlay.diff()
+                }).await.unwrap()
+            }
+        };
+        let record = |diff: StateDiff| {
+            let db = db.clone();
+            async move { db.record_contract_call(&call_record(&Uuid::new_v4().to_string(), &[], &diff)).await }
+        };
+        let set_a1: fn(&mut StateOverlay) = |s| s.storage_set("0xa", b"a", vec![1]).unwrap();
+        let set_a2: fn(&mut StateOverlay) = |s| s.storage_set("0xa", b"a", vec![2]).unwrap();
+        let copy_a_to_b: fn(&mut StateOverlay) = |s| {
+            let a = s.storage_get("0xa", b"a").unwrap().unwrap();
+            s.storage_set("0xa", b"b", a).unwrap();
+        };
+        let slot_b = || db.get_contract_storage("0xa", "62");
+
+        record(run(set_a1).await).await.unwrap();
+        let stale = run(copy_a_to_b).await;
+        record(run(set_a2).await).await.unwrap();
+
+        // "b" was computed from the old "a": nothing of the call is kept
+        let err = record(stale).await.unwrap_err();
+        assert!(err.downcast_ref::<StorageConflict>().is_some());
+        assert_eq!(slot_b().await.unwrap(), None);
+
+        // Re-run on the current state
+        record(run(copy_a_to_b).await).await.unwrap();
+        assert_eq!(slot_b().await.unwrap().as_deref(), Some("02"));
+    }
+}
@@ -16,9 +16,42 @@
  *   MIT License
  * ============================================== */
 
-use crate::models::{CompiledContract, DeployedContract};
+use crate::abi::ContractAbi;
+use crate::analysis::{CallGraphNode, Counterexample, ModuleAnalysis, SUPPORTED_PROPERTIES};
+use crate::models::CompiledContract;
+pub use crate::sdk::{SDKGenerator, SdkLanguage};
 use std::error::Error;
+use std::fmt;
 use uuid::Uuid;
+use wast::parser::{self, ParseBuffer};
+
+/// Compiler version recorded for contracts assembled from WAT
+pub const WAT_COMPILER_VERSION: &str = "wast 64";
+
+/// Source language accepted by the compile endpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceLanguage {
+    #[default]
+    Rust,
+    Wat,
+}
+
+/// WAT syntax error with a 1-based source position
+#[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
+pub struct SyntaxError {
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Syntax error at {}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl Error for SyntaxError {}
 
 /// Contract Compilation Service
 pub struct ContractCompiler;
@@ -47,9 +80,59 @@ impl ContractCompiler {
             compiler_version: Some("rustc 1.75.0".to_string()),
             compiled_by: None,
             gas_estimate: Some(gas_estimate),
+            abi_json: None,
         })
     }
 
+    /// Assemble WebAssembly text format into a binary module
+    pub fn assemble_wat(source: &str) -> Result<Vec<u8>, SyntaxError> {
+        let syntax_error = |e: wast::Error| {
+            let (line, column) = e.span().linecol_in(source);
+            SyntaxError { message: e.message(), line: line + 1, column: column + 1 }
+        };
+
+        let buf = ParseBuffer::new(source).map_err(syntax_error)?;
+        let mut wat = parser::parse::<wast::Wat>(&buf).map_err(syntax_error)?;
+        wat.encode().map_err(syntax_error)
+    }
+
+    /// Compile a WAT contract: assemble, then register like a pre-built binary
+    pub fn compile_wat_contract(
+        source_code: &str,
+        contract_name: &str,
+    ) -> Result<CompiledContract, Box<dyn Error>> {
+        let wasm_bytes = Self::assemble_wat(source_code)?;
+        let mut compiled = Self::from_wasm_binary(&wasm_bytes, contract_name)?;
+        compiled.source_code = Some(source_code.to_string());
+        compiled.compiler_version = Some(WAT_COMPILER_VERSION.to_string());
+        Ok(compiled)
+    }
+
+    /// Register a pre-built WASM binary (e.g. from `cargo build --target wasm32-unknown-unknown`)
+    pub fn from_wasm_binary(
+        wasm_bytes: &[u8],
+        contract_name: &str,
+    ) -> Result<CompiledContract, Box<dyn Error>> {
+        wasmer::wasmparser::validate(wasm_bytes).map_err(|e| format!("Invalid WASM module: {}", e))?;
+        let abi = Self::extract_abi(wasm_bytes)?;
+
+        Ok(CompiledContract {
+            id: Uuid::new_v4().to_string(),
+            contract_name: contract_name.to_string(),
+            wasm_hex: hex::encode(wasm_bytes),
+            source_code: None,
+            compiler_version: None,
+            compiled_by: None,
+            gas_estimate: Some(wasm_bytes.len() as i64 * 10),
+            abi_json: abi.map(|a| serde_json::to_string(&a)).transpose()?,
+        })
+    }
+
+    /// Read the `qverse.abi` custom section, if the module has one
+    pub fn extract_abi(wasm_bytes: &[u8]) -> Result<Option<ContractAbi>, Box<dyn Error>> {
+        ContractAbi::from_wasm(wasm_bytes).map_err(|e| e.to_string().into())
+    }
+
     /// Validate contract source code
     pub fn validate_source(source: &str) -> Result<(), Box<dyn Error>> {
         if source.is_empty() {
@@ -65,31 +148,45 @@ impl ContractCompiler {
     }
 }
 
-/// Formal Verification Engine (Simplified)
+/// Formal Verification Engine: static analysis over the compiled wasm
 pub struct FormalVerifier;
 
 impl FormalVerifier {
-    /// Verify contract properties (simplified - in production, use TLA+ or Coq)
+    /// Check `properties` (all supported ones when empty) against the contract's bytecode
     pub fn verify_contract(
         contract: &CompiledContract,
         properties: Vec<String>,
     ) -> Result<VerificationResult, Box<dyn Error>> {
-        // Mock verification
+        let properties = if properties.is_empty() {
+            SUPPORTED_PROPERTIES.iter().map(|p| p.to_string()).collect()
+        } else {
+            properties
+        };
+        if let Some(unknown) = properties.iter().find(|p| !SUPPORTED_PROPERTIES.contains(&p.as_str())) {
+            return Err(format!("Unknown property '{}' (supported: {})", unknown, SUPPORTED_PROPERTIES.join(", ")).into());
+        }
+
+        let wasm_bytes = hex::decode(&contract.wasm_hex)?;
+        let analysis = ModuleAnalysis::analyze(&wasm_bytes).map_err(|e| format!("Invalid WASM module: {}", e))?;
+        let abi = contract.abi_json.as_deref()
+            .map(|json| ContractAbi::from_json(json.as_bytes()))
+            .transpose()
+            .map_err(|e| e.to_string())?;
+
         let mut results = Vec::new();
-        
         for prop in properties {
-            // In production, this would use formal verification tools
-            results.push(PropertyResult {
-                property: prop.clone(),
-                verified: true, // Mock
-                proof: Some(format!("Proof for {}", prop)),
+            let outcome = analysis.check(&prop, abi.as_ref()).ok_or("Unknown property")?;
+            results.push(match outcome {
+                Ok(proof) => PropertyResult { property: prop, verified: true, proof: Some(proof), counterexample: None },
+                Err(counterexample) => PropertyResult { property: prop, verified: false, proof: None, counterexample: Some(counterexample) },
             });
         }
-        
+
         Ok(VerificationResult {
             contract_id: contract.id.clone(),
             all_verified: results.iter().all(|r| r.verified),
             properties: results,
+            call_graph: analysis.call_graph(),
         })
     }
 }
@@ -99,6 +196,7 @@ pub struct PropertyResult {
     pub property: String,
     pub verified: bool,
     pub proof: Option<String>,
+    pub counterexample: Option<Counterexample>,
 }
 
 #[derive(Debug, Clone)]
@@ -107,73 +205,7 @@ pub struct VerificationResult {
     pub contract_id: String,
     pub all_verified: bool,
     pub properties: Vec<PropertyResult>,
-}
-
-/// SDK Generator
-pub struct SDKGenerator;
-
-impl SDKGenerator {
-    /// Generate JavaScript SDK code
-    pub fn generate_js_sdk(contract: &DeployedContract) -> String {
-        format!(
-            r#"
-// Q-Verse SDK - Auto-generated
-const QVerseSDK = {{
-    contractAddress: "{}",
-    contractId: "{}",
-    
-    async call(functionName, args) {{
-        const response = await fetch('/api/contracts/execute', {{
-            method: 'POST',
-            headers: {{ 'Content-Type': 'application/json' }},
-            body: JSON.stringify({{
-                contract_id: "{}",
-                function: functionName,
-                args: args
-            }})
-        }});
-        return await response.json();
-    }}
-}};
-
-export default QVerseSDK;
-"#,
-            contract.address, contract.contract_id, contract.contract_id
-        )
-    }
-
-    /// Generate Rust SDK code
-    pub fn generate_rust_sdk(contract: &DeployedContract) -> String {
-        format!(
-            r#"
-// Q-Verse Rust SDK - Auto-generated
-pub struct QVerseContract {{
-    pub address: String,
-    pub contract_id: String,
-}}
-
-impl QVerseContract {{
-    pub fn new(address: String, contract_id: String) -> Self {{
-        Self {{ address, contract_id }}
-    }}
-    
-    pub async fn call(&self, function: &str, args: Vec<String>) -> Result<serde_json::Value, Box<dyn std::error::Error>> {{
-        let client = reqwest::Client::new();
-        let response = client
-            .post("http://localhost:8080/api/contracts/execute")
-            .json(&serde_json::json!({{
-                "contract_id": self.contract_id,
-                "function": function,
-                "args": args
-            }}))
-            .send()
-            .await?;
-        Ok(response.json().await?)
-    }}
-}}
-"#,
-        )
-    }
+    pub call_graph: Vec<CallGraphNode>,
 }
 
 #[cfg(test)]
@@ -190,4 +222,20 @@ mod tests {
         "#;
         assert!(ContractCompiler::validate_source(valid_source).is_ok());
     }
+
+    #[test]
+    fn test_compile_wat() {
+        let source = r#"(module (func (export "add") (param i32 i32) (result i32) local.get 0 local.get 1 i32.add))"#;
+        let compiled = ContractCompiler::compile_wat_contract(source, "Adder").unwrap();
+        assert!(hex::decode(&compiled.wasm_hex).unwrap().starts_with(b"\0asm"));
+        assert_eq!(compiled.compiler_version.as_deref(), Some(WAT_COMPILER_VERSION));
+        assert_eq!(compiled.source_code.as_deref(), Some(source));
+    }
+
+    #[test]
+    fn test_wat_syntax_error_position() {
+        let source = "(module\n  (func (result i32)\n    i32.konst 1))";
+        let err = ContractCompiler::assemble_wat(source).unwrap_err();
+        assert_eq!((err.line, err.column), (3, 5));
+    }
 }
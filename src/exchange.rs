@@ -17,12 +17,129 @@
  *   MIT License
  * ============================================== */
 
-use crate::models::{LiquidityPool, Order, Trade, OrderSide, OrderType};
+use crate::db::Database;
+use crate::models::{CurveType, LiquidityPool, Order, OrderSide, OrderStatus, SelfTradeMode, SelfTradePrevention, TimeInForce, Trade};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
 
 /// Automated Market Maker (AMM) - Uniswap V2 style constant product formula
 pub struct AMM;
 
+/// Curve StableSwap invariant for two pegged tokens (amplification A, n = 2):
+/// `A·n^n·(x + y) + D = A·D·n^n + D^(n+1) / (n^n·x·y)`. A higher A keeps the price
+/// closer to 1:1 over a wider range of balances; A → 0 approaches constant product.
+pub struct StableSwap;
+
+impl StableSwap {
+    const MAX_ITERATIONS: usize = 255;
+
+    /// Invariant D of balances `x` and `y` (Newton's method)
+    pub fn invariant(x: f64, y: f64, amplification: f64) -> Result<f64, Box<dyn Error>> {
+        if x <= 0.0 || y <= 0.0 {
+            return Err("Invalid reserves".into());
+        }
+        let sum = x + y;
+        let ann = amplification * 4.0;
+        let mut d = sum;
+        for _ in 0..Self::MAX_ITERATIONS {
+            let d_p = d * d / (2.0 * x) * d / (2.0 * y);
+            let prev = d;
+            d = (ann * sum + 2.0 * d_p) * d / ((ann - 1.0) * d + 3.0 * d_p);
+            if (d - prev).abs() <= 1e-12 * d {
+                return Ok(d);
+            }
+        }
+        Err("StableSwap invariant did not converge".into())
+    }
+
+    /// Balance of one token that keeps the invariant `d` when the other token's balance is `x`
+    fn balance_for(x: f64, d: f64, amplification: f64) -> Result<f64, Box<dyn Error>> {
+        let ann = amplification * 4.0;
+        let c = d * d / (2.0 * x) * d / (2.0 * ann);
+        let b = x + d / ann;
+        let mut y = d;
+        for _ in 0..Self::MAX_ITERATIONS {
+            let prev = y;
+            y = (y * y + c) / (2.0 * y + b - d);
+            if (y - prev).abs() <= 1e-12 * d {
+                return Ok(y);
+            }
+        }
+        Err("StableSwap balance did not converge".into())
+    }
+
+    /// Output for `amount_in`, the fee taken from the input like in `AMM::calculate_swap_out`
+    pub fn calculate_swap_out(
+        reserve_in: f64,
+        reserve_out: f64,
+        amount_in: f64,
+        fee_rate: f64,
+        amplification: f64,
+    ) -> Result<f64, Box<dyn Error>> {
+        if amount_in <= 0.0 {
+            return Err("Invalid reserves or amount".into());
+        }
+        let d = Self::invariant(reserve_in, reserve_out, amplification)?;
+        let balance_out = Self::balance_for(reserve_in + amount_in * (1.0 - fee_rate), d, amplification)?;
+        Ok((reserve_out - balance_out).max(0.0))
+    }
+}
+
+/// Pool shares minted to nobody by the first deposit, so a pool can never be drained to zero
+pub const MINIMUM_LIQUIDITY: f64 = 0.001;
+
+/// Token amounts and pool shares of a liquidity deposit or withdrawal
+#[derive(Debug, Clone, Serialize)]
+pub struct LiquidityQuote {
+    pub pool_id: String,
+    pub amount_a: f64,
+    pub amount_b: f64,
+    pub shares: f64, // Minted to / burned from the provider
+    pub locked: f64, // MINIMUM_LIQUIDITY on the first deposit
+}
+
+/// Outcome of swapping through one pool, computed from its current reserves
+#[derive(Debug, Clone, Serialize)]
+pub struct SwapQuote {
+    pub pool_id: String,
+    pub token_in: String,
+    pub token_out: String,
+    pub amount_in: f64,
+    pub amount_out: f64,
+    pub fee: f64,          // In token_in; stays in the pool for the LPs
+    pub reserve_in: f64,   // Reserves after the swap
+    pub reserve_out: f64,
+    pub price_impact: f64, // Percent of reserve_out taken
+}
+
+/// Swaps through a path of pools, each hop's output feeding the next
+#[derive(Debug, Clone, Serialize)]
+pub struct SwapRoute {
+    pub amount_in: f64,
+    pub amount_out: f64,
+    pub hops: Vec<SwapQuote>,
+}
+
+impl SwapRoute {
+    fn new(hops: Vec<SwapQuote>) -> Option<Self> {
+        Some(Self {
+            amount_in: hops.first()?.amount_in,
+            amount_out: hops.last()?.amount_out,
+            hops,
+        })
+    }
+
+    /// Fees charged along the route, per token (each hop charges in its input token)
+    pub fn fees(&self) -> BTreeMap<String, f64> {
+        let mut fees = BTreeMap::new();
+        for hop in &self.hops {
+            *fees.entry(hop.token_in.clone()).or_insert(0.0) += hop.fee;
+        }
+        fees
+    }
+}
+
 impl AMM {
     /// Calculate output amount for a swap (x * y = k)
     /// reserve_in: Reserve of input token
@@ -46,6 +163,104 @@ impl AMM {
         Ok(numerator / denominator)
     }
 
+    /// Quotes swapping `amount_in` of `token_in` through `pool` at its fee rate. The whole
+    /// input enters the reserves, so the fee grows the constant product for the LPs.
+    pub fn quote_swap(pool: &LiquidityPool, token_in: &str, amount_in: f64) -> Result<SwapQuote, Box<dyn Error>> {
+        let (token_out, reserve_in, reserve_out) = if pool.token_a == token_in {
+            (&pool.token_b, pool.reserve_a, pool.reserve_b)
+        } else if pool.token_b == token_in {
+            (&pool.token_a, pool.reserve_b, pool.reserve_a)
+        } else {
+            return Err(format!("{} is not in pool {}", token_in, pool.id).into());
+        };
+        if !(0.0..1.0).contains(&pool.fee_rate) {
+            return Err(format!("Invalid fee rate of pool {}", pool.id).into());
+        }
+
+        let amount_out = match Self::curve(pool)? {
+            CurveType::ConstantProduct => Self::calculate_swap_out(reserve_in, reserve_out, amount_in, pool.fee_rate)?,
+            CurveType::StableSwap => {
+                StableSwap::calculate_swap_out(reserve_in, reserve_out, amount_in, pool.fee_rate, pool.amplification)?
+            }
+        };
+        Ok(SwapQuote {
+            pool_id: pool.id.clone(),
+            token_in: token_in.to_string(),
+            token_out: token_out.clone(),
+            amount_in,
+            amount_out,
+            fee: amount_in * pool.fee_rate,
+            reserve_in: reserve_in + amount_in,
+            reserve_out: reserve_out - amount_out,
+            price_impact: amount_out / reserve_out * 100.0,
+        })
+    }
+
+    /// Curve of `pool`; StableSwap pools need an amplification of at least 1
+    fn curve(pool: &LiquidityPool) -> Result<CurveType, Box<dyn Error>> {
+        match CurveType::parse(&pool.curve_type) {
+            Some(CurveType::StableSwap) if pool.amplification < 1.0 => {
+                Err(format!("Invalid amplification of pool {}", pool.id).into())
+            }
+            Some(curve) => Ok(curve),
+            None => Err(format!("Unknown curve type of pool {}", pool.id).into()),
+        }
+    }
+
+    /// Quotes `amount_in` of `token_in` through `pools` in order, on their current reserves
+    pub fn quote_route(pools: &[LiquidityPool], token_in: &str, amount_in: f64) -> Result<SwapRoute, Box<dyn Error>> {
+        let mut hops: Vec<SwapQuote> = Vec::with_capacity(pools.len());
+        for pool in pools {
+            let quote = match hops.last() {
+                Some(prev) => Self::quote_swap(pool, &prev.token_out, prev.amount_out)?,
+                None => Self::quote_swap(pool, token_in, amount_in)?,
+            };
+            hops.push(quote);
+        }
+        SwapRoute::new(hops).ok_or_else(|| "Empty swap route".into())
+    }
+
+    /// Route with the highest output from `token_in` to `token_out` over at most `max_hops`
+    /// pools, with every hop's fee and price impact included. Paths never revisit a token.
+    pub fn best_route(pools: &[LiquidityPool], token_in: &str, token_out: &str, amount_in: f64, max_hops: usize) -> Option<SwapRoute> {
+        let mut best = None;
+        Self::search_routes(pools, token_in, token_out, amount_in, max_hops, &mut Vec::new(), &mut best);
+        best.and_then(SwapRoute::new)
+    }
+
+    fn search_routes(
+        pools: &[LiquidityPool],
+        token: &str,
+        token_out: &str,
+        amount: f64,
+        hops_left: usize,
+        path: &mut Vec<SwapQuote>,
+        best: &mut Option<Vec<SwapQuote>>,
+    ) {
+        if hops_left == 0 {
+            return;
+        }
+        for pool in pools {
+            // Skips pools without `token` or without liquidity
+            let Ok(quote) = Self::quote_swap(pool, token, amount) else { continue };
+            if path.iter().any(|hop| hop.token_in == quote.token_out) {
+                continue;
+            }
+
+            let out = quote.amount_out;
+            let next = quote.token_out.clone();
+            path.push(quote);
+            if next == token_out {
+                if best.as_ref().is_none_or(|hops| out > hops.last().map_or(0.0, |hop| hop.amount_out)) {
+                    *best = Some(path.clone());
+                }
+            } else {
+                Self::search_routes(pools, &next, token_out, out, hops_left - 1, path, best);
+            }
+            path.pop();
+        }
+    }
+
     /// Calculate required input amount for desired output
     pub fn calculate_swap_in(
         reserve_in: f64,
@@ -67,17 +282,18 @@ impl AMM {
         Ok(numerator / denominator)
     }
 
-    /// Add liquidity to pool
+    /// Pool shares minted for adding `amount_a` and `amount_b`
     pub fn add_liquidity(
         reserve_a: f64,
         reserve_b: f64,
+        total_supply: f64,
         amount_a: f64,
         amount_b: f64,
     ) -> Result<f64, Box<dyn Error>> {
         if reserve_a == 0.0 && reserve_b == 0.0 {
             // New pool: use geometric mean
             Ok((amount_a * amount_b).sqrt())
-        } else if reserve_a == 0.0 || reserve_b == 0.0 {
+        } else if reserve_a == 0.0 || reserve_b == 0.0 || total_supply <= 0.0 {
             return Err("Invalid pool state".into());
         } else {
             // Existing pool: maintain ratio
@@ -88,7 +304,8 @@ impl AMM {
                 return Err("Liquidity ratio mismatch".into());
             }
             
-            let liquidity = (amount_a * reserve_b + amount_b * reserve_a) / (2.0 * reserve_b);
+            // Same share of the pool as the smaller side of the deposit
+            let liquidity = (amount_a / reserve_a).min(amount_b / reserve_b) * total_supply;
             Ok(liquidity)
         }
     }
@@ -110,82 +327,587 @@ impl AMM {
         
         Ok((amount_a, amount_b))
     }
+
+    /// Quotes a deposit of up to `amount_a` / `amount_b`. An existing pool takes them at its
+    /// current ratio, so one side may be used only partly; each side must stay at or above
+    /// its minimum. The first deposit sets the price and locks `MINIMUM_LIQUIDITY`.
+    pub fn quote_add_liquidity(
+        pool: &LiquidityPool,
+        amount_a: f64,
+        amount_b: f64,
+        amount_a_min: f64,
+        amount_b_min: f64,
+    ) -> Result<LiquidityQuote, Box<dyn Error>> {
+        if amount_a <= 0.0 || amount_b <= 0.0 {
+            return Err("Liquidity amounts must be positive".into());
+        }
+        let first = pool.reserve_a == 0.0 && pool.reserve_b == 0.0;
+        let (amount_a, amount_b) = if first {
+            (amount_a, amount_b)
+        } else {
+            let optimal_b = amount_a * pool.reserve_b / pool.reserve_a;
+            if optimal_b <= amount_b {
+                (amount_a, optimal_b)
+            } else {
+                (amount_b * pool.reserve_a / pool.reserve_b, amount_b)
+            }
+        };
+        if amount_a < amount_a_min || amount_b < amount_b_min {
+            return Err(format!("Slippage too high: pool ratio takes {} / {}", amount_a, amount_b).into());
+        }
+
+        // Deposits are proportional on both curves; a new pool's shares follow its invariant
+        let minted = match (Self::curve(pool)?, first) {
+            (CurveType::StableSwap, true) => StableSwap::invariant(amount_a, amount_b, pool.amplification)?,
+            _ => Self::add_liquidity(pool.reserve_a, pool.reserve_b, pool.total_supply, amount_a, amount_b)?,
+        };
+        let locked = if first { MINIMUM_LIQUIDITY } else { 0.0 };
+        if minted <= locked {
+            return Err("Deposit too small".into());
+        }
+        Ok(LiquidityQuote { pool_id: pool.id.clone(), amount_a, amount_b, shares: minted - locked, locked })
+    }
+
+    /// Quotes burning `shares` for their part of both reserves, each at or above its minimum
+    pub fn quote_remove_liquidity(
+        pool: &LiquidityPool,
+        shares: f64,
+        amount_a_min: f64,
+        amount_b_min: f64,
+    ) -> Result<LiquidityQuote, Box<dyn Error>> {
+        if shares > pool.total_supply - MINIMUM_LIQUIDITY + DUST {
+            return Err("Cannot remove the locked minimum liquidity".into());
+        }
+        let (amount_a, amount_b) = Self::remove_liquidity(pool.reserve_a, pool.reserve_b, shares, pool.total_supply)?;
+        if amount_a < amount_a_min || amount_b < amount_b_min {
+            return Err(format!("Slippage too high: shares are worth {} / {}", amount_a, amount_b).into());
+        }
+        Ok(LiquidityQuote { pool_id: pool.id.clone(), amount_a, amount_b, shares, locked: 0.0 })
+    }
 }
 
-/// Order Matching Engine
-pub struct OrderMatcher;
+/// Quantities at or below this are treated as filled (f64 rounding)
+pub(crate) const DUST: f64 = 1e-9;
 
-impl OrderMatcher {
-    /// Match limit orders (simple price-time priority)
-    pub fn match_orders(
-        buy_orders: &mut Vec<Order>,
-        sell_orders: &mut Vec<Order>,
-    ) -> Vec<Trade> {
-        let mut trades = Vec::new();
-        
-        buy_orders.sort_by(|a, b| {
-            b.price.partial_cmp(&a.price)
-                .unwrap_or(std::cmp::Ordering::Equal)
-        });
-        
-        sell_orders.sort_by(|a, b| {
-            a.price.partial_cmp(&b.price)
-                .unwrap_or(std::cmp::Ordering::Equal)
+/// Resting limit orders of one pair
+#[derive(Debug, Clone, Default)]
+pub struct OrderBook {
+    bids: Vec<Order>, // Highest price first, oldest first within a price
+    asks: Vec<Order>, // Lowest price first, oldest first within a price
+}
+
+impl OrderBook {
+    /// Rests `order` behind every order at the same or a better price
+    pub fn insert(&mut self, order: Order) {
+        let side = OrderSide::parse(&order.side).unwrap_or(OrderSide::Buy);
+        let levels = self.side_mut(side);
+        let at = levels.partition_point(|o| match side {
+            OrderSide::Buy => o.price >= order.price,
+            OrderSide::Sell => o.price <= order.price,
         });
+        levels.insert(at, order);
+    }
 
-        let mut buy_idx = 0;
-        let mut sell_idx = 0;
-
-        while buy_idx < buy_orders.len() && sell_idx < sell_orders.len() {
-            let buy_order = &buy_orders[buy_idx];
-            let sell_order = &sell_orders[sell_idx];
-
-            if buy_order.price >= sell_order.price {
-                let trade_amount = (buy_order.amount - buy_order.filled)
-                    .min(sell_order.amount - sell_order.filled);
-                let trade_price = sell_order.price; // Price-time priority: taker pays maker's price
-
-                if trade_amount > 0.0 {
-                    let trade = Trade {
-                        id: uuid::Uuid::new_v4().to_string(),
-                        order_id: buy_order.id.clone(),
-                        pair: buy_order.pair.clone(),
-                        price: trade_price,
-                        amount: trade_amount,
-                        side: "BUY".to_string(),
-                        maker_wallet_id: sell_order.wallet_id.clone(),
-                        taker_wallet_id: buy_order.wallet_id.clone(),
-                        fee: trade_amount * trade_price * 0.001, // 0.1% fee
-                    };
-
-                    trades.push(trade);
-                    
-                    // Update filled amounts
-                    let buy_order = &mut buy_orders[buy_idx];
-                    buy_order.filled += trade_amount;
-                    if buy_order.filled >= buy_order.amount {
-                        buy_order.status = "FILLED".to_string();
-                        buy_idx += 1;
-                    }
+    pub fn remove(&mut self, order_id: &str) -> Option<Order> {
+        for levels in [&mut self.bids, &mut self.asks] {
+            if let Some(i) = levels.iter().position(|o| o.id == order_id) {
+                return Some(levels.remove(i));
+            }
+        }
+        None
+    }
+
+    pub fn get(&self, order_id: &str) -> Option<&Order> {
+        self.bids.iter().chain(&self.asks).find(|o| o.id == order_id)
+    }
+
+    pub fn bids(&self) -> &[Order] {
+        &self.bids
+    }
+
+    pub fn asks(&self) -> &[Order] {
+        &self.asks
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bids.is_empty() && self.asks.is_empty()
+    }
+
+    fn side(&self, side: OrderSide) -> &[Order] {
+        match side {
+            OrderSide::Buy => &self.bids,
+            OrderSide::Sell => &self.asks,
+        }
+    }
+
+    fn side_mut(&mut self, side: OrderSide) -> &mut Vec<Order> {
+        match side {
+            OrderSide::Buy => &mut self.bids,
+            OrderSide::Sell => &mut self.asks,
+        }
+    }
+
+    /// Updates size, fill, status and hold of a resting order in place (keeps its priority)
+    fn update(&mut self, order: &Order) {
+        for levels in [&mut self.bids, &mut self.asks] {
+            if let Some(resting) = levels.iter_mut().find(|o| o.id == order.id) {
+                resting.amount = order.amount;
+                resting.filled = order.filled;
+                resting.status = order.status.clone();
+                resting.held = order.held;
+            }
+        }
+    }
+}
+
+/// `orders.trigger_state` of a stop order hidden until its trigger price
+pub const STOP_WAITING: &str = "WAITING";
+/// `orders.trigger_state` of a stop order that has fired
+pub const STOP_TRIGGERED: &str = "TRIGGERED";
+
+/// Market orders without an explicit `max_slippage` accept 5% from the best price
+pub const DEFAULT_MARKET_SLIPPAGE: f64 = 0.05;
+
+/// How a matched order enters its book
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placement {
+    New,
+    Requeued,  // Amended price or increased size: loses time priority
+    Reduced,   // Decreased size at the same price: keeps its place
+    Stop,      // Stop order waiting, hidden, for its trigger price
+    Triggered, // Stop order that fired: leaves the hidden list and trades
+}
+
+/// Outcome of matching one incoming order against its book
+#[derive(Debug, Clone)]
+pub struct OrderMatch {
+    pub order: Order,       // Incoming order with its final fill and status
+    pub trades: Vec<Trade>, // In execution order
+    pub makers: Vec<Order>, // Resting orders it traded with or cancelled, afterwards
+    pub placement: Placement,
+    pub prevented: Vec<SelfTradePrevention>, // Self-trades that were not executed
+}
+
+impl OrderMatch {
+    /// Whether the incoming order's remainder joins the book
+    pub fn rests(&self) -> bool {
+        self.placement != Placement::Stop && is_open(&self.order)
+    }
+}
+
+/// Status of an order after `filled` of it has traded
+pub fn fill_status(order: &Order) -> OrderStatus {
+    if order.amount - order.filled <= DUST {
+        OrderStatus::Filled
+    } else if order.filled > 0.0 {
+        OrderStatus::PartiallyFilled
+    } else {
+        OrderStatus::Pending
+    }
+}
+
+fn is_open(order: &Order) -> bool {
+    order.status == OrderStatus::Pending.as_str() || order.status == OrderStatus::PartiallyFilled.as_str()
+}
+
+/// Worst price a market order accepts when the best opposite price is `best`
+pub fn slippage_bound(side: OrderSide, best: f64, slippage: f64) -> f64 {
+    match side {
+        OrderSide::Buy => best * (1.0 + slippage),
+        OrderSide::Sell => best * (1.0 - slippage),
+    }
+}
+
+/// Token and amount a wallet needs for the unfilled part of `order`:
+/// quote at the limit price plus the taker fee for buys, base for sells
+pub fn required_funds(order: &Order, fee_rate: f64) -> Option<(&str, f64)> {
+    let (base, quote) = order.pair.split_once('/')?;
+    let remaining = order.amount - order.filled;
+    match OrderSide::parse(&order.side)? {
+        OrderSide::Buy => Some((quote, remaining * order.price * (1.0 + fee_rate))),
+        OrderSide::Sell => Some((base, remaining)),
+    }
+}
+
+/// In-memory limit order books with price-time priority, one per pair.
+///
+/// Matching is planned without touching the books: callers persist the
+/// `OrderMatch` (trades, fills and balances in one DB transaction), then
+/// `apply` it while still holding the engine lock, like `ContractRegistry`.
+/// Trades execute at the maker's price; the taker pays `fee_rate` of the
+/// notional in the quote token.
+///
+/// Market orders carry their worst acceptable price in `price` and never
+/// rest: what the book cannot fill within it is cancelled. Stop orders wait
+/// outside the books until the last trade or the oracle price reaches
+/// `stop_price`, then trade as their `order_type` (MARKET or LIMIT).
+pub struct MatchingEngine {
+    books: HashMap<String, OrderBook>,
+    stops: HashMap<String, Vec<Order>>, // Waiting stop orders per pair, oldest first
+    last_prices: HashMap<String, f64>,
+    oracle_prices: HashMap<String, f64>,
+    fee_rate: f64,
+}
+
+impl MatchingEngine {
+    pub fn new(fee_rate: f64) -> Self {
+        Self {
+            books: HashMap::new(),
+            stops: HashMap::new(),
+            last_prices: HashMap::new(),
+            oracle_prices: HashMap::new(),
+            fee_rate: fee_rate.clamp(0.0, 1.0),
+        }
+    }
+
+    pub fn fee_rate(&self) -> f64 {
+        self.fee_rate
+    }
+
+    /// Rebuilds the books and waiting stops from open orders, given oldest first
+    pub fn load(&mut self, orders: Vec<Order>) {
+        for order in orders {
+            if is_waiting_stop(&order) {
+                self.stops.entry(order.pair.clone()).or_default().push(order);
+            } else {
+                self.books.entry(order.pair.clone()).or_default().insert(order);
+            }
+        }
+    }
+
+    pub fn book(&self, pair: &str) -> Option<&OrderBook> {
+        self.books.get(pair)
+    }
+
+    /// Price of the last trade in `pair`
+    pub fn last_price(&self, pair: &str) -> Option<f64> {
+        self.last_prices.get(pair).copied()
+    }
+
+    pub fn set_last_price(&mut self, pair: &str, price: f64) {
+        self.last_prices.insert(pair.to_string(), price);
+    }
+
+    /// Oracle reference price of `pair` (base price / quote price)
+    pub fn set_oracle_price(&mut self, pair: &str, price: f64) {
+        self.oracle_prices.insert(pair.to_string(), price);
+    }
+
+    /// Pairs with stop orders waiting for a trigger
+    pub fn stop_pairs(&self) -> Vec<String> {
+        self.stops.iter()
+            .filter(|(_, stops)| !stops.is_empty())
+            .map(|(pair, _)| pair.clone())
+            .collect()
+    }
 
-                    let sell_order = &mut sell_orders[sell_idx];
-                    sell_order.filled += trade_amount;
-                    if sell_order.filled >= sell_order.amount {
-                        sell_order.status = "FILLED".to_string();
-                        sell_idx += 1;
+    /// Best opposite price a market order on `side` would trade at first
+    pub fn best_price(&self, pair: &str, side: OrderSide) -> Option<f64> {
+        let book = self.books.get(pair)?;
+        let best = match side {
+            OrderSide::Buy => book.asks.first(),
+            OrderSide::Sell => book.bids.first(),
+        };
+        best.map(|o| o.price)
+    }
+
+    /// Open order by id: resting in a book or a waiting stop
+    pub fn order(&self, order_id: &str) -> Option<&Order> {
+        self.books.values().find_map(|book| book.get(order_id))
+            .or_else(|| self.stops.values().flatten().find(|o| o.id == order_id))
+    }
+
+    /// Open orders of `wallet_id` (waiting stops included), optionally of one pair
+    pub fn open_orders(&self, wallet_id: &str, pair: Option<&str>) -> Vec<&Order> {
+        let resting = self.books.iter()
+            .filter(|(p, _)| pair.is_none_or(|pair| pair == p.as_str()))
+            .flat_map(|(_, book)| book.bids.iter().chain(&book.asks));
+        let stops = self.stops.iter()
+            .filter(|(p, _)| pair.is_none_or(|pair| pair == p.as_str()))
+            .flat_map(|(_, stops)| stops.iter());
+        resting.chain(stops)
+            .filter(|o| o.wallet_id == wallet_id)
+            .collect()
+    }
+
+    /// Takes a cancelled order off its book (after the cancellation is persisted)
+    pub fn remove(&mut self, order_id: &str) -> Option<Order> {
+        if let Some(order) = self.books.values_mut().find_map(|book| book.remove(order_id)) {
+            return Some(order);
+        }
+        self.stops.values_mut().find_map(|stops| {
+            let i = stops.iter().position(|o| o.id == order_id)?;
+            Some(stops.remove(i))
+        })
+    }
+
+    /// Open GTD orders (resting or waiting stops) whose expiry is at or before `now`
+    pub fn expired_orders(&self, now: i64) -> Vec<String> {
+        let resting = self.books.values().flat_map(|book| book.bids.iter().chain(&book.asks));
+        resting.chain(self.stops.values().flatten())
+            .filter(|o| o.time_in_force == TimeInForce::Gtd.as_str() && o.expires_at.is_some_and(|at| at <= now))
+            .map(|o| o.id.clone())
+            .collect()
+    }
+
+    /// Moves expired GTD orders to `EXPIRED`, releasing their holds, then drops them
+    /// from the books. Returns the number of orders expired.
+    pub async fn expire_orders(&mut self, db: &Database, now: i64) -> Result<usize, Box<dyn Error>> {
+        let order_ids = self.expired_orders(now);
+        if order_ids.is_empty() {
+            return Ok(0);
+        }
+        db.expire_orders(&order_ids).await?;
+        for order_id in &order_ids {
+            self.remove(order_id);
+        }
+        Ok(order_ids.len())
+    }
+
+    /// Matches a new `order` against the opposite side of its book. A stop order
+    /// whose trigger has already been reached trades at once, otherwise it waits.
+    pub fn plan(&self, mut order: Order) -> Result<OrderMatch, Box<dyn Error>> {
+        if is_waiting_stop(&order) {
+            if !self.stop_reached(&order) {
+                order.held = self.hold_for(&order);
+                return Ok(OrderMatch { order, trades: Vec::new(), makers: Vec::new(), placement: Placement::Stop, prevented: Vec::new() });
+            }
+            order.trigger_state = Some(STOP_TRIGGERED.to_string());
+        }
+        self.match_order(order, Placement::New)
+    }
+
+    /// First waiting stop of `pair` whose trigger price has been reached
+    pub fn next_triggered(&self, pair: &str) -> Option<&Order> {
+        self.stops.get(pair)?.iter().find(|o| self.stop_reached(o))
+    }
+
+    /// Plans the execution of a waiting stop order as its market or limit order
+    pub fn plan_trigger(&self, order_id: &str) -> Result<OrderMatch, Box<dyn Error>> {
+        let mut order = self.stops.values().flatten()
+            .find(|o| o.id == order_id)
+            .cloned()
+            .ok_or("Stop order is not waiting")?;
+        order.trigger_state = Some(STOP_TRIGGERED.to_string());
+        self.match_order(order, Placement::Triggered)
+    }
+
+    /// Plans a price and/or size change of a resting order. A smaller size at the
+    /// same price keeps the order's place; anything else requeues it behind its new
+    /// price level and may trade immediately.
+    pub fn plan_amend(&self, order_id: &str, price: Option<f64>, amount: Option<f64>) -> Result<OrderMatch, Box<dyn Error>> {
+        let current = self.order(order_id).ok_or("Order is not open")?;
+        if is_waiting_stop(current) {
+            return Err("Waiting stop orders cannot be amended; cancel and place a new one".into());
+        }
+        let mut order = current.clone();
+        order.price = price.unwrap_or(current.price);
+        order.amount = amount.unwrap_or(current.amount);
+        if order.amount - order.filled <= DUST {
+            return Err(format!("Amount must exceed the filled {}; cancel the order instead", order.filled).into());
+        }
+
+        if order.price == current.price && order.amount <= current.amount {
+            order.status = fill_status(&order).as_str().to_string();
+            order.held = self.hold_for(&order);
+            return Ok(OrderMatch { order, trades: Vec::new(), makers: Vec::new(), placement: Placement::Reduced, prevented: Vec::new() });
+        }
+        self.match_order(order, Placement::Requeued)
+    }
+
+    /// Whether the last trade or the oracle price has reached the stop price
+    fn stop_reached(&self, order: &Order) -> bool {
+        let (Some(stop), Some(side)) = (order.stop_price, OrderSide::parse(&order.side)) else {
+            return false;
+        };
+        [self.last_prices.get(&order.pair), self.oracle_prices.get(&order.pair)]
+            .into_iter()
+            .flatten()
+            .any(|&price| match side {
+                OrderSide::Buy => price >= stop,
+                OrderSide::Sell => price <= stop,
+            })
+    }
+
+    fn match_order(&self, mut order: Order, placement: Placement) -> Result<OrderMatch, Box<dyn Error>> {
+        let side = OrderSide::parse(&order.side).ok_or("Invalid order side")?;
+        let mut trades = Vec::new();
+        let mut makers = Vec::new();
+        let mut prevented = Vec::new();
+
+        let opposite = match side {
+            OrderSide::Buy => OrderSide::Sell,
+            OrderSide::Sell => OrderSide::Buy,
+        };
+        let resting = self.books.get(&order.pair).map(|book| book.side(opposite)).unwrap_or_default();
+        let tif = TimeInForce::parse(&order.time_in_force).unwrap_or(TimeInForce::Gtc);
+        let stp = SelfTradeMode::parse(&order.stp_mode).unwrap_or_default();
+        let stops_taker = matches!(stp, SelfTradeMode::CancelNewest | SelfTradeMode::CancelBoth);
+
+        // Book is sorted: the orders it can trade with come first
+        let crossing = resting.iter().take_while(|maker| crosses(side, maker.price, order.price));
+        if order.post_only && crossing.clone().next().is_some() {
+            return Err("Post-only order would trade on entry".into());
+        }
+        if tif == TimeInForce::Fok {
+            // Own orders never fill it, and may end matching before the rest of the book
+            let fillable: f64 = crossing
+                .take_while(|maker| maker.wallet_id != order.wallet_id || !stops_taker)
+                .filter(|maker| maker.wallet_id != order.wallet_id)
+                .map(|maker| maker.amount - maker.filled)
+                .sum();
+            if fillable < order.amount - order.filled - DUST {
+                order.status = OrderStatus::Cancelled.as_str().to_string(); // Killed: nothing trades
+                order.held = 0.0;
+                return Ok(OrderMatch { order, trades, makers, placement, prevented });
+            }
+        }
+
+        let mut taker_cancelled = false;
+        for maker in resting {
+            let remaining = order.amount - order.filled;
+            if remaining <= DUST {
+                break;
+            }
+            if !crosses(side, maker.price, order.price) {
+                break;
+            }
+
+            let amount = remaining.min(maker.amount - maker.filled);
+            if maker.wallet_id == order.wallet_id {
+                prevented.push(SelfTradePrevention {
+                    id: uuid::Uuid::new_v4().to_string(),
+                    wallet_id: order.wallet_id.clone(),
+                    pair: order.pair.clone(),
+                    taker_order_id: order.id.clone(),
+                    maker_order_id: maker.id.clone(),
+                    mode: stp.as_str().to_string(),
+                    amount,
+                    price: maker.price,
+                });
+
+                let mut maker = maker.clone();
+                let (cancel_taker, cancel_maker) = match stp {
+                    SelfTradeMode::CancelNewest => (true, false),
+                    SelfTradeMode::CancelOldest => (false, true),
+                    SelfTradeMode::CancelBoth => (true, true),
+                    SelfTradeMode::DecrementAndCancel => {
+                        // The overlap is taken off both; whichever runs out is cancelled
+                        order.amount -= amount;
+                        maker.amount -= amount;
+                        (order.amount - order.filled <= DUST, maker.amount - maker.filled <= DUST)
                     }
-                } else {
+                };
+                if cancel_maker {
+                    maker.status = OrderStatus::Cancelled.as_str().to_string();
+                    maker.held = 0.0;
+                    makers.push(maker);
+                } else if stp == SelfTradeMode::DecrementAndCancel {
+                    maker.held = self.hold_for(&maker);
+                    makers.push(maker);
+                }
+                if cancel_taker {
+                    taker_cancelled = true;
                     break;
                 }
+                continue;
+            }
+
+            trades.push(Trade {
+                id: uuid::Uuid::new_v4().to_string(),
+                order_id: order.id.clone(),
+                maker_order_id: maker.id.clone(),
+                pair: order.pair.clone(),
+                price: maker.price,
+                amount,
+                side: side.as_str().to_string(),
+                maker_wallet_id: maker.wallet_id.clone(),
+                taker_wallet_id: order.wallet_id.clone(),
+                fee: amount * maker.price * self.fee_rate,
+            });
+
+            let mut maker = maker.clone();
+            maker.filled += amount;
+            maker.status = fill_status(&maker).as_str().to_string();
+            maker.held = self.hold_for(&maker);
+            makers.push(maker);
+            order.filled += amount;
+        }
+
+        let mut status = fill_status(&order);
+        let immediate = order.order_type == "MARKET" || matches!(tif, TimeInForce::Ioc | TimeInForce::Fok);
+        if taker_cancelled || (immediate && status != OrderStatus::Filled) {
+            status = OrderStatus::Cancelled; // Never rests: the unfilled rest is dropped
+        }
+        order.status = status.as_str().to_string();
+        order.held = self.hold_for(&order);
+        Ok(OrderMatch { order, trades, makers, placement, prevented })
+    }
+
+    /// Funds to keep reserved for the unfilled part of an open `order`; buys include
+    /// the taker fee so an amend that crosses the book is always covered
+    fn hold_for(&self, order: &Order) -> f64 {
+        if !is_open(order) || fill_status(order) == OrderStatus::Filled {
+            return 0.0;
+        }
+        required_funds(order, self.fee_rate).map(|(_, amount)| amount).unwrap_or(0.0)
+    }
+
+    /// Applies a persisted match: fills makers and rests the remainder
+    pub fn apply(&mut self, matched: &OrderMatch) {
+        let pair = &matched.order.pair;
+        match matched.placement {
+            Placement::Stop => {
+                self.stops.entry(pair.clone()).or_default().push(matched.order.clone());
+                return;
+            }
+            Placement::Triggered => {
+                if let Some(stops) = self.stops.get_mut(pair) {
+                    stops.retain(|o| o.id != matched.order.id);
+                }
+            }
+            _ => {}
+        }
+        if let Some(trade) = matched.trades.last() {
+            self.last_prices.insert(pair.clone(), trade.price);
+        }
+
+        let book = self.books.entry(pair.clone()).or_default();
+        match matched.placement {
+            Placement::Requeued => {
+                book.remove(&matched.order.id);
+            }
+            Placement::Reduced => {
+                book.update(&matched.order);
+                return;
+            }
+            _ => {}
+        }
+        for maker in &matched.makers {
+            if !is_open(maker) || fill_status(maker) == OrderStatus::Filled {
+                book.remove(&maker.id);
             } else {
-                break; // No more matches possible
+                book.update(maker);
             }
         }
+        if matched.rests() {
+            book.insert(matched.order.clone());
+        }
+    }
+}
 
-        trades
+/// Whether a maker at `maker_price` is within the limit of a taker on `side`
+fn crosses(side: OrderSide, maker_price: f64, limit: f64) -> bool {
+    match side {
+        OrderSide::Buy => maker_price <= limit,
+        OrderSide::Sell => maker_price >= limit,
     }
 }
 
+fn is_waiting_stop(order: &Order) -> bool {
+    order.trigger_state.as_deref() == Some(STOP_WAITING)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -202,9 +924,385 @@ mod tests {
         assert!(amount_out < reserve_b);
     }
 
+    fn pool(reserve_a: f64, reserve_b: f64, total_supply: f64) -> LiquidityPool {
+        LiquidityPool {
+            id: "p1".to_string(),
+            token_a: "QVR".to_string(),
+            token_b: "USDT".to_string(),
+            reserve_a,
+            reserve_b,
+            total_supply,
+            fee_rate: 0.01,
+            fees_a: 0.0,
+            fees_b: 0.0,
+            curve_type: "CONSTANT_PRODUCT".to_string(),
+            amplification: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_quote_swap_keeps_fee_in_pool() {
+        let pool = pool(1000.0, 2000.0, 1414.0);
+        let quote = AMM::quote_swap(&pool, "USDT", 100.0).unwrap();
+        assert_eq!(quote.token_out, "QVR");
+        assert_eq!((quote.reserve_in, quote.fee), (2100.0, 1.0));
+        assert!((quote.reserve_out - (1000.0 - quote.amount_out)).abs() < 1e-9);
+        // Without the fee k would be unchanged; with it k grows
+        assert!(quote.reserve_in * quote.reserve_out > 1000.0 * 2000.0);
+        assert!(AMM::quote_swap(&pool, "BTC", 1.0).is_err());
+    }
+
+    #[test]
+    fn test_best_route_over_hops() {
+        let pair = |id: &str, a: &str, b: &str, reserve_a: f64, reserve_b: f64| LiquidityPool {
+            id: id.to_string(),
+            token_a: a.to_string(),
+            token_b: b.to_string(),
+            fee_rate: 0.003,
+            ..pool(reserve_a, reserve_b, 1.0)
+        };
+        // Shallow direct pool vs. a deep path through RGLS
+        let pools = vec![
+            pair("direct", "QVR", "POPEO", 100.0, 100.0),
+            pair("qvr-rgls", "QVR", "RGLS", 10_000.0, 10_000.0),
+            pair("rgls-popeo", "RGLS", "POPEO", 10_000.0, 10_000.0),
+            pair("empty", "QVR", "QVRg", 0.0, 0.0),
+        ];
+
+        let route = AMM::best_route(&pools, "QVR", "POPEO", 10.0, 3).unwrap();
+        let ids: Vec<&str> = route.hops.iter().map(|hop| hop.pool_id.as_str()).collect();
+        assert_eq!(ids, ["qvr-rgls", "rgls-popeo"]);
+        assert_eq!(route.amount_out, route.hops[1].amount_out);
+        let fees = route.fees();
+        assert_eq!(fees.keys().collect::<Vec<_>>(), ["QVR", "RGLS"]);
+        assert_eq!(fees["RGLS"], route.hops[1].fee);
+
+        // One hop allowed: only the direct pool
+        let route = AMM::best_route(&pools, "QVR", "POPEO", 10.0, 1).unwrap();
+        assert_eq!(route.hops.len(), 1);
+        assert!(AMM::best_route(&pools, "QVR", "QVRg", 10.0, 3).is_none());
+
+        // Re-quoting the chosen path gives the same output
+        let path = [pools[1].clone(), pools[2].clone()];
+        let requote = AMM::quote_route(&path, "QVR", 10.0).unwrap();
+        assert_eq!(requote.amount_out, AMM::best_route(&pools, "QVR", "POPEO", 10.0, 3).unwrap().amount_out);
+    }
+
+    #[test]
+    fn test_stable_swap_curve() {
+        // Balanced pool: D is the sum of the balances
+        assert!((StableSwap::invariant(1000.0, 1000.0, 100.0).unwrap() - 2000.0).abs() < 1e-9);
+
+        // Pegged pair trades close to 1:1, far better than constant product
+        let stable = StableSwap::calculate_swap_out(1000.0, 1000.0, 10.0, 0.0, 100.0).unwrap();
+        let product = AMM::calculate_swap_out(1000.0, 1000.0, 10.0, 0.0).unwrap();
+        assert!(stable > 9.99 && stable < 10.0);
+        assert!(stable > product);
+        // A lower amplification moves toward constant product
+        let flat = StableSwap::calculate_swap_out(1000.0, 1000.0, 10.0, 0.0, 1.0).unwrap();
+        assert!(flat < stable && flat > product);
+
+        // Pools dispatch on their curve type
+        let mut stable_pool = LiquidityPool {
+            curve_type: "STABLE_SWAP".to_string(),
+            amplification: 100.0,
+            fee_rate: 0.0,
+            ..pool(1000.0, 1000.0, 2000.0)
+        };
+        assert_eq!(AMM::quote_swap(&stable_pool, "QVR", 10.0).unwrap().amount_out, stable);
+        stable_pool.amplification = 0.0;
+        assert!(AMM::quote_swap(&stable_pool, "QVR", 10.0).is_err());
+    }
+
     #[test]
     fn test_add_liquidity() {
-        let liquidity = AMM::add_liquidity(0.0, 0.0, 1000.0, 2000.0).unwrap();
+        let liquidity = AMM::add_liquidity(0.0, 0.0, 0.0, 1000.0, 2000.0).unwrap();
         assert!(liquidity > 0.0);
     }
+
+    #[test]
+    fn test_liquidity_quotes() {
+        let mut pool = pool(0.0, 0.0, 0.0);
+
+        // First deposit: sqrt(100 * 400) = 200 shares, part of them locked
+        let quote = AMM::quote_add_liquidity(&pool, 100.0, 400.0, 0.0, 0.0).unwrap();
+        assert_eq!((quote.shares + quote.locked, quote.locked), (200.0, MINIMUM_LIQUIDITY));
+        assert!(AMM::quote_add_liquidity(&pool, 1e-4, 1e-4, 0.0, 0.0).is_err());
+
+        // Later deposits follow the pool ratio, bounded by the minimums
+        (pool.reserve_a, pool.reserve_b, pool.total_supply) = (100.0, 400.0, 200.0);
+        let quote = AMM::quote_add_liquidity(&pool, 10.0, 100.0, 0.0, 0.0).unwrap();
+        assert_eq!((quote.amount_a, quote.amount_b, quote.shares), (10.0, 40.0, 20.0));
+        assert!(AMM::quote_add_liquidity(&pool, 10.0, 100.0, 0.0, 50.0).is_err());
+
+        let quote = AMM::quote_remove_liquidity(&pool, 50.0, 25.0, 100.0).unwrap();
+        assert_eq!((quote.amount_a, quote.amount_b), (25.0, 100.0));
+        assert!(AMM::quote_remove_liquidity(&pool, 50.0, 26.0, 0.0).is_err());
+        assert!(AMM::quote_remove_liquidity(&pool, 200.0, 0.0, 0.0).is_err());
+    }
+
+    fn limit(id: &str, wallet: &str, side: &str, price: f64, amount: f64) -> Order {
+        Order {
+            id: id.to_string(),
+            wallet_id: wallet.to_string(),
+            pair: "QVR/USDT".to_string(),
+            side: side.to_string(),
+            order_type: "LIMIT".to_string(),
+            price,
+            amount,
+            filled: 0.0,
+            status: "PENDING".to_string(),
+            held: 0.0,
+            stop_price: None,
+            trigger_state: None,
+            time_in_force: "GTC".to_string(),
+            expires_at: None,
+            post_only: false,
+            stp_mode: "CANCEL_NEWEST".to_string(),
+        }
+    }
+
+    fn stop(id: &str, side: &str, stop_price: f64, order_type: &str, price: f64) -> Order {
+        Order {
+            order_type: order_type.to_string(),
+            stop_price: Some(stop_price),
+            trigger_state: Some(STOP_WAITING.to_string()),
+            ..limit(id, "sam", side, price, 1.0)
+        }
+    }
+
+    #[test]
+    fn test_price_time_priority() {
+        let mut engine = MatchingEngine::new(0.001);
+        engine.load(vec![
+            limit("a1", "alice", "SELL", 10.0, 5.0),
+            limit("b1", "bob", "SELL", 9.5, 2.0),
+            limit("a2", "alice", "SELL", 9.5, 2.0), // same price as b1, later
+            limit("c1", "carol", "BUY", 9.0, 1.0),
+        ]);
+
+        let matched = engine.plan(limit("t1", "taker", "BUY", 10.0, 5.0)).unwrap();
+        let fills: Vec<_> = matched.trades.iter().map(|t| (t.maker_order_id.as_str(), t.price, t.amount)).collect();
+        assert_eq!(fills, vec![("b1", 9.5, 2.0), ("a2", 9.5, 2.0), ("a1", 10.0, 1.0)]);
+        assert_eq!(matched.order.status, "FILLED");
+        assert!((matched.trades[0].fee - 0.019).abs() < 1e-12);
+        assert!(!matched.rests());
+
+        // Planning leaves the book alone until the match is applied
+        assert_eq!(engine.book("QVR/USDT").unwrap().asks().len(), 3);
+        engine.apply(&matched);
+        let book = engine.book("QVR/USDT").unwrap();
+        assert_eq!(book.asks().len(), 1);
+        assert_eq!(book.asks()[0].filled, 1.0);
+        assert_eq!(book.asks()[0].status, "PARTIALLY_FILLED");
+    }
+
+    #[test]
+    fn test_unmatched_remainder_rests() {
+        let mut engine = MatchingEngine::new(0.0);
+        engine.load(vec![limit("a1", "alice", "BUY", 9.0, 1.0)]);
+
+        // Sells down to 8: fills the bid at 9, rests the rest as the best ask
+        let matched = engine.plan(limit("t1", "taker", "SELL", 8.0, 3.0)).unwrap();
+        assert_eq!(matched.trades.len(), 1);
+        assert_eq!(matched.trades[0].price, 9.0);
+        assert_eq!(matched.order.status, "PARTIALLY_FILLED");
+        engine.apply(&matched);
+
+        let book = engine.book("QVR/USDT").unwrap();
+        assert!(book.bids().is_empty());
+        assert_eq!(book.asks()[0].id, "t1");
+        assert_eq!(book.asks()[0].filled, 1.0);
+
+        // A bid below the best ask does not cross
+        let matched = engine.plan(limit("t2", "taker", "BUY", 7.0, 1.0)).unwrap();
+        assert!(matched.trades.is_empty());
+        assert_eq!(matched.order.status, "PENDING");
+    }
+
+    #[test]
+    fn test_holds_follow_unfilled_part() {
+        let mut engine = MatchingEngine::new(0.01);
+        let bid = engine.plan(limit("b1", "bob", "BUY", 10.0, 4.0)).unwrap();
+        assert!((bid.order.held - 40.4).abs() < 1e-9); // quote at the limit price + taker fee
+        engine.apply(&bid);
+
+        let matched = engine.plan(limit("s1", "sam", "SELL", 10.0, 1.0)).unwrap();
+        assert_eq!(matched.order.held, 0.0); // filled at once: nothing to hold
+        assert!((matched.makers[0].held - 30.3).abs() < 1e-9);
+
+        let ask = engine.plan(limit("s2", "sam", "SELL", 12.0, 2.0)).unwrap();
+        assert_eq!(ask.order.held, 2.0); // base for sells
+    }
+
+    #[test]
+    fn test_amend_priority() {
+        let mut engine = MatchingEngine::new(0.0);
+        engine.load(vec![
+            limit("a1", "alice", "SELL", 10.0, 5.0),
+            limit("b1", "bob", "SELL", 10.0, 5.0),
+        ]);
+
+        // Smaller size at the same price keeps the front of the queue
+        let amended = engine.plan_amend("a1", None, Some(3.0)).unwrap();
+        assert_eq!(amended.placement, Placement::Reduced);
+        engine.apply(&amended);
+        let ids: Vec<_> = engine.book("QVR/USDT").unwrap().asks().iter().map(|o| (o.id.as_str(), o.amount)).collect();
+        assert_eq!(ids, vec![("a1", 3.0), ("b1", 5.0)]);
+
+        // A larger size goes to the back of the level
+        let amended = engine.plan_amend("a1", None, Some(4.0)).unwrap();
+        assert_eq!(amended.placement, Placement::Requeued);
+        engine.apply(&amended);
+        assert_eq!(engine.book("QVR/USDT").unwrap().asks()[0].id, "b1");
+
+        // A crossing price trades at once
+        engine.load(vec![limit("c1", "carol", "BUY", 9.0, 1.0)]);
+        let amended = engine.plan_amend("b1", Some(9.0), None).unwrap();
+        assert_eq!(amended.trades.len(), 1);
+        assert_eq!(amended.trades[0].maker_order_id, "c1");
+        engine.apply(&amended);
+        let book = engine.book("QVR/USDT").unwrap();
+        assert!(book.bids().is_empty());
+        assert_eq!(book.asks().iter().map(|o| o.id.as_str()).collect::<Vec<_>>(), vec!["b1", "a1"]);
+        assert_eq!(engine.open_orders("bob", None)[0].filled, 1.0);
+
+        assert!(engine.plan_amend("b1", None, Some(1.0)).is_err()); // not above the filled amount
+        assert!(engine.remove("a1").is_some());
+        assert!(engine.order("a1").is_none());
+    }
+
+    #[test]
+    fn test_market_order_sweeps_within_bound() {
+        let mut engine = MatchingEngine::new(0.0);
+        engine.load(vec![
+            limit("a1", "alice", "SELL", 10.0, 1.0),
+            limit("a2", "alice", "SELL", 10.4, 1.0),
+            limit("a3", "alice", "SELL", 11.0, 1.0),
+        ]);
+
+        let best = engine.best_price("QVR/USDT", OrderSide::Buy).unwrap();
+        let bound = slippage_bound(OrderSide::Buy, best, 0.05);
+        let market = Order { order_type: "MARKET".to_string(), ..limit("m1", "taker", "BUY", bound, 3.0) };
+        let matched = engine.plan(market).unwrap();
+
+        // 11.0 is beyond 10.5: the third unit is cancelled, never rests
+        assert_eq!(matched.trades.len(), 2);
+        assert_eq!(matched.order.filled, 2.0);
+        assert_eq!(matched.order.status, "CANCELLED");
+        assert_eq!(matched.order.held, 0.0);
+        engine.apply(&matched);
+        assert!(engine.book("QVR/USDT").unwrap().bids().is_empty());
+        assert_eq!(engine.last_price("QVR/USDT"), Some(10.4));
+    }
+
+    #[test]
+    fn test_stop_orders_wait_for_trigger() {
+        let mut engine = MatchingEngine::new(0.0);
+        engine.load(vec![
+            limit("b1", "bob", "BUY", 9.0, 5.0),
+            stop("s1", "SELL", 9.5, "MARKET", 9.0),
+            stop("s2", "SELL", 8.0, "LIMIT", 8.0),
+        ]);
+        assert!(engine.next_triggered("QVR/USDT").is_none());
+        assert_eq!(engine.open_orders("sam", None).len(), 2);
+        assert!(engine.book("QVR/USDT").unwrap().asks().is_empty()); // hidden
+
+        // A trade at 9.0 reaches the 9.5 stop only
+        let matched = engine.plan(limit("t1", "taker", "SELL", 9.0, 1.0)).unwrap();
+        engine.apply(&matched);
+        let triggered = engine.next_triggered("QVR/USDT").unwrap().id.clone();
+        assert_eq!(triggered, "s1");
+
+        let fired = engine.plan_trigger(&triggered).unwrap();
+        assert_eq!(fired.order.trigger_state.as_deref(), Some(STOP_TRIGGERED));
+        assert_eq!(fired.order.status, "FILLED");
+        engine.apply(&fired);
+        assert!(engine.next_triggered("QVR/USDT").is_none());
+
+        // The oracle price can trigger too
+        engine.set_oracle_price("QVR/USDT", 7.9);
+        assert_eq!(engine.next_triggered("QVR/USDT").unwrap().id, "s2");
+
+        // A new stop whose trigger was already reached trades immediately
+        let matched = engine.plan(stop("s3", "SELL", 9.5, "LIMIT", 9.0)).unwrap();
+        assert_eq!(matched.placement, Placement::New);
+        assert_eq!(matched.trades.len(), 1);
+    }
+
+    #[test]
+    fn test_time_in_force_and_post_only() {
+        let mut engine = MatchingEngine::new(0.0);
+        engine.load(vec![
+            limit("a1", "alice", "SELL", 10.0, 1.0),
+            limit("a2", "alice", "SELL", 11.0, 1.0),
+        ]);
+        let with_tif = |id: &str, tif: &str, amount: f64| Order {
+            time_in_force: tif.to_string(),
+            ..limit(id, "taker", "BUY", 10.5, amount)
+        };
+
+        // IOC: takes what is there, drops the rest
+        let matched = engine.plan(with_tif("i1", "IOC", 2.0)).unwrap();
+        assert_eq!((matched.order.filled, matched.order.status.as_str()), (1.0, "CANCELLED"));
+        assert!(!matched.rests());
+
+        // FOK: only one unit fits within 10.5, so nothing trades
+        let matched = engine.plan(with_tif("f1", "FOK", 2.0)).unwrap();
+        assert!(matched.trades.is_empty());
+        assert_eq!(matched.order.status, "CANCELLED");
+        let matched = engine.plan(with_tif("f2", "FOK", 1.0)).unwrap();
+        assert_eq!(matched.order.status, "FILLED");
+
+        // Post-only is rejected when it would take liquidity
+        let post = |price: f64| Order { post_only: true, ..limit("p1", "taker", "BUY", price, 1.0) };
+        assert!(engine.plan(post(10.0)).is_err());
+        assert!(engine.plan(post(9.9)).unwrap().rests());
+
+        // GTD orders expire, others never do
+        let gtd = Order { time_in_force: "GTD".to_string(), expires_at: Some(100), ..limit("g1", "bob", "BUY", 9.0, 1.0) };
+        engine.load(vec![gtd]);
+        assert!(engine.expired_orders(99).is_empty());
+        assert_eq!(engine.expired_orders(100), vec!["g1".to_string()]);
+    }
+
+    #[test]
+    fn test_self_trade_prevention_modes() {
+        let own = |mode: &str, amount: f64| Order { stp_mode: mode.to_string(), ..limit("t1", "alice", "BUY", 11.0, amount) };
+        let mut engine = MatchingEngine::new(0.0);
+        engine.load(vec![
+            limit("a1", "alice", "SELL", 10.0, 1.0),
+            limit("b1", "bob", "SELL", 10.5, 1.0),
+        ]);
+
+        // Cancel newest: the incoming order stops before its own ask
+        let matched = engine.plan(own("CANCEL_NEWEST", 2.0)).unwrap();
+        assert!(matched.trades.is_empty() && matched.makers.is_empty());
+        assert_eq!(matched.order.status, "CANCELLED");
+        assert_eq!((matched.prevented.len(), matched.prevented[0].maker_order_id.as_str()), (1, "a1"));
+
+        // Cancel oldest: own ask is cancelled, matching goes on with bob
+        let matched = engine.plan(own("CANCEL_OLDEST", 2.0)).unwrap();
+        assert_eq!(matched.makers[0].status, "CANCELLED");
+        assert_eq!(matched.trades.len(), 1);
+        assert_eq!(matched.trades[0].maker_wallet_id, "bob");
+        assert_eq!((matched.order.filled, matched.order.status.as_str()), (1.0, "PARTIALLY_FILLED"));
+
+        // Cancel both
+        let matched = engine.plan(own("CANCEL_BOTH", 2.0)).unwrap();
+        assert_eq!((matched.order.status.as_str(), matched.makers[0].status.as_str()), ("CANCELLED", "CANCELLED"));
+
+        // Decrement: the overlap comes off both, the used up side is cancelled
+        let matched = engine.plan(own("DECREMENT_AND_CANCEL", 3.0)).unwrap();
+        assert_eq!(matched.makers[0].status, "CANCELLED");
+        assert_eq!((matched.order.amount, matched.order.filled), (2.0, 1.0));
+        let matched = engine.plan(own("DECREMENT_AND_CANCEL", 0.4)).unwrap();
+        assert_eq!(matched.order.status, "CANCELLED");
+        assert!((matched.makers[0].amount - 0.6).abs() < 1e-12);
+        assert_eq!(matched.makers[0].status, "PENDING");
+
+        engine.apply(&engine.plan(own("CANCEL_OLDEST", 0.5)).unwrap());
+        assert!(engine.order("a1").is_none());
+        assert_eq!(engine.order("b1").unwrap().filled, 0.5);
+    }
 }
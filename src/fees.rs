@@ -0,0 +1,116 @@
+/* ==============================================
+ * File:        src/fees.rs
+ * Author:      USDTG GROUP TECHNOLOGY LLC
+ * Developer:   Irfan Gedik
+ * Created Date: 2025-12-22
+ * Last Update:  2025-12-22
+ * Version:     1.0.0
+ *
+ * Description:
+ *   Gas Fees
+ *
+ *   Contract calls pay for metered gas in QVR. The caller's maximum fee
+ *   (gas limit x gas price) is reserved before execution; after the call
+ *   the consumed part is split between the treasury and stakers and the
+ *   rest is refunded.
+ *
+ * License:
+ *   MIT License
+ * ============================================== */
+
+use crate::config::Config;
+use serde::Serialize;
+
+/// Protocol account (in `contract_balances`) collecting the treasury share
+pub const TREASURY_ADDRESS: &str = "protocol_treasury";
+
+/// Gas pricing parameters
+#[derive(Debug, Clone, Copy)]
+pub struct GasSchedule {
+    pub min_gas_price: f64,  // QVR per gas unit
+    pub treasury_share: f64, // 0.0..=1.0 of each fee; the rest goes to stakers
+}
+
+impl GasSchedule {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            min_gas_price: config.min_gas_price.max(0.0),
+            treasury_share: config.gas_fee_treasury_share.clamp(0.0, 1.0),
+        }
+    }
+
+    /// Amount reserved from the caller before execution
+    pub fn max_fee(&self, gas_limit: u64, gas_price: f64) -> f64 {
+        gas_limit as f64 * gas_price
+    }
+
+    /// Splits the fee for `gas_used` and computes the refund of the reservation
+    pub fn settle(&self, gas_limit: u64, gas_price: f64, gas_used: u64) -> GasFee {
+        let gas_used = gas_used.min(gas_limit);
+        let reserved = self.max_fee(gas_limit, gas_price);
+        let fee = gas_used as f64 * gas_price;
+        let treasury = fee * self.treasury_share;
+
+        GasFee {
+            gas_limit,
+            gas_price,
+            gas_used,
+            reserved,
+            fee,
+            refund: reserved - fee,
+            treasury,
+            stakers: fee - treasury,
+        }
+    }
+}
+
+impl Default for GasSchedule {
+    fn default() -> Self {
+        Self { min_gas_price: 0.000_000_01, treasury_share: 0.5 }
+    }
+}
+
+/// Gas accounting of one call, stored with its transaction
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct GasFee {
+    pub gas_limit: u64,
+    pub gas_price: f64,
+    pub gas_used: u64,
+    pub reserved: f64, // QVR
+    pub fee: f64,
+    pub refund: f64,
+    pub treasury: f64,
+    pub stakers: f64,
+}
+
+impl GasFee {
+    /// Nobody to pay the staker share: it goes to the treasury
+    pub fn without_stakers(mut self) -> Self {
+        self.treasury += self.stakers;
+        self.stakers = 0.0;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settle_splits_fee_and_refunds_rest() {
+        let schedule = GasSchedule { min_gas_price: 0.0, treasury_share: 0.25 };
+        let fee = schedule.settle(1_000, 0.001, 400);
+        assert_eq!(fee.reserved, 1.0);
+        assert!((fee.fee - 0.4).abs() < 1e-12);
+        assert!((fee.refund - 0.6).abs() < 1e-12);
+        assert!((fee.treasury - 0.1).abs() < 1e-12);
+        assert!((fee.stakers - 0.3).abs() < 1e-12);
+
+        let fee = fee.without_stakers();
+        assert!((fee.treasury - 0.4).abs() < 1e-12);
+        assert_eq!(fee.stakers, 0.0);
+
+        // Usage is capped at the limit
+        assert_eq!(schedule.settle(10, 0.1, 50).refund, 0.0);
+    }
+}
@@ -0,0 +1,349 @@
+/* ==============================================
+ * File:        src/host.rs
+ * Author:      USDTG GROUP TECHNOLOGY LLC
+ * Developer:   Irfan Gedik
+ * Created Date: 2025-12-22
+ * Last Update:  2025-12-22
+ * Version:     1.0.0
+ *
+ * Description:
+ *   Q-VM Host Functions
+ *
+ *   The `qverse` import namespace: the only way a contract can reach
+ *   blockchain state. Each call gets its own `HostEnv`, which collects
+ *   the side effects of the call until the VM hands them back.
+ *
+ * License:
+ *   MIT License
+ * ============================================== */
+
+use crate::state::StateOverlay;
+use crate::vm::{ModuleCache, NestedOutput, QVM};
+use serde::Serialize;
+use std::sync::{Arc, Mutex, MutexGuard};
+use wasmer::{imports, Function, FunctionEnv, FunctionEnvMut, Imports, Instance, Memory, RuntimeError, Store};
+use wasmer_middlewares::metering::{get_remaining_points, set_remaining_points, MeteringPoints};
+
+/// Import module name for all host functions
+pub const HOST_MODULE: &str = "qverse";
+
+/// Every function a contract may import from `HOST_MODULE`
+pub const HOST_FUNCTIONS: &[&str] = &[
+    "emit_event", "storage_read", "storage_write", "balance", "transfer",
+    "call_contract", "caller", "origin", "block_height",
+];
+
+/// Topics are fixed 32-byte words
+pub const TOPIC_SIZE: usize = 32;
+pub const MAX_TOPICS: usize = 4;
+pub const MAX_EVENT_DATA: usize = 16 * 1024;
+pub const MAX_EVENTS_PER_CALL: usize = 256;
+pub const MAX_STORAGE_KEY: usize = 256;
+pub const MAX_STORAGE_VALUE: usize = 16 * 1024;
+pub const MAX_ADDRESS_LEN: usize = 128;
+pub const MAX_FUNCTION_NAME: usize = 128;
+pub const MAX_CALL_DATA: usize = 16 * 1024;
+pub const MAX_CALL_DEPTH: usize = 8;
+
+/// `call_contract` results below zero
+pub const CALL_REVERTED: i32 = -1;
+pub const CALL_REENTRANT: i32 = -2;
+
+/// Event emitted by a contract through `qverse.emit_event`
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct EmittedEvent {
+    pub contract: String,    // Emitting contract (differs from the callee for nested calls)
+    pub topics: Vec<String>, // hex, 32 bytes each
+    pub data: String,        // hex
+}
+
+/// Per-instance host state
+///
+/// Nested calls get their own `HostEnv` but share the parent's state
+/// overlay, so their writes are visible to the caller once they return.
+pub struct HostEnv {
+    pub memory: Option<Memory>,
+    pub instance: Option<Instance>,
+    pub modules: ModuleCache,   // Compiled code shared with nested calls
+    pub contract: String,       // Address of the executing contract
+    pub caller: Option<String>, // Wallet or contract that called it
+    pub origin: Option<String>, // Wallet that started the call chain
+    pub block_height: u64,      // Block the call executes in
+    pub call_stack: Vec<String>,
+    pub state: Arc<Mutex<StateOverlay>>,
+    pub events: Vec<EmittedEvent>,
+}
+
+impl HostEnv {
+    /// Top-level call, made by `caller` (a wallet address)
+    pub fn new(modules: ModuleCache, contract: String, caller: Option<String>, block_height: u64, state: StateOverlay) -> Self {
+        Self {
+            memory: None,
+            instance: None,
+            modules,
+            call_stack: vec![contract.clone()],
+            contract,
+            origin: caller.clone(),
+            caller,
+            block_height,
+            state: Arc::new(Mutex::new(state)),
+            events: Vec::new(),
+        }
+    }
+
+    /// Environment for a call from this contract into `contract`
+    fn nested(&self, contract: String) -> Self {
+        let mut call_stack = self.call_stack.clone();
+        call_stack.push(contract.clone());
+        Self {
+            memory: None,
+            instance: None,
+            modules: self.modules.clone(),
+            contract,
+            caller: Some(self.contract.clone()),
+            origin: self.origin.clone(),
+            block_height: self.block_height,
+            call_stack,
+            state: self.state.clone(),
+            events: Vec::new(),
+        }
+    }
+
+    pub fn state(&self) -> MutexGuard<'_, StateOverlay> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Builds the `qverse` import object for one instance
+pub fn imports(store: &mut Store, env: &FunctionEnv<HostEnv>) -> Imports {
+    imports! {
+        "qverse" => {
+            "emit_event" => Function::new_typed_with_env(store, env, emit_event),
+            "storage_read" => Function::new_typed_with_env(store, env, storage_read),
+            "storage_write" => Function::new_typed_with_env(store, env, storage_write),
+            "balance" => Function::new_typed_with_env(store, env, balance),
+            "transfer" => Function::new_typed_with_env(store, env, transfer),
+            "call_contract" => Function::new_typed_with_env(store, env, call_contract),
+            "caller" => Function::new_typed_with_env(store, env, caller),
+            "origin" => Function::new_typed_with_env(store, env, origin),
+            "block_height" => Function::new_typed_with_env(store, env, block_height),
+        }
+    }
+}
+
+/// emit_event(topics_ptr, topics_len, data_ptr, data_len)
+/// topics: 0-4 concatenated 32-byte topics; data: arbitrary bytes
+fn emit_event(
+    mut env: FunctionEnvMut<HostEnv>,
+    topics_ptr: i32,
+    topics_len: i32,
+    data_ptr: i32,
+    data_len: i32,
+) -> Result<(), RuntimeError> {
+    let topics = read_memory(&env, topics_ptr, topics_len, TOPIC_SIZE * MAX_TOPICS)?;
+    if topics.len() % TOPIC_SIZE != 0 {
+        return Err(RuntimeError::new("emit_event: topics must be 32-byte words"));
+    }
+    let data = read_memory(&env, data_ptr, data_len, MAX_EVENT_DATA)?;
+
+    let host = env.data_mut();
+    if host.events.len() >= MAX_EVENTS_PER_CALL {
+        return Err(RuntimeError::new(format!("emit_event: more than {} events in one call", MAX_EVENTS_PER_CALL)));
+    }
+    host.events.push(EmittedEvent {
+        contract: host.contract.clone(),
+        topics: topics.chunks(TOPIC_SIZE).map(hex::encode).collect(),
+        data: hex::encode(data),
+    });
+    Ok(())
+}
+
+/// storage_read(key_ptr, key_len, out_ptr, out_cap) -> value length, -1 if unset
+/// At most `out_cap` bytes are copied; a larger result means the buffer was too small.
+fn storage_read(
+    env: FunctionEnvMut<HostEnv>,
+    key_ptr: i32,
+    key_len: i32,
+    out_ptr: i32,
+    out_cap: i32,
+) -> Result<i32, RuntimeError> {
+    let key = read_memory(&env, key_ptr, key_len, MAX_STORAGE_KEY)?;
+    let host = env.data();
+    let value = match host.state().storage_get(&host.contract, &key).map_err(|e| RuntimeError::new(e.to_string()))? {
+        Some(v) => v,
+        None => return Ok(-1),
+    };
+
+    let n = value.len().min(out_cap.max(0) as usize);
+    write_memory(&env, out_ptr, &value[..n])?;
+    Ok(value.len() as i32)
+}
+
+/// storage_write(key_ptr, key_len, value_ptr, value_len)
+fn storage_write(
+    env: FunctionEnvMut<HostEnv>,
+    key_ptr: i32,
+    key_len: i32,
+    value_ptr: i32,
+    value_len: i32,
+) -> Result<(), RuntimeError> {
+    let key = read_memory(&env, key_ptr, key_len, MAX_STORAGE_KEY)?;
+    let value = read_memory(&env, value_ptr, value_len, MAX_STORAGE_VALUE)?;
+    let host = env.data();
+    host.state().storage_set(&host.contract, &key, value).map_err(|e| RuntimeError::new(e.to_string()))
+}
+
+/// balance(addr_ptr, addr_len) -> QVR balance in base units
+fn balance(env: FunctionEnvMut<HostEnv>, addr_ptr: i32, addr_len: i32) -> Result<i64, RuntimeError> {
+    let account = read_address(&env, addr_ptr, addr_len)?;
+    let units = env.data().state().balance(&account).map_err(|e| RuntimeError::new(e.to_string()))?;
+    Ok(units as i64)
+}
+
+/// transfer(to_ptr, to_len, amount) -> 0 on success, 1 if the contract's balance is too low
+/// Moves QVR (base units) from the executing contract to `to`.
+fn transfer(env: FunctionEnvMut<HostEnv>, to_ptr: i32, to_len: i32, amount: i64) -> Result<i32, RuntimeError> {
+    if amount < 0 {
+        return Err(RuntimeError::new("transfer: negative amount"));
+    }
+    let to = read_address(&env, to_ptr, to_len)?;
+    let host = env.data();
+    match host.state().transfer(&host.contract, &to, amount as u64) {
+        Ok(true) => Ok(0),
+        Ok(false) => Ok(1),
+        Err(e) => Err(RuntimeError::new(e.to_string())),
+    }
+}
+
+/// call_contract(addr_ptr, addr_len, fn_ptr, fn_len, input_ptr, input_len, gas, out_ptr, out_cap) -> i32
+///
+/// Calls `fn` on another deployed contract with at most `gas` of the
+/// caller's remaining gas. The callee export takes `()` or `(ptr, len)` input
+/// and returns nothing or packed `(ptr << 32) | len` output. Returns the
+/// output length (copied up to `out_cap`), `CALL_REVERTED` if the callee
+/// failed, or `CALL_REENTRANT` if its reentrancy guard refused the call.
+/// A failed callee's state changes and events are discarded; the caller's
+/// own changes before the call are kept.
+#[allow(clippy::too_many_arguments)]
+fn call_contract(
+    mut env: FunctionEnvMut<HostEnv>,
+    addr_ptr: i32,
+    addr_len: i32,
+    fn_ptr: i32,
+    fn_len: i32,
+    input_ptr: i32,
+    input_len: i32,
+    gas: i64,
+    out_ptr: i32,
+    out_cap: i32,
+) -> Result<i32, RuntimeError> {
+    let target = read_address(&env, addr_ptr, addr_len)?;
+    let function = String::from_utf8(read_memory(&env, fn_ptr, fn_len, MAX_FUNCTION_NAME)?)
+        .map_err(|_| RuntimeError::new("Function name is not valid UTF-8"))?;
+    let input = read_memory(&env, input_ptr, input_len, MAX_CALL_DATA)?;
+    if gas <= 0 {
+        return Err(RuntimeError::new("call_contract: gas must be positive"));
+    }
+
+    // Alt bütçe: istenen gaz, kalan gazı aşamaz
+    let instance = env.data().instance.clone()
+        .ok_or_else(|| RuntimeError::new("Host environment has no instance"))?;
+    let remaining = match get_remaining_points(&mut env, &instance) {
+        MeteringPoints::Remaining(left) => left,
+        MeteringPoints::Exhausted => return Err(RuntimeError::new("Out of gas")),
+    };
+    let budget = (gas as u64).min(remaining);
+
+    let host = env.data();
+    if host.call_stack.len() >= MAX_CALL_DEPTH {
+        return Ok(CALL_REVERTED);
+    }
+    let code = match host.state().code(&target).map_err(|e| RuntimeError::new(e.to_string()))? {
+        Some(code) => code,
+        None => return Ok(CALL_REVERTED),
+    };
+    if code.reentrancy_guard && host.call_stack.contains(&code.address) {
+        return Ok(CALL_REENTRANT);
+    }
+
+    let nested = host.nested(code.address.clone());
+    let modules = host.modules.clone();
+    let checkpoint = host.state().checkpoint();
+
+    let (result, gas_used) = QVM::call_nested(&modules, &code.wasm, &function, &input, nested, budget);
+    set_remaining_points(&mut env, &instance, remaining - gas_used.min(remaining));
+
+    match result {
+        Ok(NestedOutput { output, events }) => {
+            let host = env.data_mut();
+            if host.events.len() + events.len() > MAX_EVENTS_PER_CALL {
+                return Err(RuntimeError::new(format!("emit_event: more than {} events in one call", MAX_EVENTS_PER_CALL)));
+            }
+            host.events.extend(events);
+
+            let n = output.len().min(out_cap.max(0) as usize);
+            write_memory(&env, out_ptr, &output[..n])?;
+            Ok(output.len() as i32)
+        }
+        Err(e) => {
+            log::debug!("Nested call {}::{} reverted: {}", code.address, function, e);
+            env.data().state().rollback(checkpoint);
+            Ok(CALL_REVERTED)
+        }
+    }
+}
+
+/// caller(out_ptr, out_cap) -> length, -1 if there is none
+fn caller(env: FunctionEnvMut<HostEnv>, out_ptr: i32, out_cap: i32) -> Result<i32, RuntimeError> {
+    let caller = env.data().caller.clone();
+    write_optional(&env, caller, out_ptr, out_cap)
+}
+
+/// origin(out_ptr, out_cap) -> length, -1 if there is none
+fn origin(env: FunctionEnvMut<HostEnv>, out_ptr: i32, out_cap: i32) -> Result<i32, RuntimeError> {
+    let origin = env.data().origin.clone();
+    write_optional(&env, origin, out_ptr, out_cap)
+}
+
+/// block_height() -> height of the block the call executes in
+fn block_height(env: FunctionEnvMut<HostEnv>) -> i64 {
+    env.data().block_height as i64
+}
+
+fn write_optional(env: &FunctionEnvMut<HostEnv>, value: Option<String>, out_ptr: i32, out_cap: i32) -> Result<i32, RuntimeError> {
+    match value {
+        Some(v) => {
+            let n = v.len().min(out_cap.max(0) as usize);
+            write_memory(env, out_ptr, &v.as_bytes()[..n])?;
+            Ok(v.len() as i32)
+        }
+        None => Ok(-1),
+    }
+}
+
+fn read_address(env: &FunctionEnvMut<HostEnv>, ptr: i32, len: i32) -> Result<String, RuntimeError> {
+    let bytes = read_memory(env, ptr, len, MAX_ADDRESS_LEN)?;
+    String::from_utf8(bytes).map_err(|_| RuntimeError::new("Address is not valid UTF-8"))
+}
+
+/// Copies `len` bytes out of the contract's memory, refusing anything over `max`
+fn read_memory(env: &FunctionEnvMut<HostEnv>, ptr: i32, len: i32, max: usize) -> Result<Vec<u8>, RuntimeError> {
+    if len < 0 || len as usize > max {
+        return Err(RuntimeError::new(format!("Invalid region length {} (max {})", len, max)));
+    }
+    let memory = env.data().memory.as_ref()
+        .ok_or_else(|| RuntimeError::new("Contract does not export 'memory'"))?;
+
+    let mut buf = vec![0u8; len as usize];
+    memory.view(env).read(ptr as u32 as u64, &mut buf)
+        .map_err(|e| RuntimeError::new(e.to_string()))?;
+    Ok(buf)
+}
+
+fn write_memory(env: &FunctionEnvMut<HostEnv>, ptr: i32, bytes: &[u8]) -> Result<(), RuntimeError> {
+    let memory = env.data().memory.as_ref()
+        .ok_or_else(|| RuntimeError::new("Contract does not export 'memory'"))?;
+
+    memory.view(env).write(ptr as u32 as u64, bytes)
+        .map_err(|e| RuntimeError::new(e.to_string()))
+}
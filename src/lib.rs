@@ -16,19 +16,27 @@
  *   MIT License
  * ============================================== */
 
+pub mod abi;
+pub mod analysis;
 pub mod api;
 pub mod contracts;
 pub mod crypto;
 pub mod db;
+pub mod fees;
+pub mod host;
 pub mod models;
+pub mod state;
 // pub mod network; // Temporarily disabled due to libp2p compatibility issues 
 pub mod vm;      
+pub mod wasm_validator;
 pub mod compliance;
 pub mod ai; // Yeni: Yapay Zeka
 pub mod qrc20; // Yeni: Token Standardı
 pub mod exchange;
 pub mod wallet;
 pub mod developer;
+pub mod sdk;
+pub mod testkit;
 pub mod mobile;
 pub mod config;
 pub mod validation;
@@ -54,11 +62,17 @@ pub use middleware::{RateLimiter, RequestIdMiddleware, SecurityHeadersMiddleware
 // Shared State - moved from main.rs to lib.rs so api.rs can access it
 pub struct AppState {
     pub db: Database,
-    pub vm: Arc<Mutex<QVM>>,
+    pub vm: QVM,
     pub ai: Arc<Mutex<QMind>>,
     pub network_tx: mpsc::Sender<String>, 
     pub connected_peers: Arc<Mutex<Vec<String>>>,
     pub cache: CacheManager,
     pub metrics: Metrics,
     pub rate_limiter: RateLimiter,
+    pub contracts: Arc<tokio::sync::Mutex<contracts::ContractRegistry>>, // Native (protocol) contracts
+    pub gas: fees::GasSchedule, // Gas pricing for contract calls
+    pub exchange: Arc<tokio::sync::Mutex<exchange::MatchingEngine>>, // Live order books
+    pub max_swap_hops: usize, // Routing limit for AMM swaps
+    pub voting_period_secs: u64, // Voting window of governance proposals
+    pub oracle_keys: std::collections::HashMap<String, String>, // Authorized price feed sources
 }
@@ -19,9 +19,13 @@
 use actix_web::{web, App, HttpServer, middleware::Logger, middleware::Compress};
 use actix_cors::Cors;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::sync::mpsc;
 // use q_verse_core::network::P2PNode; // Temporarily disabled
 use q_verse_core::{Database, QVM, QMind, CacheManager, Metrics, RateLimiter, RequestIdMiddleware, SecurityHeadersMiddleware, AppState, config};
+use q_verse_core::contracts::ContractRegistry;
+use q_verse_core::fees::GasSchedule;
+use q_verse_core::exchange::MatchingEngine;
 
 #[tokio::main]
 async fn main() -> std::io::Result<()> {
@@ -34,6 +38,9 @@ async fn main() -> std::io::Result<()> {
     
     log::info!("🌌 Q-Verse Core: Quantum-Safe Hybrid Finance Network");
     log::info!("---------------------------------------------------");
+    if config.oracle_keys.is_empty() {
+        log::warn!("⚠️  ORACLE_KEYS is not set: every POST /api/oracle/update will be rejected with 401");
+    }
     
     // 1. Initialize Database
     log::info!("🔌 Connecting to Ledger...");
@@ -43,13 +50,7 @@ async fn main() -> std::io::Result<()> {
     db.init_schema().await.expect("Failed to init schema");
     log::info!("✅ Database connected and schema initialized");
 
-    // 2. Initialize Engines
-    log::info!("🧠 Initializing Q-VM & Q-Mind AI...");
-    let vm = Arc::new(Mutex::new(QVM::new()));
-    let ai = Arc::new(Mutex::new(QMind::new()));
-    log::info!("✅ Q-VM and Q-Mind initialized");
-
-    // 2.5. Initialize Cache & Metrics
+    // 2. Initialize Cache & Metrics
     log::info!("📊 Initializing Cache & Metrics...");
     let cache = CacheManager::new();
     let metrics = Metrics::new();
@@ -58,6 +59,30 @@ async fn main() -> std::io::Result<()> {
     let rate_limiter = RateLimiter::new(5000, 60); // 5000 requests per minute per IP
     log::info!("✅ Cache & Metrics initialized");
 
+    // 2.5. Initialize Engines
+    log::info!("🧠 Initializing Q-VM & Q-Mind AI...");
+    let vm = QVM::new(
+        config.vm_workers,
+        Duration::from_millis(config.contract_timeout_ms),
+        config.contract_gas_limit,
+        metrics.clone(),
+    );
+    let ai = Arc::new(Mutex::new(QMind::new()));
+    log::info!("✅ Q-VM ({} workers) and Q-Mind initialized", config.vm_workers);
+
+    let mut registry = ContractRegistry::with_defaults(&config);
+    registry.load(&db).await.expect("Failed to load native contract state");
+    let contracts = Arc::new(tokio::sync::Mutex::new(registry));
+    log::info!("✅ Native contracts loaded");
+
+    let mut engine = MatchingEngine::new(config.trading_fee_rate);
+    engine.load(db.get_open_orders().await.expect("Failed to load open orders"));
+    for (pair, price) in db.get_last_trade_prices().await.expect("Failed to load last trade prices") {
+        engine.set_last_price(&pair, price);
+    }
+    let exchange = Arc::new(tokio::sync::Mutex::new(engine));
+    log::info!("✅ Order books loaded");
+
     // 3. Start P2P Network (Temporarily disabled)
     // log::info!("🌐 Bootstrapping P2P Network...");
     // let mut p2p_node = P2PNode::new(config.node_id.as_deref()).await
@@ -84,6 +109,12 @@ async fn main() -> std::io::Result<()> {
         cache: cache.clone(),
         metrics: metrics.clone(),
         rate_limiter: rate_limiter.clone(),
+        contracts: contracts.clone(),
+        gas: GasSchedule::from_config(&config),
+        exchange: exchange.clone(),
+        max_swap_hops: config.max_swap_hops.max(1),
+        voting_period_secs: config.voting_period_secs,
+        oracle_keys: config.oracle_keys.clone(),
     });
 
     // Start cache cleanup task
@@ -98,6 +129,40 @@ async fn main() -> std::io::Result<()> {
         }
     });
 
+    // Staking: settle reward epochs and release matured unstakes
+    let staking_db = db.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            let now = chrono::Utc::now().timestamp();
+            match contracts.lock().await.advance_epoch(&staking_db, now).await {
+                Ok(0) => {}
+                Ok(epochs) => log::info!("💰 Settled {} staking epoch(s)", epochs),
+                Err(e) => log::error!("Staking epoch settlement failed: {}", e),
+            }
+            if let Err(e) = staking_db.release_unlocked_stakes(now).await {
+                log::error!("Unstake release failed: {}", e);
+            }
+        }
+    });
+
+    // Exchange: expire GTD orders
+    let expiry_db = db.clone();
+    let expiry_exchange = exchange.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(5));
+        loop {
+            interval.tick().await;
+            let now = chrono::Utc::now().timestamp();
+            match expiry_exchange.lock().await.expire_orders(&expiry_db, now).await {
+                Ok(0) => {}
+                Ok(expired) => log::info!("⌛ Expired {} GTD order(s)", expired),
+                Err(e) => log::error!("Order expiry failed: {}", e),
+            }
+        }
+    });
+
     // 4. Start API Server
     let bind_addr = config.bind_address();
     log::info!("🚀 Starting API Server at http://{}", bind_addr);
@@ -30,6 +30,8 @@ pub struct Metrics {
     pub total_swaps: Arc<AtomicU64>,
     pub total_bridge_transactions: Arc<AtomicU64>,
     pub active_connections: Arc<AtomicU64>,
+    pub contract_queue_depth: Arc<AtomicU64>,
+    pub active_contract_calls: Arc<AtomicU64>,
     pub response_times: Arc<RwLock<Vec<u64>>>, // milliseconds
 }
 
@@ -63,7 +65,7 @@ impl Metrics {
     }
 
     pub fn record_response_time(&self, ms: u64) {
-        let mut times = self.response_times.try_write();
+        let times = self.response_times.try_write();
         if let Ok(mut t) = times {
             t.push(ms);
             // Keep only last 1000 response times
@@ -89,6 +91,8 @@ impl Metrics {
             total_swaps: self.total_swaps.load(Ordering::Relaxed),
             total_bridge_transactions: self.total_bridge_transactions.load(Ordering::Relaxed),
             active_connections: self.active_connections.load(Ordering::Relaxed),
+            contract_queue_depth: self.contract_queue_depth.load(Ordering::Relaxed),
+            active_contract_calls: self.active_contract_calls.load(Ordering::Relaxed),
             avg_response_time_ms: avg_response_time,
             success_rate: if self.total_requests.load(Ordering::Relaxed) > 0 {
                 (self.successful_requests.load(Ordering::Relaxed) as f64 
@@ -109,6 +113,8 @@ pub struct MetricsStats {
     pub total_swaps: u64,
     pub total_bridge_transactions: u64,
     pub active_connections: u64,
+    pub contract_queue_depth: u64,
+    pub active_contract_calls: u64,
     pub avg_response_time_ms: f64,
     pub success_rate: f64,
 }
@@ -16,9 +16,8 @@
  *   MIT License
  * ============================================== */
 
-use actix_web::{dev::ServiceRequest, Error, HttpMessage, HttpRequest};
+use actix_web::{dev::ServiceRequest, Error, HttpMessage};
 use actix_web::dev::{Service, Transform};
-use actix_web::web::Data;
 use futures::future::{ready, Ready};
 use futures::Future;
 use std::cell::RefCell;
@@ -129,7 +128,7 @@ where
     type Error = Error;
     type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
 
-    fn poll_ready(&self, ctx: &mut std::task::Context<'_>) -> std::task::Poll<Result<(), Self::Error>> {
+    fn poll_ready(&self, _ctx: &mut std::task::Context<'_>) -> std::task::Poll<Result<(), Self::Error>> {
         // We can't directly poll the inner service because of RefCell borrowing
         // This is a limitation of the current design, but it should work for most cases
         std::task::Poll::Ready(Ok(()))
@@ -144,7 +143,7 @@ where
         let service = self.service.clone();
         Box::pin(async move {
             let fut = {
-                let mut service_ref = service.borrow_mut();
+                let service_ref = service.borrow_mut();
                 let fut = service_ref.call(req);
                 drop(service_ref);
                 fut
@@ -190,7 +189,7 @@ where
     type Error = Error;
     type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
 
-    fn poll_ready(&self, ctx: &mut std::task::Context<'_>) -> std::task::Poll<Result<(), Self::Error>> {
+    fn poll_ready(&self, _ctx: &mut std::task::Context<'_>) -> std::task::Poll<Result<(), Self::Error>> {
         // We can't directly poll the inner service because of RefCell borrowing
         // This is a limitation of the current design, but it should work for most cases
         std::task::Poll::Ready(Ok(()))
@@ -200,7 +199,7 @@ where
         let service = self.service.clone();
         Box::pin(async move {
             let fut = {
-                let mut service_ref = service.borrow_mut();
+                let service_ref = service.borrow_mut();
                 let fut = service_ref.call(req);
                 drop(service_ref);
                 fut
@@ -149,7 +149,7 @@ impl BiometricAuthManager {
     pub fn verify_biometric(
         challenge: &str,
         signature: &str,
-        public_key: &str,
+        _public_key: &str,
     ) -> Result<bool, Box<dyn Error>> {
         // In production, use actual biometric verification
         // For now, simple validation
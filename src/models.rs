@@ -143,15 +143,15 @@ impl TokenSymbol {
 }
 
 
-impl ToString for TokenSymbol {
-    fn to_string(&self) -> String {
-        match self {
-            TokenSymbol::QVR => "QVR".to_string(),
-            TokenSymbol::RGLS => "RGLS".to_string(),
-            TokenSymbol::POPEO => "POPEO".to_string(),
-            TokenSymbol::QVRg => "QVRg".to_string(),
-            TokenSymbol::QVRt => "QVRt".to_string(),
-        }
+impl std::fmt::Display for TokenSymbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            TokenSymbol::QVR => "QVR",
+            TokenSymbol::RGLS => "RGLS",
+            TokenSymbol::POPEO => "POPEO",
+            TokenSymbol::QVRg => "QVRg",
+            TokenSymbol::QVRt => "QVRt",
+        })
     }
 }
 
@@ -177,6 +177,10 @@ pub struct Wallet {
     pub spend_public_key: String, // verification key for spending
     pub view_public_key: String,  // key for scanning blockchain
     pub audit_public_key: Option<String>, // optional key for auditors
+
+    // "salt$digest" of the spend secret, never exposed
+    #[serde(skip)]
+    pub spend_key_hash: Option<String>,
     
     pub created_at: DateTime<Utc>,
 }
@@ -184,17 +188,22 @@ pub struct Wallet {
 impl<'r> sqlx::FromRow<'r, sqlx::sqlite::SqliteRow> for Wallet {
     fn from_row(row: &'r sqlx::sqlite::SqliteRow) -> Result<Self, sqlx::Error> {
         use sqlx::Row;
+        // IDs are stored as text, not as the 16-byte blobs sqlx decodes `Uuid` from
+        let uuid = |column: &str| -> Result<Uuid, sqlx::Error> {
+            let text: String = row.try_get(column)?;
+            Uuid::parse_str(&text).map_err(|e| sqlx::Error::ColumnDecode { index: column.to_string(), source: Box::new(e) })
+        };
         Ok(Wallet {
-            id: row.get("id"),
-            user_id: row.get("user_id"),
+            id: uuid("id")?,
+            user_id: uuid("user_id")?,
             address: row.get("address"),
             spend_public_key: row.get("public_key"), // Map old column
             view_public_key: row.try_get("view_public_key").unwrap_or_else(|_| "legacy_view_key".to_string()),
             audit_public_key: row.try_get("audit_public_key").ok(),
-            created_at: {
-                let timestamp: i64 = row.get("created_at");
-                DateTime::from_timestamp(timestamp, 0).unwrap_or_else(|| Utc::now())
-            },
+            spend_key_hash: row.try_get("spend_key_hash").ok().flatten(),
+            created_at: row.try_get::<chrono::NaiveDateTime, _>("created_at")
+                .map(|t| t.and_utc())
+                .unwrap_or_else(|_| Utc::now()),
         })
     }
 }
@@ -203,12 +212,12 @@ impl Wallet {
     /// Creates a new Quantum-Secure Wallet with Spend/View Keys
     pub fn new(user_id: Uuid) -> (Self, String, String) {
         // In a real implementation, we use curve25519-dalek to generate keys
-        // Here we simulate it for structure compliance
-        let spend_sk = Uuid::new_v4().to_string().replace("-", "");
-        let spend_pk = format!("spk_{}", &spend_sk[0..16]);
+        // Here we simulate it; public keys are one-way digests so the address never reveals a secret
+        let spend_sk = random_key();
+        let spend_pk = format!("spk_{}", &key_digest("spk", &spend_sk)[0..16]);
         
-        let view_sk = Uuid::new_v4().to_string().replace("-", "");
-        let view_pk = format!("vpk_{}", &view_sk[0..16]);
+        let view_sk = random_key();
+        let view_pk = format!("vpk_{}", &key_digest("vpk", &view_sk)[0..16]);
         
         // Unified address (mock derivation)
         let address = format!("qvr{}{}", &spend_pk[4..], &view_pk[4..]);
@@ -220,12 +229,65 @@ impl Wallet {
             spend_public_key: spend_pk,
             view_public_key: view_pk,
             audit_public_key: None,
+            spend_key_hash: Some(spend_key_hash(&spend_sk)),
             created_at: Utc::now(),
         };
         
         // Return wallet + private keys (spend, view)
         (wallet, spend_sk, view_sk)
     }
+
+    /// Whether `secret_key` is the spend key this wallet was created with.
+    /// Wallets created before spend key hashes were stored cannot authorize spends.
+    pub fn verify_spend_key(&self, secret_key: &str) -> bool {
+        let Some((salt, hash)) = self.spend_key_hash.as_deref().and_then(|h| h.split_once('$')) else {
+            return false;
+        };
+        // Constant-time comparison so response timing does not leak the digest
+        crate::crypto::constant_time_eq(key_digest(salt, secret_key).as_bytes(), hash.as_bytes())
+    }
+
+    /// Whether `secret_key` is the spend key of a wallet created before spend key hashes were stored.
+    /// Those secrets were 32 hex characters whose first half is the public key suffix.
+    pub fn verify_legacy_spend_key(&self, secret_key: &str) -> bool {
+        self.spend_key_hash.is_none()
+            && secret_key.len() == 32
+            && secret_key.bytes().all(|b| b.is_ascii_hexdigit())
+            && secret_key.get(0..16).is_some_and(|prefix| self.spend_public_key == format!("spk_{}", prefix))
+    }
+
+    /// Replaces the spend key and returns the new secret. Address and public keys stay the same.
+    pub fn rekey(&mut self) -> String {
+        let spend_sk = random_key();
+        self.spend_key_hash = Some(spend_key_hash(&spend_sk));
+        spend_sk
+    }
+
+    /// Signature over the terms of one operation, keyed by the spend secret and this wallet's salt.
+    /// Only meaningful once `secret_key` has passed `verify_spend_key`.
+    pub fn sign_spend(&self, secret_key: &str, terms: &str) -> String {
+        let salt = self.spend_key_hash.as_deref()
+            .and_then(|h| h.split_once('$'))
+            .map_or("", |(salt, _)| salt);
+        key_digest(salt, &format!("{}:{}", secret_key, terms))
+    }
+}
+
+/// 32 random bytes, hex encoded
+fn random_key() -> String {
+    hex::encode(rand::random::<[u8; 32]>())
+}
+
+/// "salt$digest" of a spend secret, with a fresh salt
+fn spend_key_hash(spend_sk: &str) -> String {
+    let salt = random_key()[0..32].to_string();
+    format!("{}${}", salt, key_digest(&salt, spend_sk))
+}
+
+/// Hex SHA-256 of `"{salt}:{value}"`
+fn key_digest(salt: &str, value: &str) -> String {
+    use sha2::{Digest, Sha256};
+    hex::encode(Sha256::digest(format!("{}:{}", salt, value)))
 }
 
 // 💰 Balance Model
@@ -327,7 +389,7 @@ pub struct Transaction {
 // In a real scenario, this would involve complex proof generation
 impl Transaction {
     pub fn new_public(
-        from_wallet_id: &str,
+        _from_wallet_id: &str,
         to_address: &str,
         token: TokenSymbol,
         amount: f64,
@@ -356,6 +418,15 @@ impl Transaction {
             status: "PENDING".to_string(),
         }
     }
+
+    /// Amount of the first output when its commitment is public ("PUBLIC_{amount}")
+    pub fn public_amount(&self) -> Option<f64> {
+        self.outputs.first()?
+            .commitment
+            .strip_prefix("PUBLIC_")?
+            .parse()
+            .ok()
+    }
 }
 
 
@@ -387,6 +458,42 @@ pub struct LiquidityPool {
     pub reserve_b: f64,
     pub total_supply: f64,
     pub fee_rate: f64,
+    pub fees_a: f64, // Swap fees accrued to LPs (kept in the reserves)
+    pub fees_b: f64,
+    pub curve_type: String, // CurveType
+    pub amplification: f64, // StableSwap A; unused by constant product pools
+}
+
+/// Pricing curve of a liquidity pool
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CurveType {
+    #[default]
+    ConstantProduct, // x * y = k
+    StableSwap,      // Curve invariant for pegged pairs
+}
+
+impl CurveType {
+    pub fn parse(curve: &str) -> Option<Self> {
+        match curve.to_uppercase().as_str() {
+            "CONSTANT_PRODUCT" => Some(Self::ConstantProduct),
+            "STABLE_SWAP" => Some(Self::StableSwap),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::ConstantProduct => "CONSTANT_PRODUCT",
+            Self::StableSwap => "STABLE_SWAP",
+        }
+    }
+}
+
+impl LiquidityPool {
+    /// Balance symbol of the pool's LP shares
+    pub fn lp_token(&self) -> String {
+        format!("LP-{}", self.id)
+    }
 }
 
 impl<'r> sqlx::FromRow<'r, sqlx::sqlite::SqliteRow> for LiquidityPool {
@@ -400,16 +507,37 @@ impl<'r> sqlx::FromRow<'r, sqlx::sqlite::SqliteRow> for LiquidityPool {
             reserve_b: row.get("reserve_b"),
             total_supply: row.get("total_supply"),
             fee_rate: row.get("fee_rate"),
+            fees_a: row.get("fees_a"),
+            fees_b: row.get("fees_b"),
+            curve_type: row.get("curve_type"),
+            amplification: row.get("amplification"),
         })
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
 pub enum OrderSide {
     Buy,
     Sell,
 }
 
+impl OrderSide {
+    pub fn parse(side: &str) -> Option<Self> {
+        match side.to_uppercase().as_str() {
+            "BUY" => Some(Self::Buy),
+            "SELL" => Some(Self::Sell),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Buy => "BUY",
+            Self::Sell => "SELL",
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum OrderType {
     Market,
@@ -417,7 +545,68 @@ pub enum OrderType {
     StopLoss,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// How long an order may stay in the book
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum TimeInForce {
+    Gtc, // Good till cancelled
+    Ioc, // Immediate or cancel: the unfilled rest is cancelled
+    Fok, // Fill or kill: fills completely at once or not at all
+    Gtd, // Good till date: expires at `expires_at`
+}
+
+impl TimeInForce {
+    pub fn parse(tif: &str) -> Option<Self> {
+        match tif.to_uppercase().as_str() {
+            "GTC" => Some(Self::Gtc),
+            "IOC" => Some(Self::Ioc),
+            "FOK" => Some(Self::Fok),
+            "GTD" => Some(Self::Gtd),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Gtc => "GTC",
+            Self::Ioc => "IOC",
+            Self::Fok => "FOK",
+            Self::Gtd => "GTD",
+        }
+    }
+}
+
+/// What the matcher does when an order would trade with a resting order of the same wallet
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SelfTradeMode {
+    #[default]
+    CancelNewest,       // Cancel the incoming order's remainder
+    CancelOldest,       // Cancel the resting order, keep matching
+    CancelBoth,
+    DecrementAndCancel, // Reduce both by the overlap; cancel whichever is used up
+}
+
+impl SelfTradeMode {
+    pub fn parse(mode: &str) -> Option<Self> {
+        match mode.to_uppercase().as_str() {
+            "CANCEL_NEWEST" => Some(Self::CancelNewest),
+            "CANCEL_OLDEST" => Some(Self::CancelOldest),
+            "CANCEL_BOTH" => Some(Self::CancelBoth),
+            "DECREMENT_AND_CANCEL" => Some(Self::DecrementAndCancel),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::CancelNewest => "CANCEL_NEWEST",
+            Self::CancelOldest => "CANCEL_OLDEST",
+            Self::CancelBoth => "CANCEL_BOTH",
+            Self::DecrementAndCancel => "DECREMENT_AND_CANCEL",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
 pub enum OrderStatus {
     Pending,
     Filled,
@@ -426,6 +615,19 @@ pub enum OrderStatus {
     Expired,
 }
 
+impl OrderStatus {
+    /// Value stored in `orders.status`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "PENDING",
+            Self::Filled => "FILLED",
+            Self::PartiallyFilled => "PARTIALLY_FILLED",
+            Self::Cancelled => "CANCELLED",
+            Self::Expired => "EXPIRED",
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, FromRow, utoipa::ToSchema)]
 pub struct Order {
     pub id: String,
@@ -437,12 +639,20 @@ pub struct Order {
     pub amount: f64,
     pub filled: f64,
     pub status: String,
+    pub held: f64, // Funds still reserved for the unfilled part
+    pub stop_price: Option<f64>,       // Stop orders: trigger price
+    pub trigger_state: Option<String>, // Stop orders: WAITING or TRIGGERED
+    pub time_in_force: String,         // GTC, IOC, FOK or GTD
+    pub expires_at: Option<i64>,       // GTD: unix seconds
+    pub post_only: bool,               // Maker only: rejected if it would trade on entry
+    pub stp_mode: String,              // Self-trade prevention, from the wallet's setting
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Trade {
     pub id: String,
-    pub order_id: String,
+    pub order_id: String,       // Taker (incoming) order
+    pub maker_order_id: String, // Resting order it matched
     pub pair: String,
     pub price: f64,
     pub amount: f64,
@@ -452,6 +662,19 @@ pub struct Trade {
     pub fee: f64,
 }
 
+/// A match the self-trade prevention stopped, reported to the wallet
+#[derive(Debug, Serialize, Deserialize, Clone, FromRow)]
+pub struct SelfTradePrevention {
+    pub id: String,
+    pub wallet_id: String,
+    pub pair: String,
+    pub taker_order_id: String,
+    pub maker_order_id: String,
+    pub mode: String,
+    pub amount: f64, // Quantity that would have traded
+    pub price: f64,  // Resting order's price
+}
+
 // 🌉 BRIDGE MODELS
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -542,6 +765,9 @@ pub struct Proposal {
     pub votes_against: f64,
     pub voting_power_for: f64,
     pub voting_power_against: f64,
+    // Contract upgrade this proposal authorizes, if any
+    pub upgrade_contract_address: Option<String>,
+    pub upgrade_compiled_contract_id: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -595,6 +821,7 @@ pub struct CompiledContract {
     pub compiler_version: Option<String>,
     pub compiled_by: Option<String>,
     pub gas_estimate: Option<i64>,
+    pub abi_json: Option<String>, // Contents of the `qverse.abi` custom section
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, sqlx::FromRow)]
@@ -605,6 +832,80 @@ pub struct DeployedContract {
     pub deployer_wallet_id: String,
     pub address: String,
     pub deployment_tx_id: Option<String>,
+    pub upgradeable: bool,
+    pub upgrade_authority_type: Option<String>, // WALLET | MULTISIG | GOVERNANCE
+    pub upgrade_authority_id: Option<String>,
+    pub current_version: i64,
+    pub reentrancy_guard: bool, // Reject nested calls into this contract while it is executing
+}
+
+/// Who may upgrade an upgradeable deployment
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UpgradeAuthorityKind {
+    Wallet,     // upgrade_authority_id: wallet ID
+    Multisig,   // upgrade_authority_id: multisig wallet ID
+    Governance, // any passed governance proposal
+}
+
+impl UpgradeAuthorityKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Wallet => "WALLET",
+            Self::Multisig => "MULTISIG",
+            Self::Governance => "GOVERNANCE",
+        }
+    }
+}
+
+/// Proof of approval presented with an upgrade
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpgradeApproval {
+    Wallet(String),     // caller wallet ID
+    Multisig(String),   // approved multisig transaction ID
+    Governance(String), // passed proposal ID
+}
+
+/// One code version behind a contract address
+#[derive(Debug, Serialize, Deserialize, Clone, sqlx::FromRow)]
+pub struct ContractVersion {
+    pub contract_address: String,
+    pub version: i64,
+    pub compiled_contract_id: String,
+    pub approved_by: String,
+    pub migration_tx_id: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+/// Persisted contract event (see `contract_events`)
+#[derive(Debug, Serialize, Deserialize, Clone, utoipa::ToSchema)]
+pub struct ContractEventLog {
+    pub id: String,
+    pub transaction_id: String,
+    pub block_number: i64,
+    pub contract_address: String,
+    pub log_index: i64,
+    pub topics: Vec<String>,
+    pub data: String,
+}
+
+impl<'r> sqlx::FromRow<'r, sqlx::sqlite::SqliteRow> for ContractEventLog {
+    fn from_row(row: &'r sqlx::sqlite::SqliteRow) -> Result<Self, sqlx::Error> {
+        use sqlx::Row;
+        let topics = ["topic0", "topic1", "topic2", "topic3"]
+            .iter()
+            .filter_map(|col| row.try_get::<Option<String>, _>(*col).ok().flatten())
+            .collect();
+        Ok(ContractEventLog {
+            id: row.get("id"),
+            transaction_id: row.get("transaction_id"),
+            block_number: row.get("block_number"),
+            contract_address: row.get("contract_address"),
+            log_index: row.get("log_index"),
+            topics,
+            data: row.get("data"),
+        })
+    }
 }
 
 // 🏢 ENTERPRISE MODELS
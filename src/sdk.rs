@@ -0,0 +1,893 @@
+/* ==============================================
+ * File:        src/sdk.rs
+ * Author:      USDTG GROUP TECHNOLOGY LLC
+ * Developer:   Irfan Gedik
+ * Created Date: 2025-12-22
+ * Last Update:  2025-12-22
+ * Version:     1.0.0
+ *
+ * Description:
+ *   ABI-driven SDK Generator
+ *
+ *   Emits a typed client per deployed contract in TypeScript, Rust or
+ *   Python: one method per ABI function with typed parameters and
+ *   returns, event types with decoders (see the event encoding in
+ *   `abi.rs`), and a base URL that can be changed at runtime.
+ *
+ * License:
+ *   MIT License
+ * ============================================== */
+
+use crate::abi::{self, AbiEvent, AbiEventField, AbiType, ContractAbi};
+use crate::models::DeployedContract;
+use std::error::Error;
+use std::fmt::Write;
+
+pub const DEFAULT_SDK_BASE_URL: &str = "http://localhost:8080";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SdkLanguage {
+    TypeScript,
+    Rust,
+    Python,
+}
+
+impl SdkLanguage {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            // js: TypeScript çıktısı, eski istemcilerle uyum için
+            "ts" | "typescript" | "js" | "javascript" => Some(Self::TypeScript),
+            "rust" | "rs" => Some(Self::Rust),
+            "python" | "py" => Some(Self::Python),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::TypeScript => "typescript",
+            Self::Rust => "rust",
+            Self::Python => "python",
+        }
+    }
+}
+
+/// SDK Generator
+pub struct SDKGenerator;
+
+impl SDKGenerator {
+    /// Typed client for `contract` from its ABI; `base_url` is the generated default
+    pub fn generate(
+        contract: &DeployedContract,
+        contract_name: &str,
+        abi: &ContractAbi,
+        language: SdkLanguage,
+        base_url: &str,
+    ) -> Result<String, Box<dyn Error>> {
+        Self::check_inputs(abi, base_url)?;
+        let client = format!("{}Client", pascal_case(contract_name));
+
+        let code = match language {
+            SdkLanguage::TypeScript => Self::generate_ts_sdk(contract, &client, abi, base_url),
+            SdkLanguage::Rust => Self::generate_rust_sdk(contract, &client, abi, base_url),
+            SdkLanguage::Python => Self::generate_python_sdk(contract, &client, abi, base_url),
+        }?;
+        Ok(code)
+    }
+
+    /// Names end up as identifiers and string literals in generated code
+    fn check_inputs(abi: &ContractAbi, base_url: &str) -> Result<(), Box<dyn Error>> {
+        let url_ok = (base_url.starts_with("http://") || base_url.starts_with("https://"))
+            && !base_url.chars().any(|c| c.is_whitespace() || c.is_control() || matches!(c, '"' | '\'' | '\\' | '`' | '$' | '{' | '}'));
+        if !url_ok {
+            return Err(format!("Invalid base URL: {}", base_url).into());
+        }
+
+        let names = abi.functions.iter().map(|f| &f.name)
+            .chain(abi.functions.iter().flat_map(|f| f.params.iter().map(|p| &p.name)))
+            .chain(abi.events.iter().map(|e| &e.name))
+            .chain(abi.events.iter().flat_map(|e| e.fields.iter().map(|f| &f.name)));
+        for name in names {
+            let mut chars = name.chars();
+            let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+                && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if !valid {
+                return Err(format!("ABI name '{}' is not a valid identifier", name).into());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn generate_ts_sdk(
+        contract: &DeployedContract,
+        client: &str,
+        abi: &ContractAbi,
+        base_url: &str,
+    ) -> Result<String, std::fmt::Error> {
+        let mut out = String::new();
+        writeln!(out, "// Q-Verse TypeScript SDK - Auto-generated from the contract ABI")?;
+        writeln!(out, "export const DEFAULT_BASE_URL = \"{}\";", base_url)?;
+        writeln!(out, "export const CONTRACT_ADDRESS = \"{}\";", contract.address)?;
+        writeln!(out)?;
+        writeln!(out, "export interface EventLog {{ topics: string[]; data: string; }}")?;
+        writeln!(out, "export interface CallResult {{ results: unknown[]; events: EventLog[]; gas_used: number; transaction_id?: string; }}")?;
+        writeln!(out)?;
+        out.push_str(TS_HELPERS);
+
+        for event in &abi.events {
+            writeln!(out)?;
+            writeln!(out, "export interface {}Event {{", pascal_case(&event.name))?;
+            for field in &event.fields {
+                writeln!(out, "  {}: {};", field.name, ts_type(field.ty))?;
+            }
+            writeln!(out, "}}")?;
+            writeln!(out)?;
+            writeln!(out, "export function decode{}Event(log: EventLog): {}Event {{", pascal_case(&event.name), pascal_case(&event.name))?;
+            writeln!(out, "  const data = new DataReader(log.data);")?;
+            writeln!(out, "  return {{")?;
+            for (field, source) in field_sources(event) {
+                let expr = match source {
+                    FieldSource::Topic(i) => match field.ty {
+                        AbiType::I32 | AbiType::I64 => format!("topicInt(log, {})", i),
+                        AbiType::F32 | AbiType::F64 => format!("topicFloat(log, {})", i),
+                        AbiType::String | AbiType::Bytes => format!("log.topics[{}]", i),
+                    },
+                    FieldSource::Data => format!("data.{}()", reader_method(field.ty)),
+                };
+                writeln!(out, "    {}: {},", field.name, expr)?;
+            }
+            writeln!(out, "  }};")?;
+            writeln!(out, "}}")?;
+        }
+
+        writeln!(out)?;
+        write!(out, "export type EventKind =")?;
+        if abi.events.is_empty() {
+            write!(out, " never")?;
+        }
+        for event in &abi.events {
+            write!(out, "\n  | {{ name: \"{}\"; args: {}Event }}", event.name, pascal_case(&event.name))?;
+        }
+        writeln!(out, ";")?;
+        writeln!(out)?;
+        writeln!(out, "export function decodeEvent(log: EventLog): EventKind | null {{")?;
+        writeln!(out, "  switch (log.topics[0]) {{")?;
+        for event in &abi.events {
+            writeln!(out, "    case \"{}\": return {{ name: \"{}\", args: decode{}Event(log) }};", abi::event_topic(&event.name), event.name, pascal_case(&event.name))?;
+        }
+        writeln!(out, "    default: return null;")?;
+        writeln!(out, "  }}")?;
+        writeln!(out, "}}")?;
+
+        writeln!(out)?;
+        writeln!(out, "export class {} {{", client)?;
+        writeln!(out, "  constructor(")?;
+        writeln!(out, "    public baseUrl: string = DEFAULT_BASE_URL,")?;
+        writeln!(out, "    public callerWalletId?: string,")?;
+        writeln!(out, "    public readonly address: string = CONTRACT_ADDRESS,")?;
+        writeln!(out, "  ) {{}}")?;
+        writeln!(out)?;
+        writeln!(out, "  async call(fn: string, args: unknown[]): Promise<CallResult> {{")?;
+        writeln!(out, "    const response = await fetch(`${{this.baseUrl}}/api/contracts/execute`, {{")?;
+        writeln!(out, "      method: \"POST\",")?;
+        writeln!(out, "      headers: {{ \"Content-Type\": \"application/json\" }},")?;
+        writeln!(out, "      body: JSON.stringify({{ contract_address: this.address, caller_wallet_id: this.callerWalletId, function: fn, args }}),")?;
+        writeln!(out, "    }});")?;
+        writeln!(out, "    const body = await response.json();")?;
+        writeln!(out, "    if (!body.success) throw new Error(body.error ?? `Call to ${{fn}} failed`);")?;
+        writeln!(out, "    return body.data as CallResult;")?;
+        writeln!(out, "  }}")?;
+
+        for function in &abi.functions {
+            let params: Vec<String> = function.params.iter()
+                .map(|p| format!("{}: {}", ts_ident(&p.name), ts_type(p.ty)))
+                .collect();
+            let args: Vec<String> = function.params.iter().map(|p| ts_ident(&p.name)).collect();
+            let returns = match function.returns.as_slice() {
+                [] => "void".to_string(),
+                [ty] => ts_type(*ty).to_string(),
+                many => format!("[{}]", many.iter().map(|t| ts_type(*t)).collect::<Vec<_>>().join(", ")),
+            };
+
+            writeln!(out)?;
+            writeln!(out, "  async {}({}): Promise<{}> {{", camel_case(&function.name), params.join(", "), returns)?;
+            writeln!(out, "    const {{ results }} = await this.call(\"{}\", [{}]);", function.name, args.join(", "))?;
+            match function.returns.len() {
+                0 => writeln!(out, "    void results;")?,
+                1 => writeln!(out, "    return results[0] as {};", returns)?,
+                _ => writeln!(out, "    return results as {};", returns)?,
+            }
+            writeln!(out, "  }}")?;
+        }
+        writeln!(out, "}}")?;
+        Ok(out)
+    }
+
+    pub fn generate_rust_sdk(
+        contract: &DeployedContract,
+        client: &str,
+        abi: &ContractAbi,
+        base_url: &str,
+    ) -> Result<String, std::fmt::Error> {
+        let mut out = String::new();
+        writeln!(out, "// Q-Verse Rust SDK - Auto-generated from the contract ABI")?;
+        writeln!(out, "// Dependencies: reqwest (json), serde_json, hex")?;
+        writeln!(out, "use serde_json::{{json, Value}};")?;
+        writeln!(out)?;
+        writeln!(out, "pub const DEFAULT_BASE_URL: &str = \"{}\";", base_url)?;
+        writeln!(out, "pub const CONTRACT_ADDRESS: &str = \"{}\";", contract.address)?;
+        out.push_str(RUST_HELPERS);
+
+        for event in &abi.events {
+            let name = pascal_case(&event.name);
+            writeln!(out)?;
+            writeln!(out, "#[derive(Debug, Clone, PartialEq)]")?;
+            writeln!(out, "pub struct {}Event {{", name)?;
+            for field in &event.fields {
+                let ty = match (field.indexed, field.ty) {
+                    (true, AbiType::I32 | AbiType::I64) => "i64",
+                    (true, AbiType::F32 | AbiType::F64) => "f64",
+                    (true, AbiType::String | AbiType::Bytes) => "String", // sha256 hex
+                    (false, ty) => rust_owned_type(ty),
+                };
+                writeln!(out, "    pub {}: {},", rust_ident(&field.name), ty)?;
+            }
+            writeln!(out, "}}")?;
+            writeln!(out)?;
+            writeln!(out, "pub fn decode_{}_event(log: &EventLog) -> Result<{}Event, Error> {{", snake_case(&event.name), name)?;
+            if event.fields.iter().any(|f| !f.indexed) {
+                writeln!(out, "    let mut data = DataReader::new(&log.data)?;")?;
+            }
+            writeln!(out, "    Ok({}Event {{", name)?;
+            for (field, source) in field_sources(event) {
+                let expr = match source {
+                    FieldSource::Topic(i) => match field.ty {
+                        AbiType::I32 | AbiType::I64 => format!("topic_i64(log, {})?", i),
+                        AbiType::F32 | AbiType::F64 => format!("topic_f64(log, {})?", i),
+                        AbiType::String | AbiType::Bytes => format!("topic_hash(log, {})?", i),
+                    },
+                    FieldSource::Data => format!("data.{}()?", reader_method_snake(field.ty)),
+                };
+                writeln!(out, "        {}: {},", rust_ident(&field.name), expr)?;
+            }
+            writeln!(out, "    }})")?;
+            writeln!(out, "}}")?;
+        }
+
+        writeln!(out)?;
+        writeln!(out, "#[derive(Debug, Clone, PartialEq)]")?;
+        writeln!(out, "pub enum EventKind {{")?;
+        for event in &abi.events {
+            writeln!(out, "    {}({}Event),", pascal_case(&event.name), pascal_case(&event.name))?;
+        }
+        writeln!(out, "}}")?;
+        writeln!(out)?;
+        writeln!(out, "/// `None` for events not declared in the ABI")?;
+        writeln!(out, "pub fn decode_event(log: &EventLog) -> Result<Option<EventKind>, Error> {{")?;
+        writeln!(out, "    match log.topics.first().map(String::as_str) {{")?;
+        for event in &abi.events {
+            writeln!(
+                out,
+                "        Some(\"{}\") => Ok(Some(EventKind::{}(decode_{}_event(log)?))),",
+                abi::event_topic(&event.name), pascal_case(&event.name), snake_case(&event.name)
+            )?;
+        }
+        writeln!(out, "        _ => Ok(None),")?;
+        writeln!(out, "    }}")?;
+        writeln!(out, "}}")?;
+
+        writeln!(out)?;
+        writeln!(out, "pub struct {} {{", client)?;
+        writeln!(out, "    pub base_url: String,")?;
+        writeln!(out, "    pub address: String,")?;
+        writeln!(out, "    pub caller_wallet_id: Option<String>,")?;
+        writeln!(out, "    http: reqwest::Client,")?;
+        writeln!(out, "}}")?;
+        writeln!(out)?;
+        writeln!(out, "impl Default for {} {{", client)?;
+        writeln!(out, "    fn default() -> Self {{")?;
+        writeln!(out, "        Self::new(DEFAULT_BASE_URL)")?;
+        writeln!(out, "    }}")?;
+        writeln!(out, "}}")?;
+        writeln!(out)?;
+        writeln!(out, "impl {} {{", client)?;
+        writeln!(out, "    pub fn new(base_url: impl Into<String>) -> Self {{")?;
+        writeln!(out, "        Self {{ base_url: base_url.into(), address: CONTRACT_ADDRESS.to_string(), caller_wallet_id: None, http: reqwest::Client::new() }}")?;
+        writeln!(out, "    }}")?;
+        writeln!(out)?;
+        writeln!(out, "    pub fn with_caller(mut self, wallet_id: impl Into<String>) -> Self {{")?;
+        writeln!(out, "        self.caller_wallet_id = Some(wallet_id.into());")?;
+        writeln!(out, "        self")?;
+        writeln!(out, "    }}")?;
+        writeln!(out)?;
+        writeln!(out, "    pub async fn call(&self, function: &str, args: Vec<Value>) -> Result<CallResult, Error> {{")?;
+        writeln!(out, "        let body: Value = self.http")?;
+        writeln!(out, "            .post(format!(\"{{}}/api/contracts/execute\", self.base_url))")?;
+        writeln!(out, "            .json(&json!({{")?;
+        writeln!(out, "                \"contract_address\": self.address,")?;
+        writeln!(out, "                \"caller_wallet_id\": self.caller_wallet_id,")?;
+        writeln!(out, "                \"function\": function,")?;
+        writeln!(out, "                \"args\": args,")?;
+        writeln!(out, "            }}))")?;
+        writeln!(out, "            .send()")?;
+        writeln!(out, "            .await?")?;
+        writeln!(out, "            .json()")?;
+        writeln!(out, "            .await?;")?;
+        writeln!(out, "        if body[\"success\"] != Value::Bool(true) {{")?;
+        writeln!(out, "            return Err(body[\"error\"].as_str().unwrap_or(\"contract call failed\").into());")?;
+        writeln!(out, "        }}")?;
+        writeln!(out, "        Ok(serde_json::from_value(body[\"data\"].clone())?)")?;
+        writeln!(out, "    }}")?;
+
+        for function in &abi.functions {
+            let params: Vec<String> = function.params.iter()
+                .map(|p| format!("{}: {}", rust_ident(&p.name), rust_param_type(p.ty)))
+                .collect();
+            let args: Vec<String> = function.params.iter()
+                .map(|p| match p.ty {
+                    AbiType::Bytes => format!("json!(hex::encode({}))", rust_ident(&p.name)),
+                    _ => format!("json!({})", rust_ident(&p.name)),
+                })
+                .collect();
+            let results: Vec<String> = function.returns.iter().enumerate()
+                .map(|(i, ty)| match ty {
+                    AbiType::Bytes => format!("result_bytes(&results, {})?", i),
+                    _ => format!("result_value(&results, {})?", i),
+                })
+                .collect();
+            let (returns, value) = match function.returns.as_slice() {
+                [] => ("()".to_string(), "()".to_string()),
+                [ty] => (rust_owned_type(*ty).to_string(), results[0].clone()),
+                many => (
+                    format!("({})", many.iter().map(|t| rust_owned_type(*t)).collect::<Vec<_>>().join(", ")),
+                    format!("({})", results.join(", ")),
+                ),
+            };
+
+            writeln!(out)?;
+            writeln!(out, "    pub async fn {}(&self{}) -> Result<{}, Error> {{",
+                rust_ident(&snake_case(&function.name)),
+                params.iter().map(|p| format!(", {}", p)).collect::<String>(),
+                returns)?;
+            let binding = if function.returns.is_empty() { "_results" } else { "results" };
+            writeln!(out, "        let {} = self.call(\"{}\", vec![{}]).await?.results;", binding, function.name, args.join(", "))?;
+            writeln!(out, "        Ok({})", value)?;
+            writeln!(out, "    }}")?;
+        }
+        writeln!(out, "}}")?;
+        Ok(out)
+    }
+
+    pub fn generate_python_sdk(
+        contract: &DeployedContract,
+        client: &str,
+        abi: &ContractAbi,
+        base_url: &str,
+    ) -> Result<String, std::fmt::Error> {
+        let mut out = String::new();
+        writeln!(out, "# Q-Verse Python SDK - Auto-generated from the contract ABI")?;
+        writeln!(out, "# Requires: requests")?;
+        writeln!(out, "import struct")?;
+        writeln!(out, "from dataclasses import dataclass")?;
+        writeln!(out, "from typing import Any, Dict, List, Optional, Tuple")?;
+        writeln!(out)?;
+        writeln!(out, "import requests")?;
+        writeln!(out)?;
+        writeln!(out, "DEFAULT_BASE_URL = \"{}\"", base_url)?;
+        writeln!(out, "CONTRACT_ADDRESS = \"{}\"", contract.address)?;
+        out.push_str(PYTHON_HELPERS);
+
+        for event in &abi.events {
+            let name = pascal_case(&event.name);
+            writeln!(out)?;
+            writeln!(out)?;
+            writeln!(out, "@dataclass")?;
+            writeln!(out, "class {}Event:", name)?;
+            if event.fields.is_empty() {
+                writeln!(out, "    pass")?;
+            }
+            for field in &event.fields {
+                let ty = if field.indexed && matches!(field.ty, AbiType::Bytes) { "str" } else { py_type(field.ty) };
+                writeln!(out, "    {}: {}", py_ident(&field.name), ty)?;
+            }
+            writeln!(out)?;
+            writeln!(out)?;
+            writeln!(out, "def decode_{}_event(log: Dict[str, Any]) -> {}Event:", snake_case(&event.name), name)?;
+            writeln!(out, "    data = _DataReader(log[\"data\"])")?;
+            writeln!(out, "    return {}Event(", name)?;
+            for (field, source) in field_sources(event) {
+                let expr = match source {
+                    FieldSource::Topic(i) => match field.ty {
+                        AbiType::I32 | AbiType::I64 => format!("_topic_int(log, {})", i),
+                        AbiType::F32 | AbiType::F64 => format!("_topic_float(log, {})", i),
+                        AbiType::String | AbiType::Bytes => format!("log[\"topics\"][{}]", i),
+                    },
+                    FieldSource::Data => format!("data.{}()", reader_method_snake(field.ty)),
+                };
+                writeln!(out, "        {}={},", py_ident(&field.name), expr)?;
+            }
+            writeln!(out, "    )")?;
+        }
+
+        writeln!(out)?;
+        writeln!(out)?;
+        writeln!(out, "EVENT_DECODERS = {{")?;
+        for event in &abi.events {
+            writeln!(out, "    \"{}\": decode_{}_event,", abi::event_topic(&event.name), snake_case(&event.name))?;
+        }
+        writeln!(out, "}}")?;
+        writeln!(out)?;
+        writeln!(out)?;
+        writeln!(out, "def decode_event(log: Dict[str, Any]) -> Optional[Any]:")?;
+        writeln!(out, "    \"\"\"Decoded event dataclass, or None for events not declared in the ABI.\"\"\"")?;
+        writeln!(out, "    topics = log.get(\"topics\") or [None]")?;
+        writeln!(out, "    decoder = EVENT_DECODERS.get(topics[0])")?;
+        writeln!(out, "    return decoder(log) if decoder else None")?;
+
+        writeln!(out)?;
+        writeln!(out)?;
+        writeln!(out, "class {}:", client)?;
+        writeln!(out, "    def __init__(self, base_url: str = DEFAULT_BASE_URL, caller_wallet_id: Optional[str] = None,")?;
+        writeln!(out, "                 address: str = CONTRACT_ADDRESS, timeout: float = 30.0):")?;
+        writeln!(out, "        self.base_url = base_url")?;
+        writeln!(out, "        self.caller_wallet_id = caller_wallet_id")?;
+        writeln!(out, "        self.address = address")?;
+        writeln!(out, "        self.timeout = timeout")?;
+        writeln!(out)?;
+        writeln!(out, "    def call(self, function: str, args: List[Any]) -> Dict[str, Any]:")?;
+        writeln!(out, "        response = requests.post(")?;
+        writeln!(out, "            f\"{{self.base_url}}/api/contracts/execute\",")?;
+        writeln!(out, "            json={{")?;
+        writeln!(out, "                \"contract_address\": self.address,")?;
+        writeln!(out, "                \"caller_wallet_id\": self.caller_wallet_id,")?;
+        writeln!(out, "                \"function\": function,")?;
+        writeln!(out, "                \"args\": args,")?;
+        writeln!(out, "            }},")?;
+        writeln!(out, "            timeout=self.timeout,")?;
+        writeln!(out, "        )")?;
+        writeln!(out, "        body = response.json()")?;
+        writeln!(out, "        if not body.get(\"success\"):")?;
+        writeln!(out, "            raise RuntimeError(body.get(\"error\") or f\"Call to {{function}} failed\")")?;
+        writeln!(out, "        return body[\"data\"]")?;
+
+        for function in &abi.functions {
+            let params: String = function.params.iter()
+                .map(|p| format!(", {}: {}", py_ident(&p.name), py_type(p.ty)))
+                .collect();
+            let args: Vec<String> = function.params.iter()
+                .map(|p| match p.ty {
+                    AbiType::Bytes => format!("{}.hex()", py_ident(&p.name)),
+                    _ => py_ident(&p.name),
+                })
+                .collect();
+            let convert = |i: usize, ty: &AbiType| match ty {
+                AbiType::Bytes => format!("bytes.fromhex(results[{}])", i),
+                _ => format!("results[{}]", i),
+            };
+            let (returns, value) = match function.returns.as_slice() {
+                [] => ("None".to_string(), None),
+                [ty] => (py_type(*ty).to_string(), Some(convert(0, ty))),
+                many => (
+                    format!("Tuple[{}]", many.iter().map(|t| py_type(*t)).collect::<Vec<_>>().join(", ")),
+                    Some(format!("({})", many.iter().enumerate().map(|(i, t)| convert(i, t)).collect::<Vec<_>>().join(", "))),
+                ),
+            };
+
+            writeln!(out)?;
+            writeln!(out, "    def {}(self{}) -> {}:", py_ident(&snake_case(&function.name)), params, returns)?;
+            match value {
+                Some(value) => {
+                    writeln!(out, "        results = self.call(\"{}\", [{}])[\"results\"]", function.name, args.join(", "))?;
+                    writeln!(out, "        return {}", value)?;
+                }
+                None => writeln!(out, "        self.call(\"{}\", [{}])", function.name, args.join(", "))?,
+            }
+        }
+        Ok(out)
+    }
+}
+
+enum FieldSource {
+    Topic(usize),
+    Data,
+}
+
+/// Where each event field is encoded, in ABI order
+fn field_sources(event: &AbiEvent) -> Vec<(&AbiEventField, FieldSource)> {
+    let mut next_topic = 1;
+    event.fields.iter()
+        .map(|field| {
+            if field.indexed {
+                next_topic += 1;
+                (field, FieldSource::Topic(next_topic - 1))
+            } else {
+                (field, FieldSource::Data)
+            }
+        })
+        .collect()
+}
+
+fn reader_method(ty: AbiType) -> &'static str {
+    match ty {
+        AbiType::I32 => "i32",
+        AbiType::I64 => "i64",
+        AbiType::F32 => "f32",
+        AbiType::F64 => "f64",
+        AbiType::String => "string",
+        AbiType::Bytes => "bytes",
+    }
+}
+
+fn reader_method_snake(ty: AbiType) -> String {
+    format!("read_{}", reader_method(ty))
+}
+
+fn ts_type(ty: AbiType) -> &'static str {
+    match ty {
+        AbiType::I32 | AbiType::I64 | AbiType::F32 | AbiType::F64 => "number",
+        AbiType::String | AbiType::Bytes => "string", // bytes: hex
+    }
+}
+
+fn rust_param_type(ty: AbiType) -> &'static str {
+    match ty {
+        AbiType::String => "&str",
+        AbiType::Bytes => "&[u8]",
+        other => rust_owned_type(other),
+    }
+}
+
+fn rust_owned_type(ty: AbiType) -> &'static str {
+    match ty {
+        AbiType::I32 => "i32",
+        AbiType::I64 => "i64",
+        AbiType::F32 => "f32",
+        AbiType::F64 => "f64",
+        AbiType::String => "String",
+        AbiType::Bytes => "Vec<u8>",
+    }
+}
+
+fn py_type(ty: AbiType) -> &'static str {
+    match ty {
+        AbiType::I32 | AbiType::I64 => "int",
+        AbiType::F32 | AbiType::F64 => "float",
+        AbiType::String => "str",
+        AbiType::Bytes => "bytes",
+    }
+}
+
+const TS_RESERVED: &[&str] = &[
+    "break", "case", "catch", "class", "const", "continue", "debugger", "default", "delete", "do", "else",
+    "enum", "export", "extends", "false", "finally", "for", "function", "if", "import", "in", "instanceof",
+    "new", "null", "return", "super", "switch", "this", "throw", "true", "try", "typeof", "var", "void",
+    "while", "with", "let", "static", "yield", "await",
+    "call", "constructor", "baseUrl", "callerWalletId", "address",
+];
+
+const RUST_RESERVED: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern", "false",
+    "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return",
+    "self", "Self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use", "where", "while",
+    "call", "new", "with_caller", "default",
+];
+
+const PY_RESERVED: &[&str] = &[
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue", "def",
+    "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import", "in", "is",
+    "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while", "with", "yield",
+    "self", "call", "data", "log", "results", "base_url", "caller_wallet_id", "address", "timeout",
+];
+
+fn ts_ident(name: &str) -> String {
+    escape_reserved(name, TS_RESERVED)
+}
+
+fn rust_ident(name: &str) -> String {
+    escape_reserved(name, RUST_RESERVED)
+}
+
+fn py_ident(name: &str) -> String {
+    escape_reserved(name, PY_RESERVED)
+}
+
+fn escape_reserved(name: &str, reserved: &[&str]) -> String {
+    if reserved.contains(&name) { format!("{}_", name) } else { name.to_string() }
+}
+
+/// Splits `camelCase`, `PascalCase` and `snake_case` into lowercase words
+fn words(name: &str) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut prev_lower = false;
+    for c in name.chars() {
+        if c == '_' || !c.is_ascii_alphanumeric() {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            prev_lower = false;
+            continue;
+        }
+        if c.is_ascii_uppercase() && prev_lower && !current.is_empty() {
+            words.push(std::mem::take(&mut current));
+        }
+        prev_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+        current.push(c.to_ascii_lowercase());
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+        None => String::new(),
+    }
+}
+
+fn snake_case(name: &str) -> String {
+    let snake = words(name).join("_");
+    if snake.starts_with(|c: char| c.is_ascii_digit()) || snake.is_empty() { format!("_{}", snake) } else { snake }
+}
+
+fn camel_case(name: &str) -> String {
+    let words = words(name);
+    let camel: String = words.iter().enumerate()
+        .map(|(i, w)| if i == 0 { w.clone() } else { capitalize(w) })
+        .collect();
+    let camel = if camel.starts_with(|c: char| c.is_ascii_digit()) || camel.is_empty() { format!("_{}", camel) } else { camel };
+    ts_ident(&camel)
+}
+
+fn pascal_case(name: &str) -> String {
+    let pascal: String = words(name).iter().map(|w| capitalize(w)).collect();
+    if pascal.starts_with(|c: char| c.is_ascii_digit()) || pascal.is_empty() { format!("Contract{}", pascal) } else { pascal }
+}
+
+const TS_HELPERS: &str = r#"
+function hexToBytes(hex: string): Uint8Array {
+  const clean = hex.startsWith("0x") ? hex.slice(2) : hex;
+  const bytes = new Uint8Array(clean.length / 2);
+  for (let i = 0; i < bytes.length; i++) bytes[i] = parseInt(clean.substr(i * 2, 2), 16);
+  return bytes;
+}
+
+function bytesToHex(bytes: Uint8Array): string {
+  return Array.from(bytes, (b) => b.toString(16).padStart(2, "0")).join("");
+}
+
+function topicView(log: EventLog, index: number): DataView {
+  const topic = hexToBytes(log.topics[index] ?? "");
+  if (topic.length !== 32) throw new Error(`Topic ${index} is not 32 bytes`);
+  return new DataView(topic.buffer, 24, 8);
+}
+
+function topicInt(log: EventLog, index: number): number {
+  return Number(topicView(log, index).getBigInt64(0, false));
+}
+
+function topicFloat(log: EventLog, index: number): number {
+  return topicView(log, index).getFloat64(0, false);
+}
+
+class DataReader {
+  private view: DataView;
+  private buf: Uint8Array;
+  private pos = 0;
+
+  constructor(hex: string) {
+    this.buf = hexToBytes(hex);
+    this.view = new DataView(this.buf.buffer);
+  }
+
+  private take(n: number): number {
+    if (this.pos + n > this.buf.length) throw new Error("Event data too short");
+    const at = this.pos;
+    this.pos += n;
+    return at;
+  }
+
+  i32(): number { return this.view.getInt32(this.take(4), true); }
+  i64(): number { return Number(this.view.getBigInt64(this.take(8), true)); }
+  f32(): number { return this.view.getFloat32(this.take(4), true); }
+  f64(): number { return this.view.getFloat64(this.take(8), true); }
+  bytes(): string {
+    const len = this.view.getUint32(this.take(4), true);
+    const at = this.take(len);
+    return bytesToHex(this.buf.slice(at, at + len));
+  }
+  string(): string {
+    const len = this.view.getUint32(this.take(4), true);
+    const at = this.take(len);
+    return new TextDecoder().decode(this.buf.slice(at, at + len));
+  }
+}
+"#;
+
+const RUST_HELPERS: &str = r#"
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct EventLog {
+    pub topics: Vec<String>,
+    pub data: String,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct CallResult {
+    pub results: Vec<Value>,
+    #[serde(default)]
+    pub events: Vec<EventLog>,
+    pub gas_used: u64,
+    pub transaction_id: Option<String>,
+}
+
+#[allow(dead_code)]
+struct DataReader {
+    bytes: Vec<u8>,
+    pos: usize,
+}
+
+#[allow(dead_code)]
+impl DataReader {
+    fn new(hex_data: &str) -> Result<Self, Error> {
+        Ok(Self { bytes: hex::decode(hex_data.trim_start_matches("0x"))?, pos: 0 })
+    }
+
+    fn take(&mut self, n: usize) -> Result<&[u8], Error> {
+        let end = self.pos + n;
+        let bytes = self.bytes.get(self.pos..end).ok_or("event data too short")?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn read_i32(&mut self) -> Result<i32, Error> { Ok(i32::from_le_bytes(self.take(4)?.try_into()?)) }
+    fn read_i64(&mut self) -> Result<i64, Error> { Ok(i64::from_le_bytes(self.take(8)?.try_into()?)) }
+    fn read_f32(&mut self) -> Result<f32, Error> { Ok(f32::from_le_bytes(self.take(4)?.try_into()?)) }
+    fn read_f64(&mut self) -> Result<f64, Error> { Ok(f64::from_le_bytes(self.take(8)?.try_into()?)) }
+    fn read_bytes(&mut self) -> Result<Vec<u8>, Error> {
+        let len = u32::from_le_bytes(self.take(4)?.try_into()?) as usize;
+        Ok(self.take(len)?.to_vec())
+    }
+    fn read_string(&mut self) -> Result<String, Error> { Ok(String::from_utf8(self.read_bytes()?)?) }
+}
+
+#[allow(dead_code)]
+fn topic_word(log: &EventLog, index: usize) -> Result<[u8; 8], Error> {
+    let topic = hex::decode(log.topics.get(index).ok_or("missing topic")?)?;
+    Ok(topic.get(24..32).filter(|_| topic.len() == 32).ok_or("topic must be 32 bytes")?.try_into()?)
+}
+
+#[allow(dead_code)]
+fn topic_i64(log: &EventLog, index: usize) -> Result<i64, Error> { Ok(i64::from_be_bytes(topic_word(log, index)?)) }
+
+#[allow(dead_code)]
+fn topic_f64(log: &EventLog, index: usize) -> Result<f64, Error> { Ok(f64::from_be_bytes(topic_word(log, index)?)) }
+
+#[allow(dead_code)]
+fn topic_hash(log: &EventLog, index: usize) -> Result<String, Error> {
+    Ok(log.topics.get(index).ok_or("missing topic")?.clone())
+}
+
+#[allow(dead_code)]
+fn result_value<T: serde::de::DeserializeOwned>(results: &[Value], index: usize) -> Result<T, Error> {
+    Ok(serde_json::from_value(results.get(index).cloned().ok_or("missing result")?)?)
+}
+
+#[allow(dead_code)]
+fn result_bytes(results: &[Value], index: usize) -> Result<Vec<u8>, Error> {
+    Ok(hex::decode(results.get(index).and_then(Value::as_str).ok_or("missing result")?)?)
+}
+"#;
+
+const PYTHON_HELPERS: &str = r#"
+
+class _DataReader:
+    def __init__(self, data_hex: str):
+        self._data = bytes.fromhex(data_hex[2:] if data_hex.startswith("0x") else data_hex)
+        self._pos = 0
+
+    def _take(self, n: int) -> bytes:
+        if self._pos + n > len(self._data):
+            raise ValueError("event data too short")
+        chunk = self._data[self._pos:self._pos + n]
+        self._pos += n
+        return chunk
+
+    def read_i32(self) -> int:
+        return struct.unpack("<i", self._take(4))[0]
+
+    def read_i64(self) -> int:
+        return struct.unpack("<q", self._take(8))[0]
+
+    def read_f32(self) -> float:
+        return struct.unpack("<f", self._take(4))[0]
+
+    def read_f64(self) -> float:
+        return struct.unpack("<d", self._take(8))[0]
+
+    def read_bytes(self) -> bytes:
+        (length,) = struct.unpack("<I", self._take(4))
+        return self._take(length)
+
+    def read_string(self) -> str:
+        return self.read_bytes().decode("utf-8")
+
+
+def _topic_word(log: Dict[str, Any], index: int) -> bytes:
+    topic = bytes.fromhex(log["topics"][index])
+    if len(topic) != 32:
+        raise ValueError(f"topic {index} is not 32 bytes")
+    return topic[24:]
+
+
+def _topic_int(log: Dict[str, Any], index: int) -> int:
+    return struct.unpack(">q", _topic_word(log, index))[0]
+
+
+def _topic_float(log: Dict[str, Any], index: int) -> float:
+    return struct.unpack(">d", _topic_word(log, index))[0]
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture() -> (DeployedContract, ContractAbi) {
+        let contract = DeployedContract {
+            id: "d1".into(),
+            contract_id: "c1".into(),
+            compiled_contract_id: None,
+            deployer_wallet_id: "w1".into(),
+            address: "0xabc".into(),
+            deployment_tx_id: None,
+            upgradeable: false,
+            upgrade_authority_type: None,
+            upgrade_authority_id: None,
+            current_version: 1,
+            reentrancy_guard: true,
+        };
+        let abi = ContractAbi::from_json(br#"{"version": 1,
+            "functions": [
+                {"name": "transfer", "params": [{"name": "to", "type": "string"}, {"name": "amount", "type": "i64"}], "returns": ["i32"]},
+                {"name": "balanceOf", "params": [{"name": "owner", "type": "bytes"}], "returns": ["i64", "bytes"], "mutability": "view"}
+            ],
+            "events": [{"name": "Transfer", "fields": [{"name": "to", "type": "string", "indexed": true}, {"name": "amount", "type": "i64"}]}]
+        }"#).unwrap();
+        (contract, abi)
+    }
+
+    #[test]
+    fn test_typed_methods_per_language() {
+        let (contract, abi) = fixture();
+        let generate = |lang| SDKGenerator::generate(&contract, "my_token", &abi, lang, "https://node.example:8443").unwrap();
+
+        let ts = generate(SdkLanguage::TypeScript);
+        assert!(ts.contains("export class MyTokenClient"));
+        assert!(ts.contains("async transfer(to: string, amount: number): Promise<number>"));
+        assert!(ts.contains("async balanceOf(owner: string): Promise<[number, string]>"));
+        assert!(ts.contains("export const DEFAULT_BASE_URL = \"https://node.example:8443\";"));
+        assert!(ts.contains(&format!("case \"{}\": return {{ name: \"Transfer\", args: decodeTransferEvent(log) }};", abi::event_topic("Transfer"))));
+
+        let rust = generate(SdkLanguage::Rust);
+        assert!(rust.contains("pub async fn transfer(&self, to: &str, amount: i64) -> Result<i32, Error>"));
+        assert!(rust.contains("pub async fn balance_of(&self, owner: &[u8]) -> Result<(i64, Vec<u8>), Error>"));
+        assert!(rust.contains("amount: data.read_i64()?,"));
+
+        let python = generate(SdkLanguage::Python);
+        assert!(python.contains("def balance_of(self, owner: bytes) -> Tuple[int, bytes]:"));
+        assert!(python.contains("def decode_transfer_event(log: Dict[str, Any]) -> TransferEvent:"));
+    }
+
+    #[test]
+    fn test_rejects_unsafe_inputs() {
+        let (contract, abi) = fixture();
+        assert!(SDKGenerator::generate(&contract, "t", &abi, SdkLanguage::Rust, "http://x\"; evil()").is_err());
+        assert!(SDKGenerator::generate(&contract, "t", &abi, SdkLanguage::Rust, "ftp://x").is_err());
+        assert_eq!(SdkLanguage::parse("JS"), Some(SdkLanguage::TypeScript));
+        assert_eq!(SdkLanguage::parse("go"), None);
+    }
+}
@@ -0,0 +1,393 @@
+/* ==============================================
+ * File:        src/state.rs
+ * Author:      USDTG GROUP TECHNOLOGY LLC
+ * Developer:   Irfan Gedik
+ * Created Date: 2025-12-22
+ * Last Update:  2025-12-22
+ * Version:     1.0.0
+ *
+ * Description:
+ *   Contract State Overlay
+ *
+ *   Contracts never write to the database directly. A call reads
+ *   committed state through a `StateReader` and buffers every write in a
+ *   `StateOverlay`; the resulting `StateDiff` is either committed with the
+ *   call transaction or, for a simulation, just returned.
+ *
+ * License:
+ *   MIT License
+ * ============================================== */
+
+use crate::db::Database;
+use crate::vm::VmError;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::runtime::Handle;
+
+/// Token moved by the `balance` / `transfer` host functions
+pub const NATIVE_TOKEN: &str = "QVR";
+
+/// Host functions use integer base units so contracts stay float-free
+pub const UNITS_PER_TOKEN: u64 = 100_000_000;
+
+pub fn to_units(amount: f64) -> u64 {
+    (amount.max(0.0) * UNITS_PER_TOKEN as f64).round() as u64
+}
+
+pub fn from_units(units: i128) -> f64 {
+    units as f64 / UNITS_PER_TOKEN as f64
+}
+
+/// Deployed code, as needed for a nested call
+#[derive(Debug, Clone)]
+pub struct ContractCode {
+    pub address: String, // Canonical address
+    pub wasm: Vec<u8>,
+    pub reentrancy_guard: bool,
+}
+
+/// Read access to committed chain state
+pub trait StateReader: Send + Sync {
+    fn storage(&self, contract: &str, key: &[u8]) -> Result<Option<Vec<u8>>, VmError>;
+
+    /// Native token balance in base units; unknown accounts are an error
+    fn balance(&self, account: &str) -> Result<u64, VmError>;
+
+    /// Current code behind a contract address
+    fn code(&self, address: &str) -> Result<Option<ContractCode>, VmError>;
+}
+
+/// Committed state in the database
+///
+/// Host functions run on blocking worker threads, so the async queries are
+/// driven with `Handle::block_on`.
+pub struct DbState {
+    db: Database,
+    handle: Handle,
+}
+
+impl DbState {
+    /// Must be created inside the Tokio runtime
+    pub fn new(db: Database) -> Self {
+        Self { db, handle: Handle::current() }
+    }
+}
+
+impl StateReader for DbState {
+    fn storage(&self, contract: &str, key: &[u8]) -> Result<Option<Vec<u8>>, VmError> {
+        let value = self.handle
+            .block_on(self.db.get_contract_storage(contract, &hex::encode(key)))
+            .map_err(|e| e.to_string())?;
+        match value {
+            Some(v) => Ok(Some(hex::decode(v)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn balance(&self, account: &str) -> Result<u64, VmError> {
+        let amount = self.handle
+            .block_on(self.db.get_account_balance(account, NATIVE_TOKEN))
+            .map_err(|e| e.to_string())?;
+        amount.map(to_units).ok_or_else(|| format!("Unknown account: {}", account).into())
+    }
+
+    fn code(&self, address: &str) -> Result<Option<ContractCode>, VmError> {
+        let deployed = match self.handle.block_on(self.db.get_deployed_contract(address)).map_err(|e| e.to_string())? {
+            Some(d) => d,
+            None => return Ok(None),
+        };
+        let compiled_id = deployed.compiled_contract_id.unwrap_or_default();
+        let compiled = match self.handle.block_on(self.db.get_compiled_contract(&compiled_id)).map_err(|e| e.to_string())? {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+
+        Ok(Some(ContractCode {
+            address: deployed.address,
+            wasm: hex::decode(compiled.wasm_hex)?,
+            reentrancy_guard: deployed.reentrancy_guard,
+        }))
+    }
+}
+
+/// In-memory state, for ad-hoc calls and tests
+#[derive(Default, Clone)]
+pub struct MemoryState {
+    pub storage: BTreeMap<(String, Vec<u8>), Vec<u8>>,
+    pub balances: BTreeMap<String, u64>,
+    pub contracts: BTreeMap<String, ContractCode>,
+}
+
+impl StateReader for MemoryState {
+    fn storage(&self, contract: &str, key: &[u8]) -> Result<Option<Vec<u8>>, VmError> {
+        Ok(self.storage.get(&(contract.to_string(), key.to_vec())).cloned())
+    }
+
+    fn balance(&self, account: &str) -> Result<u64, VmError> {
+        self.balances.get(account).copied()
+            .ok_or_else(|| format!("Unknown account: {}", account).into())
+    }
+
+    fn code(&self, address: &str) -> Result<Option<ContractCode>, VmError> {
+        Ok(self.contracts.get(address).cloned())
+    }
+}
+
+/// One storage slot changed by a call
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct StorageWrite {
+    pub contract: String,
+    pub key: String,              // hex
+    pub previous: Option<String>, // hex, None if the slot was empty
+    pub value: String,            // hex
+}
+
+/// A storage slot the call read but did not change; it must be unchanged at commit
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct StorageRead {
+    pub contract: String,
+    pub key: String,           // hex
+    pub value: Option<String>, // hex, None if the slot was empty
+}
+
+/// Net native token movement for one account
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct BalanceDelta {
+    pub account: String,
+    pub token: String,
+    pub before: f64,
+    pub after: f64,
+    pub delta: f64,
+}
+
+/// Everything a call would change
+#[derive(Debug, Clone, Default, Serialize, utoipa::ToSchema)]
+pub struct StateDiff {
+    pub storage_writes: Vec<StorageWrite>,
+    pub storage_reads: Vec<StorageRead>,
+    pub balance_deltas: Vec<BalanceDelta>,
+}
+
+impl StateDiff {
+    pub fn is_empty(&self) -> bool {
+        self.storage_writes.is_empty() && self.balance_deltas.is_empty()
+    }
+}
+
+/// Saved overlay writes, restored when a nested call fails
+pub struct Checkpoint {
+    storage: BTreeMap<(String, Vec<u8>), Vec<u8>>,
+    balance_delta: BTreeMap<String, i128>,
+}
+
+/// Uncommitted writes on top of a `StateReader`
+///
+/// Every value read from the base is cached, so a call sees one consistent
+/// snapshot even if the database changes underneath it.
+pub struct StateOverlay {
+    base: Arc<dyn StateReader>,
+    storage_base: BTreeMap<(String, Vec<u8>), Option<Vec<u8>>>,
+    storage: BTreeMap<(String, Vec<u8>), Vec<u8>>,
+    balance_base: BTreeMap<String, u64>,
+    balance_delta: BTreeMap<String, i128>,
+}
+
+impl StateOverlay {
+    pub fn new(base: Arc<dyn StateReader>) -> Self {
+        Self {
+            base,
+            storage_base: BTreeMap::new(),
+            storage: BTreeMap::new(),
+            balance_base: BTreeMap::new(),
+            balance_delta: BTreeMap::new(),
+        }
+    }
+
+    pub fn storage_get(&mut self, contract: &str, key: &[u8]) -> Result<Option<Vec<u8>>, VmError> {
+        let slot = (contract.to_string(), key.to_vec());
+        if let Some(value) = self.storage.get(&slot) {
+            return Ok(Some(value.clone()));
+        }
+        self.storage_committed(slot)
+    }
+
+    pub fn storage_set(&mut self, contract: &str, key: &[u8], value: Vec<u8>) -> Result<(), VmError> {
+        let slot = (contract.to_string(), key.to_vec());
+        // Remember the committed value for the diff
+        self.storage_committed(slot.clone())?;
+        self.storage.insert(slot, value);
+        Ok(())
+    }
+
+    pub fn balance(&mut self, account: &str) -> Result<u64, VmError> {
+        let base = self.balance_committed(account)?;
+        let delta = self.balance_delta.get(account).copied().unwrap_or(0);
+        Ok((base as i128 + delta) as u64)
+    }
+
+    /// Moves `amount` base units; Ok(false) if `from` cannot cover it
+    pub fn transfer(&mut self, from: &str, to: &str, amount: u64) -> Result<bool, VmError> {
+        self.balance(to)?; // Alıcı hesabı var olmalı
+        if self.balance(from)? < amount {
+            return Ok(false);
+        }
+        *self.balance_delta.entry(from.to_string()).or_insert(0) -= amount as i128;
+        *self.balance_delta.entry(to.to_string()).or_insert(0) += amount as i128;
+        Ok(true)
+    }
+
+    pub fn code(&self, address: &str) -> Result<Option<ContractCode>, VmError> {
+        self.base.code(address)
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            storage: self.storage.clone(),
+            balance_delta: self.balance_delta.clone(),
+        }
+    }
+
+    /// Drops every write made since `checkpoint`; cached reads stay valid
+    pub fn rollback(&mut self, checkpoint: Checkpoint) {
+        self.storage = checkpoint.storage;
+        self.balance_delta = checkpoint.balance_delta;
+    }
+
+    /// Writes, the committed slots they depend on and net balance changes, in key order
+    pub fn diff(&self) -> StateDiff {
+        let changed = |slot: &(String, Vec<u8>), value: &Vec<u8>| self.storage_base.get(slot) != Some(&Some(value.clone()));
+        let storage_writes = self.storage.iter()
+            .filter(|(slot, value)| changed(slot, value))
+            .map(|((contract, key), value)| StorageWrite {
+                contract: contract.clone(),
+                key: hex::encode(key),
+                previous: self.storage_base.get(&(contract.clone(), key.clone())).cloned().flatten().map(hex::encode),
+                value: hex::encode(value),
+            })
+            .collect();
+
+        // Written slots are checked through `previous`
+        let storage_reads = self.storage_base.iter()
+            .filter(|(slot, _)| self.storage.get(*slot).is_none_or(|value| !changed(slot, value)))
+            .map(|((contract, key), value)| StorageRead {
+                contract: contract.clone(),
+                key: hex::encode(key),
+                value: value.as_ref().map(hex::encode),
+            })
+            .collect();
+
+        let balance_deltas = self.balance_delta.iter()
+            .filter(|(_, delta)| **delta != 0)
+            .map(|(account, delta)| {
+                let before = self.balance_base.get(account).copied().unwrap_or(0) as i128;
+                BalanceDelta {
+                    account: account.clone(),
+                    token: NATIVE_TOKEN.to_string(),
+                    before: from_units(before),
+                    after: from_units(before + delta),
+                    delta: from_units(*delta),
+                }
+            })
+            .collect();
+
+        StateDiff { storage_writes, storage_reads, balance_deltas }
+    }
+
+    fn storage_committed(&mut self, slot: (String, Vec<u8>)) -> Result<Option<Vec<u8>>, VmError> {
+        if let Some(value) = self.storage_base.get(&slot) {
+            return Ok(value.clone());
+        }
+        let value = self.base.storage(&slot.0, &slot.1)?;
+        self.storage_base.insert(slot, value.clone());
+        Ok(value)
+    }
+
+    fn balance_committed(&mut self, account: &str) -> Result<u64, VmError> {
+        if let Some(balance) = self.balance_base.get(account) {
+            return Ok(*balance);
+        }
+        let balance = self.base.balance(account)?;
+        self.balance_base.insert(account.to_string(), balance);
+        Ok(balance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overlay() -> StateOverlay {
+        let mut state = MemoryState::default();
+        state.storage.insert(("c1".into(), b"count".to_vec()), vec![1]);
+        state.balances.insert("c1".into(), 5 * UNITS_PER_TOKEN);
+        state.balances.insert("alice".into(), 0);
+        StateOverlay::new(Arc::new(state))
+    }
+
+    #[test]
+    fn test_reads_see_own_writes() {
+        let mut state = overlay();
+        assert_eq!(state.storage_get("c1", b"count").unwrap(), Some(vec![1]));
+        state.storage_set("c1", b"count", vec![2]).unwrap();
+        assert_eq!(state.storage_get("c1", b"count").unwrap(), Some(vec![2]));
+
+        let diff = state.diff();
+        assert_eq!(diff.storage_writes.len(), 1);
+        assert_eq!(diff.storage_writes[0].previous.as_deref(), Some("01"));
+        assert_eq!(diff.storage_writes[0].value, "02");
+    }
+
+    #[test]
+    fn test_transfer_and_deltas() {
+        let mut state = overlay();
+        assert!(state.transfer("c1", "alice", 2 * UNITS_PER_TOKEN).unwrap());
+        assert!(!state.transfer("c1", "alice", 10 * UNITS_PER_TOKEN).unwrap());
+        assert!(state.transfer("c1", "bob", 1).is_err());
+
+        let diff = state.diff();
+        assert_eq!(diff.balance_deltas.len(), 2);
+        let alice = diff.balance_deltas.iter().find(|d| d.account == "alice").unwrap();
+        assert_eq!(alice.delta, 2.0);
+        assert_eq!(alice.after, 2.0);
+    }
+
+    #[test]
+    fn test_rollback_keeps_earlier_writes() {
+        let mut state = overlay();
+        state.storage_set("c1", b"a", vec![1]).unwrap();
+        let checkpoint = state.checkpoint();
+        state.storage_set("c1", b"b", vec![2]).unwrap();
+        state.transfer("c1", "alice", 1).unwrap();
+        state.rollback(checkpoint);
+
+        let diff = state.diff();
+        assert_eq!(diff.storage_writes.len(), 1);
+        assert_eq!(diff.storage_writes[0].key, hex::encode(b"a"));
+        assert!(diff.balance_deltas.is_empty());
+    }
+
+    #[test]
+    fn test_unchanged_write_is_not_a_diff() {
+        let mut state = overlay();
+        state.storage_set("c1", b"count", vec![1]).unwrap();
+        assert!(state.diff().is_empty());
+    }
+
+    #[test]
+    fn test_read_slots_are_part_of_the_diff() {
+        let mut state = overlay();
+        let count = state.storage_get("c1", b"count").unwrap().unwrap();
+        assert_eq!(state.storage_get("c1", b"missing").unwrap(), None);
+        state.storage_set("c1", b"total", count).unwrap();
+
+        let diff = state.diff();
+        assert_eq!(diff.storage_writes.len(), 1);
+        assert_eq!(diff.storage_writes[0].key, hex::encode(b"total"));
+        let reads: Vec<_> = diff.storage_reads.iter().map(|r| (r.key.clone(), r.value.clone())).collect();
+        assert_eq!(reads, vec![
+            (hex::encode(b"count"), Some("01".to_string())),
+            (hex::encode(b"missing"), None),
+        ]);
+    }
+}
@@ -0,0 +1,273 @@
+/* ==============================================
+ * File:        src/testkit.rs
+ * Author:      USDTG GROUP TECHNOLOGY LLC
+ * Developer:   Irfan Gedik
+ * Created Date: 2025-12-22
+ * Last Update:  2025-12-22
+ * Version:     1.0.0
+ *
+ * Description:
+ *   Contract Test Harness
+ *
+ *   An in-memory chain for testing contracts without a running node.
+ *   Calls run on the real Q-VM against a `MemoryState`; successful calls
+ *   commit their state diff and events, failed calls leave nothing behind.
+ *
+ *       let mut chain = TestChain::new();
+ *       chain.deploy_wat("counter", COUNTER_WAT)?;
+ *       chain.set_caller("alice").set_block_height(10);
+ *       let out = chain.call("counter", "increment", vec![]).await?;
+ *
+ * License:
+ *   MIT License
+ * ============================================== */
+
+use crate::abi::{self, ContractAbi};
+use crate::developer::ContractCompiler;
+use crate::host::EmittedEvent;
+use crate::metrics::Metrics;
+use crate::state::{ContractCode, MemoryState, StateDiff, to_units};
+use crate::vm::{CallContext, ContractCallOutput, QVM, VmError};
+use crate::wasm_validator::{ValidationLimits, WasmValidator};
+use serde_json::Value as JsonValue;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Mock chain host backed by Q-VM
+pub struct TestChain {
+    vm: QVM,
+    state: Arc<MemoryState>,
+    abis: BTreeMap<String, ContractAbi>,
+    caller: Option<String>,
+    block_height: u64,
+    events: Vec<EmittedEvent>,
+}
+
+impl TestChain {
+    pub fn new() -> Self {
+        Self::with_vm(QVM::default())
+    }
+
+    /// Single worker with a custom per-call gas limit
+    pub fn with_gas_limit(gas_limit: u64) -> Self {
+        Self::with_vm(QVM::new(1, Duration::from_secs(5), gas_limit, Metrics::new()))
+    }
+
+    pub fn with_vm(vm: QVM) -> Self {
+        Self {
+            vm,
+            state: Arc::new(MemoryState::default()),
+            abis: BTreeMap::new(),
+            caller: None,
+            block_height: 0,
+            events: Vec::new(),
+        }
+    }
+
+    /// Deploys `wasm` at `address` after the same validation as the node
+    pub fn deploy(&mut self, address: &str, wasm: Vec<u8>) -> Result<&mut Self, VmError> {
+        let report = WasmValidator::validate(&wasm, &ValidationLimits::default());
+        if !report.valid {
+            return Err(report.summary().into());
+        }
+        match ContractAbi::from_wasm(&wasm)? {
+            Some(abi) => self.abis.insert(address.to_string(), abi),
+            None => self.abis.remove(address),
+        };
+
+        let state = Arc::make_mut(&mut self.state);
+        state.contracts.insert(address.to_string(), ContractCode {
+            address: address.to_string(),
+            wasm,
+            reentrancy_guard: true,
+        });
+        // Kontrat adresi transfer alabilsin
+        state.balances.entry(address.to_string()).or_insert(0);
+        Ok(self)
+    }
+
+    /// Assembles WebAssembly text, then deploys it
+    pub fn deploy_wat(&mut self, address: &str, source: &str) -> Result<&mut Self, VmError> {
+        let wasm = ContractCompiler::assemble_wat(source).map_err(|e| e.to_string())?;
+        self.deploy(address, wasm)
+    }
+
+    /// Native token balance in base units (1 QVR = 10^8); creates the account
+    pub fn set_balance(&mut self, account: &str, units: u64) -> &mut Self {
+        Arc::make_mut(&mut self.state).balances.insert(account.to_string(), units);
+        self
+    }
+
+    /// Balance in base units, `None` for unknown accounts
+    pub fn balance(&self, account: &str) -> Option<u64> {
+        self.state.balances.get(account).copied()
+    }
+
+    /// Wallet address seen by `caller` / `origin` in the following calls
+    pub fn set_caller(&mut self, caller: &str) -> &mut Self {
+        self.caller = Some(caller.to_string());
+        self
+    }
+
+    pub fn clear_caller(&mut self) -> &mut Self {
+        self.caller = None;
+        self
+    }
+
+    pub fn set_block_height(&mut self, height: u64) -> &mut Self {
+        self.block_height = height;
+        self
+    }
+
+    pub fn advance_blocks(&mut self, blocks: u64) -> &mut Self {
+        self.block_height += blocks;
+        self
+    }
+
+    pub fn block_height(&self) -> u64 {
+        self.block_height
+    }
+
+    pub fn set_storage(&mut self, contract: &str, key: &[u8], value: Vec<u8>) -> &mut Self {
+        Arc::make_mut(&mut self.state).storage.insert((contract.to_string(), key.to_vec()), value);
+        self
+    }
+
+    pub fn storage(&self, contract: &str, key: &[u8]) -> Option<&[u8]> {
+        self.state.storage.get(&(contract.to_string(), key.to_vec())).map(Vec::as_slice)
+    }
+
+    /// Runs a call and commits its state changes and events
+    pub async fn call(&mut self, contract: &str, function: &str, args: Vec<JsonValue>) -> Result<ContractCallOutput, VmError> {
+        let output = self.query(contract, function, args).await?;
+        self.commit(&output.state_diff)?;
+        self.events.extend(output.events.iter().cloned());
+        Ok(output)
+    }
+
+    /// Runs a call without committing anything
+    pub async fn query(&self, contract: &str, function: &str, args: Vec<JsonValue>) -> Result<ContractCallOutput, VmError> {
+        let code = self.state.contracts.get(contract)
+            .ok_or_else(|| format!("Contract not deployed: {}", contract))?;
+        let signature = self.abis.get(contract)
+            .and_then(|abi| abi.function(function))
+            .map(|f| f.signature());
+
+        let ctx = CallContext::new(contract, self.state.clone())
+            .with_caller(self.caller.clone())
+            .with_block_height(self.block_height);
+        self.vm.call_contract(code.wasm.clone(), function.to_string(), args, signature, ctx).await
+    }
+
+    /// Every event committed so far, in emission order
+    pub fn events(&self) -> &[EmittedEvent] {
+        &self.events
+    }
+
+    /// Committed `event` emissions of `contract`, decoded with its ABI
+    pub fn decoded_events(&self, contract: &str, event: &str) -> Result<Vec<JsonValue>, VmError> {
+        let abi_event = self.abis.get(contract)
+            .and_then(|abi| abi.event(event))
+            .ok_or_else(|| format!("Event '{}' is not in the ABI of {}", event, contract))?;
+        let topic0 = abi::event_topic(event);
+
+        self.events.iter()
+            .filter(|e| e.contract == contract && e.topics.first() == Some(&topic0))
+            .map(|e| abi::decode_event(abi_event, &e.topics, &e.data))
+            .collect()
+    }
+
+    pub fn clear_events(&mut self) {
+        self.events.clear();
+    }
+
+    fn commit(&mut self, diff: &StateDiff) -> Result<(), VmError> {
+        let state = Arc::make_mut(&mut self.state);
+        for write in &diff.storage_writes {
+            state.storage.insert((write.contract.clone(), hex::decode(&write.key)?), hex::decode(&write.value)?);
+        }
+        for delta in &diff.balance_deltas {
+            state.balances.insert(delta.account.clone(), to_units(delta.after));
+        }
+        Ok(())
+    }
+}
+
+impl Default for TestChain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // increment(): count += 1, emits Incremented{count, height}; pay_caller(amount): transfer to caller
+    const COUNTER: &str = r#"(module
+        (import "qverse" "storage_read" (func $sr (param i32 i32 i32 i32) (result i32)))
+        (import "qverse" "storage_write" (func $sw (param i32 i32 i32 i32)))
+        (import "qverse" "emit_event" (func $emit (param i32 i32 i32 i32)))
+        (import "qverse" "transfer" (func $transfer (param i32 i32 i64) (result i32)))
+        (import "qverse" "caller" (func $caller (param i32 i32) (result i32)))
+        (import "qverse" "block_height" (func $height (result i64)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "count")
+        (data (i32.const 32) "\c9\ff\44\4c\c0\91\e1\11\7c\c8\c6\60\5c\0f\2e\53\57\43\8d\ff\69\0c\d5\22\1a\db\ee\61\af\5c\62\55")
+        (func (export "increment") (result i64)
+            (drop (call $sr (i32.const 0) (i32.const 5) (i32.const 64) (i32.const 8)))
+            (i64.store (i32.const 64) (i64.add (i64.load (i32.const 64)) (i64.const 1)))
+            (call $sw (i32.const 0) (i32.const 5) (i32.const 64) (i32.const 8))
+            (i64.store (i32.const 72) (call $height))
+            (call $emit (i32.const 32) (i32.const 32) (i32.const 64) (i32.const 16))
+            (i64.load (i32.const 64)))
+        (func (export "pay_caller") (param i64) (result i32)
+            (call $transfer (i32.const 128) (call $caller (i32.const 128) (i32.const 64)) (local.get 0)))
+        (@custom "qverse.abi" "{\"version\": 1, \"events\": [{\"name\": \"Incremented\", \"fields\": [{\"name\": \"count\", \"type\": \"i64\"}, {\"name\": \"height\", \"type\": \"i64\"}]}]}"))"#;
+
+    #[tokio::test]
+    async fn test_calls_commit_state_and_events() {
+        let mut chain = TestChain::new();
+        chain.deploy_wat("counter", COUNTER).unwrap();
+        chain.set_block_height(41).advance_blocks(1);
+
+        let out = chain.call("counter", "increment", vec![]).await.unwrap();
+        assert_eq!(out.results, vec![json!(1)]);
+        let out = chain.call("counter", "increment", vec![]).await.unwrap();
+        assert_eq!(out.results, vec![json!(2)]);
+        assert_eq!(chain.storage("counter", b"count"), Some(&2i64.to_le_bytes()[..]));
+
+        // Dry runs see committed state but change nothing
+        let out = chain.query("counter", "increment", vec![]).await.unwrap();
+        assert_eq!(out.results, vec![json!(3)]);
+        assert_eq!(chain.storage("counter", b"count"), Some(&2i64.to_le_bytes()[..]));
+
+        let events = chain.decoded_events("counter", "Incremented").unwrap();
+        assert_eq!(events, vec![json!({"count": 1, "height": 42}), json!({"count": 2, "height": 42})]);
+    }
+
+    #[tokio::test]
+    async fn test_caller_and_balances() {
+        let mut chain = TestChain::new();
+        chain.deploy_wat("counter", COUNTER).unwrap();
+        chain.set_balance("counter", 500).set_balance("alice", 0).set_caller("alice");
+
+        let out = chain.call("counter", "pay_caller", vec![json!(200)]).await.unwrap();
+        assert_eq!(out.results, vec![json!(0)]);
+        assert_eq!(chain.balance("alice"), Some(200));
+        assert_eq!(chain.balance("counter"), Some(300));
+
+        // Underfunded transfer returns 1 and moves nothing
+        let out = chain.call("counter", "pay_caller", vec![json!(1_000)]).await.unwrap();
+        assert_eq!(out.results, vec![json!(1)]);
+        assert_eq!(chain.balance("alice"), Some(200));
+
+        // Unknown recipient traps; the failed call leaves no trace
+        chain.set_caller("mallory");
+        assert!(chain.call("counter", "pay_caller", vec![json!(1)]).await.is_err());
+        assert_eq!(chain.balance("counter"), Some(300));
+        assert!(chain.events().is_empty());
+    }
+}
@@ -140,6 +140,14 @@ pub fn validate_order_type(order_type: &str) -> Result<(), Box<dyn Error>> {
     }
 }
 
+/// Validates time in force
+pub fn validate_time_in_force(tif: &str) -> Result<(), Box<dyn Error>> {
+    match tif.to_uppercase().as_str() {
+        "GTC" | "IOC" | "FOK" | "GTD" => Ok(()),
+        _ => Err("Invalid time in force. Must be 'GTC', 'IOC', 'FOK', or 'GTD'".into()),
+    }
+}
+
 /// Validates vote type
 pub fn validate_vote_type(vote_type: &str) -> Result<(), Box<dyn Error>> {
     match vote_type.to_uppercase().as_str() {
@@ -172,7 +180,7 @@ mod tests {
     fn test_validate_username() {
         assert!(validate_username("alice123").is_ok());
         assert!(validate_username("ab").is_err()); // Too short
-        assert!(validate_username("a".repeat(33)).is_err()); // Too long
+        assert!(validate_username(&"a".repeat(33)).is_err()); // Too long
     }
 
     #[test]
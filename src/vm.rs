@@ -16,17 +16,181 @@
  *   MIT License
  * ============================================== */
 
-use wasmer::{Store, Module, Instance, Value, Imports};
+use crate::abi::{self, FunctionSignature, LinearMemory};
+use crate::host::{self, EmittedEvent, HostEnv};
+use crate::metrics::Metrics;
+use crate::state::{MemoryState, StateDiff, StateOverlay, StateReader};
+use serde_json::Value as JsonValue;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use std::error::Error;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use wasmer::wasmparser::Operator;
+use wasmer::sys::EngineBuilder;
+use wasmer::{CompilerConfig, Cranelift, Engine, FunctionEnv, Instance, Module, Store, Type, TypedFunction, Value};
+use wasmer_middlewares::metering::{get_remaining_points, set_remaining_points, MeteringPoints};
+use wasmer_middlewares::Metering;
 
+/// Errors crossing the worker thread boundary must be `Send`.
+pub type VmError = Box<dyn Error + Send + Sync>;
+
+/// Raw result of a contract call
+#[derive(Debug)]
+pub struct ContractOutput {
+    pub values: Vec<Value>,
+    pub gas_used: u64,
+    pub events: Vec<EmittedEvent>,
+    pub state_diff: StateDiff,
+}
+
+/// Result of an ABI-typed contract call
+///
+/// The VM never commits anything: `state_diff` is applied by the caller
+/// together with the call transaction, or simply returned for a dry run.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ContractCallOutput {
+    pub signature: FunctionSignature,
+    pub results: Vec<JsonValue>,
+    pub gas_used: u64,
+    pub events: Vec<EmittedEvent>,
+    pub state_diff: StateDiff,
+}
+
+/// Output of a nested contract-to-contract call
+#[derive(Debug)]
+pub(crate) struct NestedOutput {
+    pub output: Vec<u8>,
+    pub events: Vec<EmittedEvent>,
+}
+
+/// A call that failed, with the gas it burned before failing
+#[derive(Debug)]
+pub struct CallFailure {
+    pub error: VmError,
+    pub gas_used: u64,
+}
+
+/// Where a call runs: the executing contract, who called it and the state it sees
+#[derive(Clone)]
+pub struct CallContext {
+    pub contract: String,
+    pub caller: Option<String>, // Wallet address of the signer
+    pub block_height: u64,
+    pub gas_limit: Option<u64>, // Caller's limit, capped at the VM's
+    pub state: Arc<dyn StateReader>,
+}
+
+impl CallContext {
+    pub fn new(contract: impl Into<String>, state: Arc<dyn StateReader>) -> Self {
+        Self { contract: contract.into(), caller: None, block_height: 0, gas_limit: None, state }
+    }
+
+    pub fn with_caller(mut self, caller: Option<String>) -> Self {
+        self.caller = caller;
+        self
+    }
+
+    pub fn with_block_height(mut self, block_height: u64) -> Self {
+        self.block_height = block_height;
+        self
+    }
+
+    pub fn with_gas_limit(mut self, gas_limit: u64) -> Self {
+        self.gas_limit = Some(gas_limit);
+        self
+    }
+
+    /// Empty in-memory state, for code that is not deployed
+    pub fn ephemeral() -> Self {
+        Self::new("", Arc::new(MemoryState::default()))
+    }
+}
+
+/// Most compiled modules kept in memory; the cache is cleared once it is full
+const MAX_CACHED_MODULES: usize = 256;
+
+/// A compiled module together with the engine that compiled it
+///
+/// Instances must be created in a store on the same engine, so that host
+/// functions and the module share one signature registry.
+#[derive(Clone)]
+pub struct CompiledModule {
+    pub engine: Engine,
+    pub module: Module,
+}
+
+/// Compiled modules shared by every worker, keyed by the SHA-256 of their wasm
+///
+/// Compilation runs Cranelift, so a module is compiled once per code hash and
+/// every later call, nested ones included, only instantiates it.
+#[derive(Clone)]
+pub struct ModuleCache {
+    gas_limit: u64,
+    modules: Arc<RwLock<HashMap<[u8; 32], CompiledModule>>>,
+}
+
+impl ModuleCache {
+    fn new(gas_limit: u64) -> Self {
+        Self { gas_limit, modules: Arc::new(RwLock::new(HashMap::new())) }
+    }
+
+    /// Cached module for `wasm_bytes`, compiling it on a miss
+    pub fn get_or_compile(&self, wasm_bytes: &[u8]) -> Result<CompiledModule, VmError> {
+        let hash: [u8; 32] = Sha256::digest(wasm_bytes).into();
+        if let Some(compiled) = self.modules.read().map_err(|_| "Module cache poisoned")?.get(&hash) {
+            return Ok(compiled.clone());
+        }
+
+        // Compiled outside the lock; a concurrent miss on the same code just compiles it twice
+        let engine = metered_engine(self.gas_limit);
+        let module = Module::new(&engine, wasm_bytes)?;
+        let compiled = CompiledModule { engine, module };
+        let mut modules = self.modules.write().map_err(|_| "Module cache poisoned")?;
+        if modules.len() >= MAX_CACHED_MODULES {
+            modules.clear();
+        }
+        modules.insert(hash, compiled.clone());
+        Ok(compiled)
+    }
+
+    /// Number of compiled modules held
+    pub fn len(&self) -> usize {
+        self.modules.read().map_or(0, |modules| modules.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Q-VM execution pool
+///
+/// Every call gets its own `Store` and `Instance` and runs on a blocking
+/// worker thread, so independent contracts execute in parallel. Cloning is
+/// cheap: clones share the module cache and the worker pool.
+#[derive(Clone)]
 pub struct QVM {
-    store: Store,
+    modules: ModuleCache,
+    workers: Arc<Semaphore>,
+    call_timeout: Duration,
+    gas_limit: u64,
+    metrics: Metrics,
 }
 
 impl QVM {
-    pub fn new() -> Self {
+    /// workers: Aynı anda çalışabilecek kontrat sayısı
+    /// call_timeout: Çağrı başına süre sınırı
+    /// gas_limit: Çağrı başına gaz sınırı (sonsuz döngüleri keser)
+    pub fn new(workers: usize, call_timeout: Duration, gas_limit: u64, metrics: Metrics) -> Self {
         Self {
-            store: Store::default(),
+            modules: ModuleCache::new(gas_limit),
+            workers: Arc::new(Semaphore::new(workers.max(1))),
+            call_timeout,
+            gas_limit,
+            metrics,
         }
     }
 
@@ -34,31 +198,455 @@ impl QVM {
     /// wasm_bytes: Derlenmiş WASM kodu
     /// function: Çağrılacak fonksiyon adı (örn: "transfer")
     /// args: Parametreler
-    pub fn execute_contract(&mut self, wasm_bytes: &[u8], function: &str, args: Vec<Value>) -> Result<Box<[Value]>, Box<dyn Error>> {
-        // 1. Modülü Derle (JIT Compilation - Anlık Derleme)
-        // Bu adım USDTgVerse'den daha hızlıdır çünkü Wasmer'ın Cranelift motorunu kullanıyoruz.
-        let module = Module::new(&self.store, wasm_bytes)?;
+    pub async fn execute_contract(&self, wasm_bytes: Vec<u8>, function: String, args: Vec<Value>) -> Result<ContractOutput, VmError> {
+        let modules = self.modules.clone();
+        let gas_limit = self.gas_limit;
+
+        self.run(move || Self::call(&modules, &wasm_bytes, &function, &args, CallContext::ephemeral(), gas_limit)).await
+    }
+
+    /// Waits for a free worker, then runs `job` on the blocking pool.
+    ///
+    /// A timed-out job cannot be interrupted, but metering guarantees it stops
+    /// once the gas limit is spent; its worker slot is released only then.
+    async fn run<T, F>(&self, job: F) -> Result<T, VmError>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> Result<T, VmError> + Send + 'static,
+    {
+        let queued = GaugeGuard::new(&self.metrics.contract_queue_depth);
+        let permit = self.workers.clone().acquire_owned().await
+            .map_err(|_| "Q-VM worker pool is closed")?;
+        drop(queued);
+
+        let active = GaugeGuard::new(&self.metrics.active_contract_calls);
+        let handle = tokio::task::spawn_blocking(move || {
+            let result = job();
+            drop(active);
+            drop(permit);
+            result
+        });
+
+        match tokio::time::timeout(self.call_timeout, handle).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(e)) => Err(format!("Contract worker failed: {}", e).into()),
+            Err(_) => Err(format!("Contract execution timed out after {}ms", self.call_timeout.as_millis()).into()),
+        }
+    }
+
+    /// ABI-typed call: JSON arguments in, JSON results out
+    /// signature: Bytes/String tiplerini içeren imza; yoksa export tipinden okunur
+    pub async fn call_contract(
+        &self,
+        wasm_bytes: Vec<u8>,
+        function: String,
+        args: Vec<JsonValue>,
+        signature: Option<FunctionSignature>,
+        ctx: CallContext,
+    ) -> Result<ContractCallOutput, VmError> {
+        self.call_contract_metered(wasm_bytes, function, args, signature, ctx).await
+            .map_err(|failure| failure.error)
+    }
+
+    /// Like `call_contract`, but failures report the gas they used (for fee charging).
+    /// A call that times out is charged its whole limit.
+    pub async fn call_contract_metered(
+        &self,
+        wasm_bytes: Vec<u8>,
+        function: String,
+        args: Vec<JsonValue>,
+        signature: Option<FunctionSignature>,
+        ctx: CallContext,
+    ) -> Result<ContractCallOutput, CallFailure> {
+        let modules = self.modules.clone();
+        let gas_limit = ctx.gas_limit.map_or(self.gas_limit, |limit| limit.min(self.gas_limit));
+
+        match self.run(move || Ok(Self::call_typed(&modules, &wasm_bytes, &function, &args, signature, ctx, gas_limit))).await {
+            Ok(result) => result,
+            Err(error) => Err(CallFailure { error, gas_used: gas_limit }),
+        }
+    }
 
-        // 2. Import Nesnesi (Dış dünya ile iletişim için)
-        // Kontratın blockchain verilerine erişmesi için gerekli API'lar buraya eklenecek.
-        let import_object = Imports::new();
+    /// Per-call gas ceiling
+    pub fn gas_limit(&self) -> u64 {
+        self.gas_limit
+    }
+
+    /// Compiled modules shared by this VM's workers
+    pub fn modules(&self) -> &ModuleCache {
+        &self.modules
+    }
 
-        // 3. Sanal Makineyi Başlat (Instance)
-        let instance = Instance::new(&mut self.store, &module, &import_object)?;
+    fn call(modules: &ModuleCache, wasm_bytes: &[u8], function: &str, args: &[Value], ctx: CallContext, gas_limit: u64) -> Result<ContractOutput, VmError> {
+        let (mut store, instance, env) = Self::instantiate(modules, wasm_bytes, Self::host_env(modules, ctx), gas_limit)?;
 
-        // 4. Fonksiyonu Bul
+        // Fonksiyonu Bul ve Çalıştır
         let func = instance.exports.get_function(function)?;
+        let result = func.call(&mut store, args);
+        let gas_used = Self::gas_used(&mut store, &instance, gas_limit)?;
+
+        let host = env.as_mut(&mut store);
+        Ok(ContractOutput {
+            values: result?.into_vec(),
+            gas_used,
+            events: std::mem::take(&mut host.events),
+            state_diff: host.state().diff(),
+        })
+    }
+
+    fn call_typed(
+        modules: &ModuleCache,
+        wasm_bytes: &[u8],
+        function: &str,
+        args: &[JsonValue],
+        signature: Option<FunctionSignature>,
+        ctx: CallContext,
+        gas_limit: u64,
+    ) -> Result<ContractCallOutput, CallFailure> {
+        // Setup and argument errors are not charged
+        let unmetered = |error: VmError| CallFailure { error, gas_used: 0 };
+        let (mut store, instance, env) = Self::instantiate(modules, wasm_bytes, Self::host_env(modules, ctx), gas_limit)
+            .map_err(unmetered)?;
+
+        let func = instance.exports.get_function(function).map_err(|e| unmetered(e.into()))?.clone();
+        let ty = func.ty(&store);
+        let signature = match signature {
+            Some(sig) => {
+                sig.check_against(&ty).map_err(unmetered)?;
+                sig
+            }
+            None => FunctionSignature::from_wasm(function, &ty).map_err(unmetered)?,
+        };
+
+        let values = {
+            let mut memory = InstanceMemory { store: &mut store, instance: &instance };
+            abi::encode_args(&signature, args, &mut memory).map_err(unmetered)?
+        };
+        let result = func.call(&mut store, &values);
+        let gas_used = Self::gas_used(&mut store, &instance, gas_limit)
+            .map_err(|error| CallFailure { error, gas_used: gas_limit })?;
+        let failed = |error: VmError| CallFailure { error, gas_used };
+
+        let mut memory = InstanceMemory { store: &mut store, instance: &instance };
+        let values = result.map_err(|e| failed(e.into()))?;
+        let results = abi::decode_results(&signature, &values, &mut memory).map_err(failed)?;
+
+        let host = env.as_mut(&mut store);
+        let events = std::mem::take(&mut host.events);
+        let state_diff = host.state().diff();
+
+        Ok(ContractCallOutput { signature, results, gas_used, events, state_diff })
+    }
+
+    /// Nested call from `qverse.call_contract`, run on the caller's worker thread
+    ///
+    /// Returns the raw output and events, plus the gas spent either way.
+    pub(crate) fn call_nested(
+        modules: &ModuleCache,
+        wasm_bytes: &[u8],
+        function: &str,
+        input: &[u8],
+        env: HostEnv,
+        gas_limit: u64,
+    ) -> (Result<NestedOutput, VmError>, u64) {
+        let (mut store, instance, env) = match Self::instantiate(modules, wasm_bytes, env, gas_limit) {
+            Ok(parts) => parts,
+            Err(e) => return (Err(e), 0),
+        };
+
+        let result = Self::call_raw(&mut store, &instance, function, input);
+        let gas_used = match get_remaining_points(&mut store, &instance) {
+            MeteringPoints::Remaining(left) => gas_limit - left,
+            MeteringPoints::Exhausted => gas_limit,
+        };
+
+        let result = result.map(|output| NestedOutput { output, events: std::mem::take(&mut env.as_mut(&mut store).events) });
+        (result, gas_used)
+    }
+
+    /// Byte-level calling convention between contracts:
+    /// `()` or `(ptr, len)` in, nothing or packed `(ptr << 32) | len` out
+    fn call_raw(store: &mut Store, instance: &Instance, function: &str, input: &[u8]) -> Result<Vec<u8>, VmError> {
+        let func = instance.exports.get_function(function)?.clone();
+        let ty = func.ty(&*store);
 
-        // 5. Çalıştır
-        let result = func.call(&mut self.store, &args)?;
+        let args = match ty.params() {
+            [] if input.is_empty() => Vec::new(),
+            [Type::I32, Type::I32] => {
+                let ptr = InstanceMemory { store: &mut *store, instance }.write_bytes(input)?;
+                vec![Value::I32(ptr), Value::I32(input.len() as i32)]
+            }
+            _ => return Err(format!("'{}' cannot be called by contracts: expected () or (i32 ptr, i32 len) params", function).into()),
+        };
 
-        Ok(result)
+        let values = func.call(&mut *store, &args)?;
+        match (ty.results(), values.first()) {
+            ([], _) => Ok(Vec::new()),
+            ([Type::I64], Some(Value::I64(packed))) => {
+                let packed = *packed as u64;
+                InstanceMemory { store, instance }.read_bytes((packed >> 32) as u32, packed as u32)
+            }
+            _ => Err(format!("'{}' cannot be called by contracts: expected no result or packed i64 bytes", function).into()),
+        }
+    }
+
+    fn host_env(modules: &ModuleCache, ctx: CallContext) -> HostEnv {
+        HostEnv::new(modules.clone(), ctx.contract, ctx.caller, ctx.block_height, StateOverlay::new(ctx.state))
+    }
+
+    /// Instantiates the (cached) module in a fresh, isolated store and sets its gas budget
+    fn instantiate(modules: &ModuleCache, wasm_bytes: &[u8], host_env: HostEnv, gas_limit: u64) -> Result<(Store, Instance, FunctionEnv<HostEnv>), VmError> {
+        // 1. Modülü Al (JIT derlemesi kod hash'i başına bir kez yapılır)
+        let CompiledModule { engine, module } = modules.get_or_compile(wasm_bytes)?;
+
+        // 2. Her çağrı için izole Store, modülün kendi motoru üzerinde
+        let mut store = Store::new(engine);
+
+        // 3. Import Nesnesi (Dış dünya ile iletişim için)
+        // Kontrat blockchain verilerine yalnızca `qverse` host fonksiyonlarıyla erişir.
+        let env = FunctionEnv::new(&mut store, host_env);
+        let import_object = host::imports(&mut store, &env);
+
+        // 4. Sanal Makineyi Başlat (Instance)
+        let instance = Instance::new(&mut store, &module, &import_object)?;
+        let host = env.as_mut(&mut store);
+        if let Ok(memory) = instance.exports.get_memory("memory") {
+            host.memory = Some(memory.clone());
+        }
+        host.instance = Some(instance.clone());
+        set_remaining_points(&mut store, &instance, gas_limit);
+
+        Ok((store, instance, env))
+    }
+
+    fn gas_used(store: &mut Store, instance: &Instance, gas_limit: u64) -> Result<u64, VmError> {
+        match get_remaining_points(store, instance) {
+            MeteringPoints::Remaining(left) => Ok(gas_limit - left),
+            MeteringPoints::Exhausted => Err(format!("Out of gas (limit: {})", gas_limit).into()),
+        }
     }
 
     /// Gaz (Gas) Maliyeti Hesapla
-    /// Sonsuz döngüleri engellemek için Metering (Ölçüm) ekleyeceğiz.
+    /// Deploy öncesi kaba tahmin; gerçek tüketim metering ile ölçülür.
     pub fn calculate_gas(&self, wasm_bytes: &[u8]) -> u64 {
         // Basit bir uzunluk bazlı maliyet (Geliştirilecek)
         wasm_bytes.len() as u64 * 10
     }
 }
+
+impl Default for QVM {
+    fn default() -> Self {
+        Self::new(num_cpus::get(), Duration::from_millis(5_000), 10_000_000, Metrics::new())
+    }
+}
+
+/// Linear memory of a live instance, using the contract's own `alloc` export
+struct InstanceMemory<'a> {
+    store: &'a mut Store,
+    instance: &'a Instance,
+}
+
+impl LinearMemory for InstanceMemory<'_> {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<i32, VmError> {
+        let alloc: TypedFunction<i32, i32> = self.instance.exports
+            .get_typed_function(&*self.store, "alloc")
+            .map_err(|_| "Contract must export 'alloc(i32) -> i32' to accept bytes/string arguments")?;
+        let ptr = alloc.call(&mut *self.store, bytes.len() as i32)?;
+
+        let memory = self.instance.exports.get_memory("memory")?;
+        memory.view(&*self.store).write(ptr as u32 as u64, bytes)?;
+        Ok(ptr)
+    }
+
+    fn read_bytes(&mut self, ptr: u32, len: u32) -> Result<Vec<u8>, VmError> {
+        let memory = self.instance.exports.get_memory("memory")?;
+        let mut buf = vec![0u8; len as usize];
+        memory.view(&*self.store).read(ptr as u64, &mut buf)?;
+        Ok(buf)
+    }
+}
+
+/// Engine for compiling a single module
+///
+/// A `Metering` middleware records the globals it injects into the one module it
+/// instruments, so every module needs its own; the per-call limit is set later.
+fn metered_engine(gas_limit: u64) -> Engine {
+    // Metering: Her WASM komutu 1 gaz harcar
+    let metering = Arc::new(Metering::new(gas_limit, operator_cost));
+    let mut compiler = Cranelift::default();
+    compiler.push_middleware(metering);
+    Engine::from(EngineBuilder::new(compiler))
+}
+
+fn operator_cost(_operator: &Operator) -> u64 {
+    1
+}
+
+/// Increments a gauge for as long as it is alive
+struct GaugeGuard(Arc<AtomicU64>);
+
+impl GaugeGuard {
+    fn new(gauge: &Arc<AtomicU64>) -> Self {
+        gauge.fetch_add(1, Ordering::Relaxed);
+        Self(gauge.clone())
+    }
+}
+
+impl Drop for GaugeGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::ContractCode;
+    use serde_json::json;
+
+    const CALLER: &str = r#"(module
+        (import "qverse" "storage_write" (func $sw (param i32 i32 i32 i32)))
+        (import "qverse" "call_contract" (func $call (param i32 i32 i32 i32 i32 i32 i64 i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "callee")
+        (data (i32.const 16) "fail")
+        (data (i32.const 32) "ok")
+        (data (i32.const 48) "a")
+        (func (export "run") (result i32 i32)
+            (call $sw (i32.const 48) (i32.const 1) (i32.const 48) (i32.const 1))
+            (call $call (i32.const 0) (i32.const 6) (i32.const 16) (i32.const 4) (i32.const 0) (i32.const 0) (i64.const 100000) (i32.const 0) (i32.const 0))
+            (call $call (i32.const 0) (i32.const 6) (i32.const 32) (i32.const 2) (i32.const 0) (i32.const 0) (i64.const 100000) (i32.const 0) (i32.const 0))))"#;
+
+    const CALLEE: &str = r#"(module
+        (import "qverse" "storage_write" (func $sw (param i32 i32 i32 i32)))
+        (import "qverse" "call_contract" (func $call (param i32 i32 i32 i32 i32 i32 i64 i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "b")
+        (data (i32.const 8) "r")
+        (data (i32.const 16) "caller")
+        (data (i32.const 32) "run")
+        (func (export "fail")
+            (call $sw (i32.const 0) (i32.const 1) (i32.const 0) (i32.const 1))
+            unreachable)
+        (func (export "ok")
+            (call $sw (i32.const 0) (i32.const 1) (i32.const 0) (i32.const 1))
+            (i32.store8 (i32.const 48)
+                (call $call (i32.const 16) (i32.const 6) (i32.const 32) (i32.const 3) (i32.const 0) (i32.const 0) (i64.const 100000) (i32.const 0) (i32.const 0)))
+            (call $sw (i32.const 8) (i32.const 1) (i32.const 48) (i32.const 1))))"#;
+
+    #[tokio::test]
+    async fn test_nested_calls_roll_back_and_block_reentry() {
+        let mut state = MemoryState::default();
+        for (address, wat) in [("caller", CALLER), ("callee", CALLEE)] {
+            state.contracts.insert(address.to_string(), ContractCode {
+                address: address.to_string(),
+                wasm: wasmer::wat2wasm(wat.as_bytes()).unwrap().into_owned(),
+                reentrancy_guard: true,
+            });
+        }
+        let wasm = state.contracts["caller"].wasm.clone();
+
+        let vm = QVM::new(1, Duration::from_secs(5), 1_000_000, Metrics::new());
+        let ctx = CallContext::new("caller", Arc::new(state));
+        let output = vm.call_contract(wasm.clone(), "run".into(), vec![], None, ctx.clone()).await.unwrap();
+
+        // fail() reverted, ok() succeeded with empty output
+        assert_eq!(output.results, vec![json!(host::CALL_REVERTED), json!(0)]);
+
+        // The reverted write to "b" is gone, ok() rewrote it; reentry into caller returned -2 (0xfe)
+        let writes: Vec<_> = output.state_diff.storage_writes.iter()
+            .map(|w| (w.contract.as_str(), w.key.as_str(), w.value.as_str()))
+            .collect();
+        assert_eq!(writes, vec![("callee", "62", "62"), ("callee", "72", "fe"), ("caller", "61", "61")]);
+
+        // Caller and callee were compiled once and are reused by the next call
+        assert_eq!(vm.modules().len(), 2);
+        vm.call_contract(wasm, "run".into(), vec![], None, ctx).await.unwrap();
+        assert_eq!(vm.modules().len(), 2);
+    }
+
+    /// Burns one iteration of gas-metered work per step; `tag` keeps the code hashes apart
+    fn spinner(tag: i32) -> Vec<u8> {
+        let wat = format!(r#"(module
+            (global $tag i32 (i32.const {}))
+            (func (export "spin") (param $n i64)
+                (loop $l
+                    (local.set $n (i64.sub (local.get $n) (i64.const 1)))
+                    (br_if $l (i64.ne (local.get $n) (i64.const 0))))))"#, tag);
+        wasmer::wat2wasm(wat.as_bytes()).unwrap().into_owned()
+    }
+
+    fn spin(vm: &QVM, wasm: Vec<u8>, steps: u64) -> tokio::task::JoinHandle<Result<ContractCallOutput, CallFailure>> {
+        let vm = vm.clone();
+        tokio::spawn(async move {
+            vm.call_contract_metered(wasm, "spin".into(), vec![json!(steps)], None, CallContext::ephemeral()).await
+        })
+    }
+
+    async fn wait_for(gauge: &Arc<AtomicU64>, value: u64) -> bool {
+        for _ in 0..5_000 {
+            if gauge.load(Ordering::Relaxed) == value {
+                return true;
+            }
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        false
+    }
+
+    #[tokio::test]
+    async fn test_independent_contracts_run_in_parallel() {
+        let metrics = Metrics::new();
+        let vm = QVM::new(2, Duration::from_secs(30), 10_000_000_000, metrics.clone());
+
+        let a = spin(&vm, spinner(1), 200_000_000);
+        let b = spin(&vm, spinner(2), 200_000_000);
+
+        // Both calls hold a worker at the same time
+        assert!(wait_for(&metrics.active_contract_calls, 2).await);
+        assert!(a.await.unwrap().is_ok());
+        assert!(b.await.unwrap().is_ok());
+        assert_eq!(vm.modules().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_queue_depth_rises_and_falls() {
+        let metrics = Metrics::new();
+        let vm = QVM::new(1, Duration::from_secs(30), 10_000_000_000, metrics.clone());
+
+        let running = spin(&vm, spinner(1), 200_000_000);
+        assert!(wait_for(&metrics.active_contract_calls, 1).await);
+        let queued = spin(&vm, spinner(1), 1);
+
+        // The second call waits for the only worker
+        assert!(wait_for(&metrics.contract_queue_depth, 1).await);
+        assert!(running.await.unwrap().is_ok());
+        assert!(queued.await.unwrap().is_ok());
+        assert_eq!(metrics.contract_queue_depth.load(Ordering::Relaxed), 0);
+        assert_eq!(metrics.active_contract_calls.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn test_timeout_charges_the_whole_gas_limit() {
+        let metrics = Metrics::new();
+        let vm = QVM::new(1, Duration::from_millis(10), 300_000_000, metrics.clone());
+
+        let failure = spin(&vm, spinner(1), u64::MAX >> 1).await.unwrap().unwrap_err();
+        assert!(failure.error.to_string().contains("timed out"));
+        assert_eq!(failure.gas_used, vm.gas_limit());
+
+        // The worker is released once metering stops the runaway call
+        assert!(wait_for(&metrics.active_contract_calls, 0).await);
+    }
+
+    #[tokio::test]
+    async fn test_metering_stops_out_of_gas_calls() {
+        let vm = QVM::new(1, Duration::from_secs(5), 1_000_000, Metrics::new());
+
+        let output = spin(&vm, spinner(1), 10).await.unwrap().unwrap();
+        assert!(output.gas_used > 0 && output.gas_used < 1_000);
+
+        let failure = spin(&vm, spinner(1), 10_000_000).await.unwrap().unwrap_err();
+        assert!(failure.error.to_string().contains("Out of gas"));
+        assert_eq!(failure.gas_used, 1_000_000);
+    }
+}
@@ -16,7 +16,7 @@
  *   MIT License
  * ============================================== */
 
-use crate::models::{MultiSigWallet, MultiSigSigner, PaymentRequest};
+use crate::models::{MultiSigWallet, PaymentRequest};
 use std::error::Error;
 use uuid::Uuid;
 
@@ -73,6 +73,7 @@ impl QRCodeGenerator {
     }
 
     /// Parse QR code data
+    #[allow(clippy::type_complexity)]
     pub fn parse_payment_qr(qr_data: &str) -> Result<(String, String, f64, Option<String>), Box<dyn Error>> {
         // Simple parsing (in production, use proper URL parsing)
         if !qr_data.starts_with("qverse://pay?") {
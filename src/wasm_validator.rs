@@ -0,0 +1,292 @@
+/* ==============================================
+ * File:        src/wasm_validator.rs
+ * Author:      USDTG GROUP TECHNOLOGY LLC
+ * Developer:   Irfan Gedik
+ * Created Date: 2025-12-22
+ * Last Update:  2025-12-22
+ * Version:     1.0.0
+ *
+ * Description:
+ *   WASM Module Validator
+ *
+ *   Deploy-time checks on contract bytecode: only host ABI imports,
+ *   no floating-point instructions (non-deterministic NaN bits across
+ *   nodes), and hard caps on memory, tables, functions and code size.
+ *   Every violation is collected so developers get one complete report.
+ *
+ * License:
+ *   MIT License
+ * ============================================== */
+
+use crate::host::{HOST_FUNCTIONS, HOST_MODULE};
+use serde::Serialize;
+use wasmer::wasmparser::{self, Parser, Payload, TypeRef};
+
+/// Resource caps for deployable modules
+#[derive(Debug, Clone)]
+pub struct ValidationLimits {
+    pub max_memory_pages: u64, // 64 KiB pages
+    pub max_table_size: u32,
+    pub max_functions: u32,
+    pub max_code_size: usize, // bytes, whole module
+}
+
+impl Default for ValidationLimits {
+    fn default() -> Self {
+        Self {
+            max_memory_pages: 256, // 16 MiB
+            max_table_size: 10_000,
+            max_functions: 10_000,
+            max_code_size: 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ViolationKind {
+    Malformed,
+    ForbiddenImport,
+    FloatingPoint,
+    MemoryLimit,
+    TableLimit,
+    FunctionLimit,
+    CodeSize,
+}
+
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct Violation {
+    pub kind: ViolationKind,
+    pub message: String,
+    pub function_index: Option<u32>,
+    pub offset: Option<usize>, // byte offset in the module
+}
+
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct ValidationReport {
+    pub valid: bool,
+    pub code_size: usize,
+    pub function_count: u32,
+    pub memory_pages: u64,
+    pub table_size: u32,
+    pub violations: Vec<Violation>,
+}
+
+impl ValidationReport {
+    /// One-line summary for error messages
+    pub fn summary(&self) -> String {
+        let messages: Vec<&str> = self.violations.iter().map(|v| v.message.as_str()).collect();
+        format!("WASM module rejected ({} violation(s)): {}", self.violations.len(), messages.join("; "))
+    }
+}
+
+/// Contract bytecode validator
+pub struct WasmValidator;
+
+impl WasmValidator {
+    pub fn validate(wasm_bytes: &[u8], limits: &ValidationLimits) -> ValidationReport {
+        let mut report = ValidationReport {
+            valid: false,
+            code_size: wasm_bytes.len(),
+            function_count: 0,
+            memory_pages: 0,
+            table_size: 0,
+            violations: Vec::new(),
+        };
+
+        if wasm_bytes.len() > limits.max_code_size {
+            report.violations.push(Violation {
+                kind: ViolationKind::CodeSize,
+                message: format!("Module is {} bytes (max {})", wasm_bytes.len(), limits.max_code_size),
+                function_index: None,
+                offset: None,
+            });
+        }
+
+        // Yapısal doğrulama: geçersiz modülde diğer kontroller anlamsız
+        if let Err(e) = wasmparser::validate(wasm_bytes) {
+            report.violations.push(Violation {
+                kind: ViolationKind::Malformed,
+                message: format!("Invalid WASM module: {}", e.message()),
+                function_index: None,
+                offset: Some(e.offset()),
+            });
+            return report;
+        }
+
+        if let Err(e) = Self::inspect(wasm_bytes, limits, &mut report) {
+            report.violations.push(Violation {
+                kind: ViolationKind::Malformed,
+                message: format!("Invalid WASM module: {}", e.message()),
+                function_index: None,
+                offset: Some(e.offset()),
+            });
+        }
+
+        report.valid = report.violations.is_empty();
+        report
+    }
+
+    fn inspect(wasm_bytes: &[u8], limits: &ValidationLimits, report: &mut ValidationReport) -> Result<(), wasmparser::BinaryReaderError> {
+        let mut imported_functions = 0u32;
+        let mut code_index = 0u32;
+
+        for payload in Parser::new(0).parse_all(wasm_bytes) {
+            match payload? {
+                Payload::ImportSection(reader) => {
+                    for import in reader.into_iter_with_offsets() {
+                        let (offset, import) = import?;
+                        let allowed = match import.ty {
+                            TypeRef::Func(_) => {
+                                imported_functions += 1;
+                                import.module == HOST_MODULE && HOST_FUNCTIONS.contains(&import.name)
+                            }
+                            _ => false,
+                        };
+                        if !allowed {
+                            report.violations.push(Violation {
+                                kind: ViolationKind::ForbiddenImport,
+                                message: format!("Import '{}.{}' is not part of the host ABI", import.module, import.name),
+                                function_index: None,
+                                offset: Some(offset),
+                            });
+                        }
+                    }
+                }
+                Payload::FunctionSection(reader) => {
+                    report.function_count = reader.count();
+                    if reader.count() > limits.max_functions {
+                        report.violations.push(Violation {
+                            kind: ViolationKind::FunctionLimit,
+                            message: format!("Module defines {} functions (max {})", reader.count(), limits.max_functions),
+                            function_index: None,
+                            offset: Some(reader.original_position()),
+                        });
+                    }
+                }
+                Payload::TableSection(reader) => {
+                    for table in reader.into_iter_with_offsets() {
+                        let (offset, table) = table?;
+                        let size = table.ty.maximum.unwrap_or(table.ty.initial);
+                        report.table_size = report.table_size.max(size);
+                        if size > limits.max_table_size {
+                            report.violations.push(Violation {
+                                kind: ViolationKind::TableLimit,
+                                message: format!("Table allows {} elements (max {})", size, limits.max_table_size),
+                                function_index: None,
+                                offset: Some(offset),
+                            });
+                        }
+                    }
+                }
+                Payload::MemorySection(reader) => {
+                    for memory in reader.into_iter_with_offsets() {
+                        let (offset, memory) = memory?;
+                        report.memory_pages = report.memory_pages.max(memory.initial);
+                        if memory.initial > limits.max_memory_pages
+                            || memory.maximum.is_some_and(|max| max > limits.max_memory_pages)
+                        {
+                            report.violations.push(Violation {
+                                kind: ViolationKind::MemoryLimit,
+                                message: format!(
+                                    "Memory declares {} initial / {:?} maximum pages (max {})",
+                                    memory.initial, memory.maximum, limits.max_memory_pages
+                                ),
+                                function_index: None,
+                                offset: Some(offset),
+                            });
+                        }
+                    }
+                }
+                Payload::CodeSectionEntry(body) => {
+                    let function_index = imported_functions + code_index;
+                    code_index += 1;
+
+                    let mut float_ops = 0usize;
+                    let mut first: Option<(String, usize)> = None;
+                    for op in body.get_operators_reader()?.into_iter_with_offsets() {
+                        let (op, offset) = op?;
+                        let name = format!("{:?}", op);
+                        if is_float_op(&name) {
+                            float_ops += 1;
+                            if first.is_none() {
+                                let name = name.split([' ', '{']).next().unwrap_or_default().to_string();
+                                first = Some((name, offset));
+                            }
+                        }
+                    }
+
+                    // Fonksiyon başına tek kayıt: rapor binlerce satıra şişmesin
+                    if let Some((name, offset)) = first {
+                        report.violations.push(Violation {
+                            kind: ViolationKind::FloatingPoint,
+                            message: format!(
+                                "Function {} uses {} floating-point instruction(s), first: {}",
+                                function_index, float_ops, name
+                            ),
+                            function_index: Some(function_index),
+                            offset: Some(offset),
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Float arithmetic, conversions, constants, loads/stores and SIMD lanes
+/// all carry `F32`/`F64` in their operator name (e.g. `I32TruncF64S`)
+fn is_float_op(name: &str) -> bool {
+    name.contains("F32") || name.contains("F64")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(wat: &str) -> ValidationReport {
+        let wasm = wasmer::wat2wasm(wat.as_bytes()).unwrap();
+        WasmValidator::validate(&wasm, &ValidationLimits::default())
+    }
+
+    fn kinds(report: &ValidationReport) -> Vec<ViolationKind> {
+        report.violations.iter().map(|v| v.kind).collect()
+    }
+
+    #[test]
+    fn test_accepts_host_imports() {
+        let report = validate(r#"(module
+            (import "qverse" "emit_event" (func (param i32 i32 i32 i32)))
+            (memory (export "memory") 1 16)
+            (func (export "add") (param i32 i32) (result i32) local.get 0 local.get 1 i32.add))"#);
+        assert!(report.valid, "{:?}", report.violations);
+        assert_eq!(report.function_count, 1);
+    }
+
+    #[test]
+    fn test_rejects_foreign_imports_and_floats() {
+        let report = validate(r#"(module
+            (import "env" "random" (func (result i32)))
+            (func (param f64) (result f64) local.get 0 f64.const 2 f64.mul)
+            (func (param i32) (result i32) local.get 0))"#);
+        assert!(!report.valid);
+        assert_eq!(kinds(&report), vec![ViolationKind::ForbiddenImport, ViolationKind::FloatingPoint]);
+        assert_eq!(report.violations[1].function_index, Some(1));
+        assert!(report.violations[1].message.contains("2 floating-point"));
+    }
+
+    #[test]
+    fn test_enforces_limits() {
+        let report = validate(r#"(module (memory 300) (table 20000 funcref))"#);
+        assert_eq!(kinds(&report), vec![ViolationKind::TableLimit, ViolationKind::MemoryLimit]);
+    }
+
+    #[test]
+    fn test_malformed() {
+        let report = WasmValidator::validate(b"mock_wasm_bytecode", &ValidationLimits::default());
+        assert_eq!(kinds(&report), vec![ViolationKind::Malformed]);
+    }
+}
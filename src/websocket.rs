@@ -56,6 +56,12 @@ impl Actor for QVerseWebSocket {
     }
 }
 
+impl Default for QVerseWebSocket {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl QVerseWebSocket {
     pub fn new() -> Self {
         Self {
@@ -75,7 +81,7 @@ impl QVerseWebSocket {
         });
     }
 
-    fn handle_message(&mut self, msg: String, ctx: &mut ws::WebsocketContext<Self>) {
+    fn handle_message(&mut self, msg: String, _ctx: &mut ws::WebsocketContext<Self>) {
         match serde_json::from_str::<WsMessage>(&msg) {
             Ok(ws_msg) => {
                 match ws_msg.action.as_str() {
@@ -104,6 +110,7 @@ impl QVerseWebSocket {
         }
     }
 
+    #[allow(dead_code)] // Not wired to a publisher yet
     fn broadcast_event(&self, event: &str, data: serde_json::Value, ctx: &mut ws::WebsocketContext<Self>) {
         if self.subscribed_events.contains(&event.to_string()) {
             let msg = serde_json::json!({